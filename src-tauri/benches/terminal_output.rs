@@ -0,0 +1,120 @@
+//! Throughput of the terminal output path: reads from a blocking source go
+//! through a `Pump` to a view that acknowledges what it renders.
+//!
+//! Reports, per scenario, the throughput, how many messages crossed the IPC
+//! boundary (before batching it was one per read) and the most output ever
+//! in flight to the view.
+//!
+//! Run with `cargo bench --bench terminal_output`.
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reach_lib::stream::batch::{Pump, Sink};
+use reach_lib::stream::flow::Flow;
+
+const MIB: usize = 1024 * 1024;
+
+/// Stands in for the webview: batches go to a "renderer" thread that takes
+/// `render_ns_per_byte` to parse them, then acknowledges.
+struct View {
+    tx: mpsc::Sender<Vec<u8>>,
+    flow: Arc<Flow>,
+    messages: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Sink for View {
+    fn send(&mut self, batch: Vec<u8>) -> bool {
+        self.flow.sent(batch.len());
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        let now = self.in_flight.fetch_add(batch.len(), Ordering::Relaxed) + batch.len();
+        self.peak.fetch_max(now, Ordering::Relaxed);
+        self.tx.send(batch).is_ok()
+    }
+
+    fn flow(&self) -> &Flow {
+        &self.flow
+    }
+}
+
+struct Report {
+    elapsed: Duration,
+    messages: usize,
+    peak: usize,
+}
+
+fn run(total: usize, read_size: usize, render_ns_per_byte: u64) -> Report {
+    let flow = Arc::new(Flow::new());
+    let messages = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+
+    let renderer = {
+        let flow = flow.clone();
+        let in_flight = in_flight.clone();
+        std::thread::spawn(move || {
+            let mut rendered = 0usize;
+            for batch in rx {
+                if render_ns_per_byte > 0 {
+                    let until = Instant::now() + Duration::from_nanos(render_ns_per_byte * batch.len() as u64);
+                    while Instant::now() < until {
+                        std::hint::spin_loop();
+                    }
+                }
+                rendered += black_box(batch.len());
+                in_flight.fetch_sub(batch.len(), Ordering::Relaxed);
+                flow.ack(batch.len());
+            }
+            rendered
+        })
+    };
+
+    let pump = Pump::spawn("bench-pump".into()).expect("spawn pump");
+    pump.handle().attach(View { tx, flow, messages: messages.clone(), peak: peak.clone(), in_flight });
+
+    let chunk = vec![b'x'; read_size];
+    let start = Instant::now();
+    let mut sent = 0;
+    while sent < total {
+        pump.send(chunk.clone());
+        sent += read_size;
+    }
+    pump.finish();
+    let rendered = renderer.join().expect("renderer");
+    let elapsed = start.elapsed();
+    assert_eq!(rendered, sent);
+
+    Report { elapsed, messages: messages.load(Ordering::Relaxed), peak: peak.load(Ordering::Relaxed) }
+}
+
+fn main() {
+    let scenarios: &[(&str, usize, usize, u64)] = &[
+        ("cat, fast view", 256 * MIB, 4096, 0),
+        ("cat, 64-byte reads", 32 * MIB, 64, 0),
+        ("cat, slow view (~100 MiB/s)", 64 * MIB, 4096, 10),
+        ("cat, very slow view (~10 MiB/s)", 16 * MIB, 4096, 100),
+    ];
+
+    println!(
+        "{:<34} {:>10} {:>12} {:>12} {:>14}",
+        "scenario", "MiB/s", "reads", "messages", "peak in flight"
+    );
+    for &(name, total, read_size, render) in scenarios {
+        let report = run(total, read_size, render);
+        let mib_s = total as f64 / MIB as f64 / report.elapsed.as_secs_f64();
+        println!(
+            "{:<34} {:>10.1} {:>12} {:>12} {:>11} KiB",
+            name,
+            mib_s,
+            total / read_size,
+            report.messages,
+            report.peak / 1024,
+        );
+    }
+}
@@ -23,7 +23,7 @@ fn silent_command(program: impl AsRef<std::ffi::OsStr>) -> std::process::Command
     cmd
 }
 
-use crate::ssh::client::{exec_on_connection_streaming, SshManager};
+use crate::ssh::client::{OutputStream, RemoteCommand, SshManager};
 use crate::ansible::types::{AnsibleCommand, AnsibleCommandEvent, AnsibleCommandRequest};
 use crate::toolchain::detect::windows_to_wsl_path;
 
@@ -333,10 +333,27 @@ pub async fn run_remote(
         .get_handle(connection_id)
         .map_err(|e| e.to_string())?;
 
-    let exit_code =
-        exec_on_connection_streaming(&handle, &cmd, run_id, "ansible-output", app_handle)
-            .await
-            .map_err(|e| e.to_string())?;
+    let emit_line = |stream: OutputStream, line: String| {
+        let _ = app_handle.emit(
+            &event_name,
+            AnsibleCommandEvent {
+                run_id: run_id.to_string(),
+                stream: stream.as_str().to_string(),
+                line,
+                done: false,
+                exit_code: None,
+            },
+        );
+    };
+
+    // Package installs and wait loops can go quiet for minutes; only the
+    // connection dropping ends the run.
+    let output = RemoteCommand::new(cmd)
+        .idle_timeout(None)
+        .run_lines(&handle, emit_line)
+        .await
+        .map_err(|e| e.to_string())?;
+    let exit_code = output.exit_code_or_default();
 
     // Emit done event
     let _ = app_handle.emit(
@@ -0,0 +1,222 @@
+//! Spotting the start of a ZMODEM or trzsz transfer in terminal output.
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+/// `sz` opens with a ZRQINIT hex header, `rz` with a ZRINIT one. Both start
+/// `**<ZDLE>B0`; the next digit tells them apart.
+const ZMODEM_PREFIX: &[u8] = b"**\x18B0";
+const TRZSZ_PREFIX: &[u8] = b"::TRZSZ:TRANSFER:";
+/// Longest tail of a trzsz start line (`R:1.1.6:1234567890100`) we wait for.
+const TRZSZ_LINE_MAX: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferProtocol {
+    Zmodem,
+    Trzsz,
+}
+
+/// Seen from this end: an upload sends local files to the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Start {
+    pub protocol: TransferProtocol,
+    pub direction: Direction,
+    /// `trz -d`: the remote wants whole directories.
+    pub directories: bool,
+}
+
+/// Result of [`Detector::scan`].
+pub struct Scan<'a> {
+    /// Output that belongs on the terminal.
+    pub output: Cow<'a, [u8]>,
+    /// A transfer start, with the bytes that follow it. For ZMODEM these
+    /// begin with the start header itself, which the protocol needs to see.
+    pub start: Option<(Start, Vec<u8>)>,
+}
+
+/// Scans output chunk by chunk. A start sequence split across chunks is
+/// still found; the part of it that arrived earlier has already been shown,
+/// which is harmless (`sz` prints it on any terminal that isn't listening).
+#[derive(Debug, Default)]
+pub struct Detector {
+    zmodem: usize,
+    trzsz: usize,
+    /// After a trzsz prefix: the part of it that wasn't shown, then the rest
+    /// of its line, held back until complete.
+    trzsz_line: Option<(usize, Vec<u8>)>,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scan<'a>(&mut self, data: &'a [u8]) -> Scan<'a> {
+        if let Some((prefix, held)) = self.trzsz_line.take() {
+            return self.trzsz_tail(prefix, held, data, 0, Cow::Borrowed(&[]));
+        }
+        for (i, &b) in data.iter().enumerate() {
+            if self.zmodem == ZMODEM_PREFIX.len() && (b == b'0' || b == b'1') {
+                self.zmodem = 0;
+                let direction = if b == b'0' { Direction::Download } else { Direction::Upload };
+                let header_start = (i + 1).saturating_sub(ZMODEM_PREFIX.len() + 1);
+                let mut rest = ZMODEM_PREFIX.to_vec();
+                rest.push(b);
+                rest.extend_from_slice(&data[i + 1..]);
+                return Scan {
+                    output: Cow::Borrowed(&data[..header_start]),
+                    start: Some((
+                        Start { protocol: TransferProtocol::Zmodem, direction, directories: false },
+                        rest,
+                    )),
+                };
+            }
+            self.zmodem = advance(ZMODEM_PREFIX, self.zmodem, b);
+            self.trzsz = advance(TRZSZ_PREFIX, self.trzsz, b);
+            if self.trzsz == TRZSZ_PREFIX.len() {
+                self.trzsz = 0;
+                self.zmodem = 0;
+                let shown = (i + 1).saturating_sub(TRZSZ_PREFIX.len());
+                let held = data[shown..=i].to_vec();
+                return self.trzsz_tail(held.len(), held, data, i + 1, Cow::Borrowed(&data[..shown]));
+            }
+        }
+        Scan { output: Cow::Borrowed(data), start: None }
+    }
+
+    /// Collect the trzsz start line from `data[from..]` up to its newline.
+    /// `held` starts with `prefix` bytes of the start sequence itself.
+    fn trzsz_tail<'a>(
+        &mut self,
+        prefix: usize,
+        mut held: Vec<u8>,
+        data: &'a [u8],
+        from: usize,
+        shown: Cow<'a, [u8]>,
+    ) -> Scan<'a> {
+        let tail = &data[from..];
+        let Some(nl) = tail.iter().position(|&b| b == b'\n') else {
+            held.extend_from_slice(tail);
+            if held.len() - prefix <= TRZSZ_LINE_MAX {
+                self.trzsz_line = Some((prefix, held));
+                return Scan { output: shown, start: None };
+            }
+            return Scan { output: give_back(shown, &held, &[]), start: None };
+        };
+        held.extend_from_slice(&tail[..nl]);
+        let rest = &tail[nl + 1..];
+        let line = &held[prefix..];
+        let start = match line.get(..2) {
+            Some(b"S:") => Some((Direction::Download, false)),
+            Some(b"R:") => Some((Direction::Upload, false)),
+            Some(b"D:") => Some((Direction::Upload, true)),
+            _ => None,
+        };
+        match start {
+            Some((direction, directories)) if line.len() <= TRZSZ_LINE_MAX => Scan {
+                output: shown,
+                start: Some((
+                    Start { protocol: TransferProtocol::Trzsz, direction, directories },
+                    rest.to_vec(),
+                )),
+            },
+            _ => {
+                held.push(b'\n');
+                Scan { output: give_back(shown, &held, rest), start: None }
+            }
+        }
+    }
+}
+
+/// Matched length of `pattern` after `b`, given `matched` bytes so far. On a
+/// mismatch, falls back to the longest prefix that still ends the input.
+fn advance(pattern: &[u8], matched: usize, b: u8) -> usize {
+    if matched < pattern.len() && pattern[matched] == b {
+        return matched + 1;
+    }
+    (1..=matched)
+        .rev()
+        .find(|&k| pattern[k - 1] == b && pattern[..k - 1] == pattern[matched + 1 - k..matched])
+        .unwrap_or(0)
+}
+
+/// Output that turned out not to start a transfer after all.
+fn give_back<'a>(shown: Cow<'a, [u8]>, held: &[u8], rest: &[u8]) -> Cow<'a, [u8]> {
+    let mut out = shown.into_owned();
+    out.extend_from_slice(held);
+    out.extend_from_slice(rest);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_zmodem_start_across_chunks() {
+        let mut d = Detector::new();
+        let first = d.scan(b"$ sz notes.txt\r\nrz\r**\x18");
+        assert_eq!(&*first.output, b"$ sz notes.txt\r\nrz\r**\x18");
+        assert!(first.start.is_none());
+
+        let second = d.scan(b"B00000000000000\r\x8a\x11");
+        assert!(second.output.is_empty());
+        let (start, rest) = second.start.unwrap();
+        assert_eq!(start.protocol, TransferProtocol::Zmodem);
+        assert_eq!(start.direction, Direction::Download);
+        assert_eq!(rest, b"**\x18B00000000000000\r\x8a\x11");
+    }
+
+    #[test]
+    fn finds_rz_after_extra_padding() {
+        let mut d = Detector::new();
+        let scan = d.scan(b"rz waiting to receive.***\x18B0100000023be50\r\x8a\x11");
+        assert_eq!(&*scan.output, b"rz waiting to receive.*");
+        let (start, rest) = scan.start.unwrap();
+        assert_eq!(start.direction, Direction::Upload);
+        assert!(rest.starts_with(b"**\x18B01"));
+    }
+
+    #[test]
+    fn finds_trzsz_start_line() {
+        let mut d = Detector::new();
+        let scan = d.scan(b"\x1b7\x07::TRZSZ:TRANSFER:R:1.1.6:17");
+        assert_eq!(&*scan.output, b"\x1b7\x07");
+        assert!(scan.start.is_none());
+
+        let scan = d.scan(b"00000000100\r\n#CFG:");
+        assert!(scan.output.is_empty());
+        let (start, rest) = scan.start.unwrap();
+        assert_eq!(start.protocol, TransferProtocol::Trzsz);
+        assert_eq!(start.direction, Direction::Upload);
+        assert!(!start.directories);
+        assert_eq!(rest, b"#CFG:");
+    }
+
+    #[test]
+    fn malformed_trzsz_line_is_shown() {
+        let mut d = Detector::new();
+        let scan = d.scan(b"grep ::TRZSZ:TRANSFER: *.go\r\n$ ");
+        assert_eq!(&*scan.output, b"grep ::TRZSZ:TRANSFER: *.go\r\n$ ");
+        assert!(scan.start.is_none());
+    }
+
+    #[test]
+    fn ordinary_output_passes_through() {
+        let mut d = Detector::new();
+        for chunk in [&b"**bold** text"[..], b"\x18B", b"::TRZSZ", b":TRANS", b"FEr:"] {
+            let scan = d.scan(chunk);
+            assert_eq!(&*scan.output, chunk);
+            assert!(scan.start.is_none());
+        }
+    }
+}
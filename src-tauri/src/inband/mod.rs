@@ -0,0 +1,31 @@
+//! ZMODEM (`rz`/`sz`) and trzsz (`trz`/`tsz`) transfers carried inside a
+//! terminal's own byte stream, for SSH shells and serial consoles alike.
+
+pub mod detect;
+pub mod session;
+pub mod trzsz;
+pub mod zmodem;
+
+use tauri::{AppHandle, Emitter};
+
+use session::{InbandSession, Notice};
+
+/// Emit whatever `session` has to tell the frontend.
+pub fn emit_notices(app: &AppHandle, session: &mut InbandSession) {
+    for notice in session.take_notices() {
+        match notice {
+            Notice::Transfer(event) => {
+                let _ = app.emit(&format!("inband-transfer-{}", session.id()), event);
+            }
+            Notice::Progress(progress) => {
+                let _ = app.emit(&format!("transfer-progress-{}", progress.id), &progress);
+            }
+            Notice::Complete(transfer_id) => {
+                let _ = app.emit(&format!("transfer-complete-{}", transfer_id), ());
+            }
+            Notice::Error { transfer_id, message } => {
+                let _ = app.emit(&format!("transfer-error-{}", transfer_id), message);
+            }
+        }
+    }
+}
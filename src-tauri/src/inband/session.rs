@@ -0,0 +1,514 @@
+//! One terminal's in-band transfers. Remote output is watched for a start
+//! sequence, held while the user picks files or a folder, then handed to the
+//! protocol until the transfer ends and the terminal gets its stream back.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::detect::{Detector, Direction, Start, TransferProtocol};
+use super::trzsz::{self, Trzsz};
+use super::zmodem::{self, Zmodem};
+use crate::sftp::transfer::TransferProgress;
+
+/// How long a start waits for the user before it's turned down.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long a running transfer may go without hearing from the remote.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Minimum gap between progress events for one file.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A transfer protocol driven by bytes: remote output in through `feed`,
+/// bytes for the remote out of `poll_transmit`. Local files are read and
+/// written by the engine itself.
+pub(crate) trait Engine: Send {
+    fn feed(&mut self, data: &[u8]);
+    fn wants_transmit(&self) -> bool;
+    /// Next bytes for the remote, in chunks small enough to interleave with
+    /// reading the answers.
+    fn poll_transmit(&mut self) -> Option<Vec<u8>>;
+    fn take_events(&mut self) -> Vec<FileEvent>;
+    fn outcome(&self) -> Option<&Outcome>;
+    /// Output that followed the end of the transfer.
+    fn take_leftover(&mut self) -> Vec<u8>;
+    /// What the remote prints once it's done, to keep off the terminal.
+    fn trailer(&self) -> &'static [u8] {
+        b""
+    }
+    fn cancel(&mut self, reason: &str);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileEvent {
+    Started { name: String, total: u64 },
+    Progress(u64),
+    /// A download finished and was saved here.
+    Done(PathBuf),
+    /// An upload finished.
+    Sent,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Done,
+    Failed(String),
+}
+
+/// Events on `inband-transfer-{id}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum InbandEvent {
+    /// The remote started a transfer. Answer with the files to upload or the
+    /// folder to download into, or nothing to turn it down.
+    Request { protocol: TransferProtocol, direction: Direction },
+    /// A file is starting; its progress follows on `transfer-progress-{transferId}`
+    /// and it ends with `transfer-complete-` or `transfer-error-`.
+    #[serde(rename_all = "camelCase")]
+    File { transfer_id: String, filename: String, total_bytes: u64, direction: Direction },
+    Finished { ok: bool, error: Option<String>, files: Vec<String> },
+}
+
+/// Something to tell the frontend; see `inband::emit_notices`.
+#[derive(Debug, Clone)]
+pub enum Notice {
+    Transfer(InbandEvent),
+    Progress(TransferProgress),
+    Complete(String),
+    Error { transfer_id: String, message: String },
+}
+
+enum State {
+    Idle,
+    Pending { start: Start, held: Vec<u8>, since: Instant },
+    Active(Box<Active>),
+}
+
+struct Active {
+    engine: Box<dyn Engine>,
+    direction: Direction,
+    file: Option<ActiveFile>,
+    files: Vec<String>,
+    heard: Instant,
+}
+
+struct ActiveFile {
+    transfer_id: String,
+    name: String,
+    total: u64,
+    done: u64,
+    reported: Instant,
+}
+
+impl ActiveFile {
+    fn progress(&self) -> TransferProgress {
+        let percent = if self.total > 0 {
+            (self.done as f64 / self.total as f64 * 100.0).min(100.0)
+        } else {
+            100.0
+        };
+        TransferProgress {
+            id: self.transfer_id.clone(),
+            filename: self.name.clone(),
+            bytes_transferred: self.done,
+            total_bytes: self.total,
+            percent,
+        }
+    }
+}
+
+pub struct InbandSession {
+    id: String,
+    detector: Detector,
+    state: State,
+    /// Bytes for the remote that no running engine owns (a refusal, the last
+    /// words of a finished one).
+    outbox: Vec<u8>,
+    /// Terminal output that came out of a transfer ending.
+    stray: Vec<u8>,
+    /// The remote's sign-off, still to drop from upcoming output.
+    trailer: &'static [u8],
+    notices: Vec<Notice>,
+    transfers: u64,
+}
+
+impl InbandSession {
+    /// `id` is the connection id or port name; transfer ids derive from it.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            detector: Detector::new(),
+            state: State::Idle,
+            outbox: Vec::new(),
+            stray: Vec::new(),
+            trailer: b"",
+            notices: Vec::new(),
+            transfers: 0,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// A transfer is starting or running; remote output is not for the
+    /// terminal and keystrokes are not for the remote.
+    pub fn is_busy(&self) -> bool {
+        !matches!(self.state, State::Idle)
+    }
+
+    /// Route remote output. Returns what belongs on the terminal.
+    /// `interactive` says whether anyone is there to answer a request; if
+    /// not, a transfer start is turned down straight away.
+    pub fn output<'a>(&mut self, data: &'a [u8], interactive: bool) -> Cow<'a, [u8]> {
+        let data = match self.state {
+            State::Idle => &data[self.skip_trailer(data)..],
+            _ => data,
+        };
+        let shown = match &mut self.state {
+            State::Idle => {
+                let scan = self.detector.scan(data);
+                if let Some((start, rest)) = scan.start {
+                    self.begin(start, rest, interactive);
+                }
+                scan.output
+            }
+            State::Pending { held, .. } => {
+                held.extend_from_slice(data);
+                Cow::Borrowed(&[][..])
+            }
+            State::Active(active) => {
+                active.heard = Instant::now();
+                active.engine.feed(data);
+                self.pump();
+                Cow::Borrowed(&[][..])
+            }
+        };
+        if self.stray.is_empty() {
+            return shown;
+        }
+        let mut out = std::mem::take(&mut self.stray);
+        out.extend_from_slice(&shown);
+        Cow::Owned(out)
+    }
+
+    /// Local keystrokes. Returns whether they should go to the remote; while
+    /// busy they don't, and Ctrl+C cancels the transfer.
+    pub fn input(&mut self, data: &[u8]) -> bool {
+        if !self.is_busy() {
+            return true;
+        }
+        if data.contains(&0x03) {
+            self.cancel("Cancelled");
+        }
+        false
+    }
+
+    /// The user's answer to a request: files to upload, or a single folder
+    /// to download into. `None` or an empty list turns it down.
+    pub fn respond(&mut self, paths: Option<Vec<PathBuf>>) {
+        let Some(paths) = paths.filter(|p| !p.is_empty()) else {
+            self.decline("Cancelled");
+            return;
+        };
+        let State::Pending { start, held, .. } = std::mem::replace(&mut self.state, State::Idle) else {
+            return;
+        };
+        let dir = || paths[0].clone();
+        let mut engine: Box<dyn Engine> = match (start.protocol, start.direction) {
+            (TransferProtocol::Zmodem, Direction::Download) => Box::new(Zmodem::receive(dir())),
+            (TransferProtocol::Zmodem, Direction::Upload) => Box::new(Zmodem::send(paths.clone())),
+            (TransferProtocol::Trzsz, Direction::Download) => Box::new(Trzsz::receive(dir())),
+            (TransferProtocol::Trzsz, Direction::Upload) => Box::new(Trzsz::send(paths.clone())),
+        };
+        engine.feed(&held);
+        self.state = State::Active(Box::new(Active {
+            engine,
+            direction: start.direction,
+            file: None,
+            files: Vec::new(),
+            heard: Instant::now(),
+        }));
+        self.pump();
+    }
+
+    pub fn cancel(&mut self, reason: &str) {
+        match &mut self.state {
+            State::Idle => {}
+            State::Pending { .. } => self.decline(reason),
+            State::Active(active) => {
+                active.engine.cancel(reason);
+                self.pump();
+            }
+        }
+    }
+
+    /// Call about once a second while busy: gives up on unanswered requests
+    /// and on transfers the remote has gone quiet on.
+    pub fn tick(&mut self) {
+        match &mut self.state {
+            State::Pending { since, .. } if since.elapsed() >= ANSWER_TIMEOUT => {
+                self.decline("Nobody answered the transfer request");
+            }
+            State::Active(active) if active.heard.elapsed() >= IDLE_TIMEOUT && !active.engine.wants_transmit() => {
+                active.engine.cancel("The remote side stopped responding");
+                self.pump();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn wants_transmit(&self) -> bool {
+        !self.outbox.is_empty() || matches!(&self.state, State::Active(a) if a.engine.wants_transmit())
+    }
+
+    /// Next bytes for the remote, if any.
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        if !self.outbox.is_empty() {
+            return Some(std::mem::take(&mut self.outbox));
+        }
+        let State::Active(active) = &mut self.state else { return None };
+        let bytes = active.engine.poll_transmit();
+        self.pump();
+        bytes
+    }
+
+    pub fn take_notices(&mut self) -> Vec<Notice> {
+        std::mem::take(&mut self.notices)
+    }
+
+    fn begin(&mut self, start: Start, held: Vec<u8>, interactive: bool) {
+        self.state = State::Pending { start, held, since: Instant::now() };
+        if start.directories {
+            self.decline("Directory uploads aren't supported; upload files instead");
+        } else if !interactive {
+            self.decline("No terminal is attached to answer the transfer");
+        } else {
+            self.notices.push(Notice::Transfer(InbandEvent::Request {
+                protocol: start.protocol,
+                direction: start.direction,
+            }));
+        }
+    }
+
+    fn decline(&mut self, reason: &str) {
+        let State::Pending { start, .. } = std::mem::replace(&mut self.state, State::Idle) else {
+            return;
+        };
+        match start.protocol {
+            TransferProtocol::Zmodem => self.outbox.extend_from_slice(zmodem::CANCEL),
+            TransferProtocol::Trzsz => self.outbox.extend(trzsz::decline()),
+        }
+        self.notices.push(Notice::Transfer(InbandEvent::Finished {
+            ok: false,
+            error: Some(reason.to_string()),
+            files: Vec::new(),
+        }));
+    }
+
+    /// Turn engine events into notices, and wind up a finished transfer.
+    fn pump(&mut self) {
+        let State::Active(active) = &mut self.state else { return };
+        for event in active.engine.take_events() {
+            match event {
+                FileEvent::Started { name, total } => {
+                    self.transfers += 1;
+                    let transfer_id = format!("{}-inband-{}", self.id, self.transfers);
+                    self.notices.push(Notice::Transfer(InbandEvent::File {
+                        transfer_id: transfer_id.clone(),
+                        filename: name.clone(),
+                        total_bytes: total,
+                        direction: active.direction,
+                    }));
+                    active.file = Some(ActiveFile { transfer_id, name, total, done: 0, reported: Instant::now() });
+                }
+                FileEvent::Progress(done) => {
+                    if let Some(file) = &mut active.file {
+                        file.done = done;
+                        if file.reported.elapsed() >= PROGRESS_INTERVAL {
+                            file.reported = Instant::now();
+                            self.notices.push(Notice::Progress(file.progress()));
+                        }
+                    }
+                }
+                FileEvent::Done(_) | FileEvent::Sent => {
+                    if let Some(file) = active.file.take() {
+                        self.notices.push(Notice::Progress(file.progress()));
+                        self.notices.push(Notice::Complete(file.transfer_id));
+                        active.files.push(file.name);
+                    }
+                }
+                FileEvent::Failed(message) => {
+                    if let Some(file) = active.file.take() {
+                        self.notices.push(Notice::Error { transfer_id: file.transfer_id, message });
+                    }
+                }
+            }
+        }
+
+        let Some(outcome) = active.engine.outcome().cloned() else { return };
+        // Whatever the engine still has to say (a sign-off, a cancel) goes out
+        // after it's gone.
+        while let Some(bytes) = active.engine.poll_transmit() {
+            self.outbox.extend(bytes);
+        }
+        if let Some(file) = active.file.take() {
+            self.notices.push(Notice::Error {
+                transfer_id: file.transfer_id,
+                message: "Interrupted".to_string(),
+            });
+        }
+        let (ok, error) = match outcome {
+            Outcome::Done => (true, None),
+            Outcome::Failed(message) => (false, Some(message)),
+        };
+        self.notices.push(Notice::Transfer(InbandEvent::Finished {
+            ok,
+            error,
+            files: std::mem::take(&mut active.files),
+        }));
+        self.trailer = if ok { active.engine.trailer() } else { b"" };
+        let leftover = active.engine.take_leftover();
+        self.state = State::Idle;
+        let skip = self.skip_trailer(&leftover);
+        self.stray.extend_from_slice(&leftover[skip..]);
+    }
+
+    /// How much of `data` is the remote's sign-off.
+    fn skip_trailer(&mut self, data: &[u8]) -> usize {
+        if self.trailer.is_empty() || data.is_empty() {
+            return 0;
+        }
+        let n = data.iter().zip(self.trailer).take_while(|(a, b)| a == b).count();
+        self.trailer = if n == data.len() { &self.trailer[n..] } else { b"" };
+        n
+    }
+}
+
+/// `dir/name`, or `dir/name (1).ext` and so on if that's taken.
+pub(crate) fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let first = dir.join(name);
+    if !first.exists() {
+        return first;
+    }
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| name.to_string());
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let candidate = dir.join(format!("{} ({}){}", stem, n, ext));
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(notices: &[Notice]) -> Option<(bool, Option<String>, Vec<String>)> {
+        notices.iter().find_map(|n| match n {
+            Notice::Transfer(InbandEvent::Finished { ok, error, files }) => Some((*ok, error.clone(), files.clone())),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn request_holds_output_until_answered() {
+        let mut s = InbandSession::new("c1");
+        let shown = s.output(b"$ sz a.txt\r\nrz\r**\x18B00000000000000\r\x8a\x11", true);
+        assert_eq!(&*shown, b"$ sz a.txt\r\nrz\r");
+        assert!(s.is_busy());
+        assert!(matches!(
+            s.take_notices()[..],
+            [Notice::Transfer(InbandEvent::Request { protocol: TransferProtocol::Zmodem, direction: Direction::Download })]
+        ));
+        assert!(s.output(b"**\x18B00000000000000\r\x8a\x11", true).is_empty());
+        assert!(!s.input(b"ls\r"));
+
+        s.respond(None);
+        assert!(!s.is_busy());
+        assert_eq!(s.poll_transmit().unwrap(), zmodem::CANCEL);
+        assert_eq!(finished(&s.take_notices()), Some((false, Some("Cancelled".into()), vec![])));
+        assert_eq!(&*s.output(b"\r\n$ ", true), b"\r\n$ ");
+    }
+
+    #[test]
+    fn declines_when_nobody_is_attached() {
+        let mut s = InbandSession::new("c1");
+        let shown = s.output(b"\x1b7\x07::TRZSZ:TRANSFER:S:1.1.6:1700000000100\r\n", false);
+        assert_eq!(&*shown, b"\x1b7\x07");
+        assert!(!s.is_busy());
+        assert!(s.poll_transmit().unwrap().starts_with(b"#ACT:"));
+        assert!(finished(&s.take_notices()).is_some_and(|(ok, _, _)| !ok));
+    }
+
+    #[test]
+    fn ctrl_c_cancels_a_pending_request() {
+        let mut s = InbandSession::new("c1");
+        let _ = s.output(b"**\x18B0100000023be50\r\x8a\x11", true);
+        assert!(!s.input(b"\x03"));
+        assert!(!s.is_busy());
+        assert_eq!(s.poll_transmit().unwrap(), zmodem::CANCEL);
+    }
+
+    #[test]
+    fn uploads_to_a_remote_rz_and_gives_the_terminal_back() {
+        let dir = std::env::temp_dir().join(format!("reach-inband-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("remote")).unwrap();
+        std::fs::write(dir.join("report.pdf"), vec![7u8; 3000]).unwrap();
+
+        // Our own receiver stands in for the remote `rz`.
+        let mut remote = Zmodem::receive(dir.join("remote"));
+        remote.feed(b"**\x18B00000000000000\r\x8a\x11");
+        let zrinit = remote.poll_transmit().unwrap();
+
+        let mut s = InbandSession::new("c1");
+        let mut greeting = b"rz waiting to receive.".to_vec();
+        greeting.extend(&zrinit);
+        assert_eq!(&*s.output(&greeting, true), b"rz waiting to receive.");
+        s.respond(Some(vec![dir.join("report.pdf")]));
+
+        let mut shown = Vec::new();
+        for _ in 0..1000 {
+            while let Some(bytes) = s.poll_transmit() {
+                remote.feed(&bytes);
+            }
+            match remote.poll_transmit() {
+                Some(bytes) => shown.extend_from_slice(&s.output(&bytes, true)),
+                None if !s.is_busy() => break,
+                None => {}
+            }
+        }
+        shown.extend_from_slice(&s.output(b"\r\n$ ", true));
+
+        assert_eq!(std::fs::read(dir.join("remote/report.pdf")).unwrap(), vec![7u8; 3000]);
+        assert_eq!(shown, b"\r\n$ ");
+        let notices = s.take_notices();
+        assert_eq!(finished(&notices), Some((true, None, vec!["report.pdf".to_string()])));
+        let progress: Vec<_> = notices
+            .iter()
+            .filter_map(|n| match n {
+                Notice::Progress(p) => Some(p.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(progress.last().map(|p| (p.bytes_transferred, p.percent)), Some((3000, 100.0)));
+        assert!(notices.iter().any(|n| matches!(n, Notice::Complete(id) if id == "c1-inband-1")));
+    }
+
+    #[test]
+    fn unique_path_numbers_taken_names() {
+        let dir = std::env::temp_dir().join(format!("reach-unique-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(unique_path(&dir, "a.tar.gz"), dir.join("a.tar.gz"));
+        std::fs::write(dir.join("a.tar.gz"), b"").unwrap();
+        assert_eq!(unique_path(&dir, "a.tar.gz"), dir.join("a.tar (1).gz"));
+    }
+}
@@ -0,0 +1,579 @@
+//! trzsz (`trz`/`tsz`): a line protocol spoken through the terminal. Every
+//! message is `#TYPE:payload\n`; strings and file data travel zlib-compressed
+//! and base64-encoded, sizes as plain decimals. We take the client side and
+//! leave out binary mode (`-b`) and directories (`-d`).
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use base64::Engine as _;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+
+use super::session::{unique_path, Engine, FileEvent, Outcome};
+
+/// The trzsz release whose protocol we speak.
+const VERSION: &str = "1.1.6";
+/// Upload chunk size to start from; doubled while acknowledgements come back
+/// quickly, up to what the remote's buffer allows.
+const MIN_CHUNK: u64 = 1024;
+const MAX_CHUNK: u64 = 1024 * 1024;
+const FAST_ACK: Duration = Duration::from_millis(500);
+/// Longest message accepted from the remote.
+const MAX_LINE: usize = 16 * 1024 * 1024;
+
+/// The remote's `#CFG` message.
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    #[serde(default)]
+    binary: bool,
+    #[serde(default)]
+    bufsize: Option<u64>,
+}
+
+fn encode(data: &[u8]) -> String {
+    let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec can't fail.
+    let _ = z.write_all(data);
+    let compressed = z.finish().unwrap_or_default();
+    base64::engine::general_purpose::STANDARD.encode(compressed)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .map_err(|e| format!("Bad message from the remote: {}", e))?;
+    let mut out = Vec::new();
+    ZlibDecoder::new(&compressed[..])
+        .read_to_end(&mut out)
+        .map_err(|e| format!("Bad message from the remote: {}", e))?;
+    Ok(out)
+}
+
+fn message(kind: &str, payload: &str) -> Vec<u8> {
+    format!("#{}:{}\n", kind, payload).into_bytes()
+}
+
+fn action(confirm: bool) -> Vec<u8> {
+    let act = serde_json::json!({
+        "lang": "rust",
+        "confirm": confirm,
+        "version": VERSION,
+        "support_dir": false,
+        "binary": false,
+        "newline": "\n",
+    });
+    message("ACT", &encode(act.to_string().as_bytes()))
+}
+
+/// What to send when the user turns a transfer down.
+pub(crate) fn decline() -> Vec<u8> {
+    action(false)
+}
+
+struct Upload {
+    files: VecDeque<(String, File, u64)>,
+    current: Option<(File, u64, u64)>,
+    chunk: u64,
+    max_chunk: u64,
+    sent_at: Instant,
+}
+
+struct Download {
+    dir: PathBuf,
+    remaining: u64,
+    name: Option<String>,
+    current: Option<(File, PathBuf, u64, u64)>,
+    saved: u64,
+}
+
+enum Role {
+    Upload(Upload),
+    Download(Download),
+}
+
+/// What the remote is to send next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Config,
+    // Uploads: acknowledgements of what we sent.
+    NumAck(u64),
+    NameAck,
+    SizeAck(u64),
+    DataAck(u64),
+    Md5Ack,
+    // Downloads: the file list, one piece at a time.
+    Num,
+    Name,
+    Size,
+    Data,
+    Md5,
+}
+
+pub struct Trzsz {
+    role: Role,
+    expect: Expect,
+    line: Vec<u8>,
+    md5: md5::Context,
+    digest: [u8; 16],
+    out: Vec<u8>,
+    events: Vec<FileEvent>,
+    outcome: Option<Outcome>,
+    leftover: Vec<u8>,
+}
+
+impl Trzsz {
+    /// Send `files` to a remote `trz`.
+    pub fn send(files: Vec<PathBuf>) -> Self {
+        let mut t = Self::new(Role::Upload(Upload {
+            files: VecDeque::new(),
+            current: None,
+            chunk: MIN_CHUNK,
+            max_chunk: MAX_CHUNK,
+            sent_at: Instant::now(),
+        }));
+        for path in files {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            match File::open(&path).and_then(|f| f.metadata().map(|m| (f, m.len()))) {
+                Ok((file, size)) => {
+                    if let Role::Upload(up) = &mut t.role {
+                        up.files.push_back((name, file, size));
+                    }
+                }
+                Err(e) => {
+                    t.events.push(FileEvent::Started { name, total: 0 });
+                    t.events.push(FileEvent::Failed(e.to_string()));
+                }
+            }
+        }
+        t
+    }
+
+    /// Receive what a remote `tsz` sends into `dir`.
+    pub fn receive(dir: PathBuf) -> Self {
+        Self::new(Role::Download(Download { dir, remaining: 0, name: None, current: None, saved: 0 }))
+    }
+
+    fn new(role: Role) -> Self {
+        Self {
+            role,
+            expect: Expect::Config,
+            line: Vec::new(),
+            md5: md5::Context::new(),
+            digest: [0; 16],
+            out: action(true),
+            events: Vec::new(),
+            outcome: None,
+            leftover: Vec::new(),
+        }
+    }
+
+    fn send_line(&mut self, kind: &str, payload: &str) {
+        self.out.extend(message(kind, payload));
+    }
+
+    fn fail(&mut self, reason: String) {
+        self.stop(reason, true);
+    }
+
+    /// End the transfer, dropping a half-received file. `tell_remote` unless
+    /// the remote is the one that gave up.
+    fn stop(&mut self, reason: String, tell_remote: bool) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let in_flight = match &mut self.role {
+            Role::Upload(up) => up.current.take().is_some(),
+            Role::Download(down) => down
+                .current
+                .take()
+                .map(|(_, path, _, _)| {
+                    let _ = std::fs::remove_file(path);
+                })
+                .is_some(),
+        };
+        if in_flight {
+            self.events.push(FileEvent::Failed(reason.clone()));
+        }
+        if tell_remote {
+            self.send_line("fail", &encode(reason.as_bytes()));
+        }
+        self.outcome = Some(Outcome::Failed(reason));
+    }
+
+    fn finish(&mut self, summary: String) {
+        self.send_line("EXIT", &encode(summary.as_bytes()));
+        self.outcome = Some(Outcome::Done);
+    }
+
+    fn handle_line(&mut self, raw: &[u8]) {
+        let text = String::from_utf8_lossy(raw);
+        let text = text.trim_end_matches('\r');
+        // Anything before the last '#' is terminal noise; payloads never
+        // contain one.
+        let Some(hash) = text.rfind('#') else { return };
+        let Some((kind, payload)) = text[hash + 1..].split_once(':') else { return };
+        if kind.eq_ignore_ascii_case("fail") {
+            let reason = decode(payload)
+                .map(|m| String::from_utf8_lossy(&m).into_owned())
+                .unwrap_or_else(|_| payload.to_string());
+            self.stop(reason, false);
+            return;
+        }
+        let expected = match self.expect {
+            Expect::Config => "CFG",
+            Expect::NumAck(_) | Expect::NameAck | Expect::SizeAck(_) | Expect::DataAck(_) | Expect::Md5Ack => "SUCC",
+            Expect::Num => "NUM",
+            Expect::Name => "NAME",
+            Expect::Size => "SIZE",
+            Expect::Data => "DATA",
+            Expect::Md5 => "MD5",
+        };
+        if kind != expected {
+            self.fail(format!("Expected {} from the remote, got {}", expected, kind));
+            return;
+        }
+        if let Err(reason) = self.step(payload) {
+            self.fail(reason);
+        }
+    }
+
+    fn step(&mut self, payload: &str) -> Result<(), String> {
+        let number = || payload.trim().parse::<u64>().map_err(|_| format!("Bad number from the remote: {}", payload));
+        match self.expect {
+            Expect::Config => {
+                let config: Config = serde_json::from_slice(&decode(payload)?).map_err(|e| e.to_string())?;
+                if config.binary {
+                    return Err("Binary mode (-b) isn't supported".into());
+                }
+                match &mut self.role {
+                    Role::Upload(up) => {
+                        if let Some(size) = config.bufsize {
+                            up.max_chunk = size.clamp(MIN_CHUNK, MAX_CHUNK);
+                        }
+                        let count = up.files.len() as u64;
+                        self.expect = Expect::NumAck(count);
+                        self.send_line("NUM", &count.to_string());
+                    }
+                    Role::Download(_) => self.expect = Expect::Num,
+                }
+            }
+            Expect::NumAck(count) => {
+                if number()? != count {
+                    return Err("The remote miscounted the files".into());
+                }
+                self.offer_next();
+            }
+            Expect::NameAck => {
+                let Role::Upload(up) = &self.role else { return Ok(()) };
+                let size = up.current.as_ref().map(|c| c.1).unwrap_or(0);
+                self.expect = Expect::SizeAck(size);
+                self.send_line("SIZE", &size.to_string());
+            }
+            Expect::SizeAck(size) => {
+                if number()? != size {
+                    return Err("The remote got the file size wrong".into());
+                }
+                self.send_chunk()?;
+            }
+            Expect::DataAck(len) => {
+                if number()? != len {
+                    return Err("The remote lost part of the file".into());
+                }
+                let Role::Upload(up) = &mut self.role else { return Ok(()) };
+                if up.sent_at.elapsed() < FAST_ACK {
+                    up.chunk = (up.chunk * 2).min(up.max_chunk);
+                }
+                self.send_chunk()?;
+            }
+            Expect::Md5Ack => {
+                if decode(payload)? != self.digest {
+                    return Err("Checksum mismatch on the remote side".into());
+                }
+                if let Role::Upload(up) = &mut self.role {
+                    up.current = None;
+                }
+                self.events.push(FileEvent::Sent);
+                self.offer_next();
+            }
+            Expect::Num => {
+                let count = number()?;
+                self.send_line("SUCC", &count.to_string());
+                let Role::Download(down) = &mut self.role else { return Ok(()) };
+                down.remaining = count;
+                if count == 0 {
+                    self.finish("Nothing to receive".into());
+                } else {
+                    self.expect = Expect::Name;
+                }
+            }
+            Expect::Name => {
+                let Role::Download(down) = &mut self.role else { return Ok(()) };
+                let name = String::from_utf8_lossy(&decode(payload)?).into_owned();
+                let base = std::path::Path::new(&name)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .ok_or_else(|| format!("Refusing to save {:?}", name))?;
+                let path = unique_path(&down.dir, &base);
+                let file = File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let local = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(base);
+                down.current = Some((file, path, 0, 0));
+                down.name = Some(local.clone());
+                self.md5 = md5::Context::new();
+                self.expect = Expect::Size;
+                self.send_line("SUCC", &encode(local.as_bytes()));
+            }
+            Expect::Size => {
+                let size = number()?;
+                let Role::Download(down) = &mut self.role else { return Ok(()) };
+                if let Some(current) = &mut down.current {
+                    current.2 = size;
+                }
+                self.events.push(FileEvent::Started { name: down.name.take().unwrap_or_default(), total: size });
+                self.expect = if size == 0 { Expect::Md5 } else { Expect::Data };
+                self.send_line("SUCC", &size.to_string());
+            }
+            Expect::Data => {
+                let data = decode(payload)?;
+                let Role::Download(down) = &mut self.role else { return Ok(()) };
+                let Some((file, path, total, received)) = &mut down.current else { return Ok(()) };
+                file.write_all(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+                *received += data.len() as u64;
+                self.md5.consume(&data);
+                self.events.push(FileEvent::Progress(*received));
+                if *received >= *total {
+                    self.expect = Expect::Md5;
+                }
+                self.send_line("SUCC", &data.len().to_string());
+            }
+            Expect::Md5 => {
+                let theirs = decode(payload)?;
+                let ours = std::mem::replace(&mut self.md5, md5::Context::new()).compute();
+                if theirs != ours.0 {
+                    return Err("Checksum mismatch; the download is corrupt".into());
+                }
+                let Role::Download(down) = &mut self.role else { return Ok(()) };
+                if let Some((file, path, _, _)) = down.current.take() {
+                    let _ = file.sync_all();
+                    self.events.push(FileEvent::Done(path));
+                }
+                down.saved += 1;
+                down.remaining = down.remaining.saturating_sub(1);
+                let summary = (down.remaining == 0)
+                    .then(|| format!("Saved {} file(s) to {}", down.saved, down.dir.display()));
+                self.send_line("SUCC", &encode(&ours.0));
+                match summary {
+                    Some(summary) => self.finish(summary),
+                    None => self.expect = Expect::Name,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn offer_next(&mut self) {
+        let Role::Upload(up) = &mut self.role else { return };
+        let Some((name, file, size)) = up.files.pop_front() else {
+            self.finish("Upload complete".into());
+            return;
+        };
+        up.current = Some((file, size, 0));
+        self.md5 = md5::Context::new();
+        self.events.push(FileEvent::Started { name: name.clone(), total: size });
+        self.expect = Expect::NameAck;
+        self.send_line("NAME", &encode(name.as_bytes()));
+    }
+
+    /// The next `#DATA` message, or `#MD5` once the file is all sent.
+    fn send_chunk(&mut self) -> Result<(), String> {
+        let Role::Upload(up) = &mut self.role else { return Ok(()) };
+        let Some((file, size, sent)) = &mut up.current else { return Ok(()) };
+        if let Expect::DataAck(len) = self.expect {
+            *sent += len;
+            self.events.push(FileEvent::Progress(*sent));
+        }
+        if *sent >= *size {
+            self.digest = std::mem::replace(&mut self.md5, md5::Context::new()).compute().0;
+            self.expect = Expect::Md5Ack;
+            let digest = encode(&self.digest);
+            self.send_line("MD5", &digest);
+            return Ok(());
+        }
+        let want = up.chunk.min(*size - *sent);
+        let mut buf = Vec::with_capacity(want as usize);
+        file.take(want).read_to_end(&mut buf).map_err(|e| e.to_string())?;
+        if buf.is_empty() {
+            return Err("The file shrank while it was being sent".into());
+        }
+        self.md5.consume(&buf);
+        up.sent_at = Instant::now();
+        self.expect = Expect::DataAck(buf.len() as u64);
+        self.send_line("DATA", &encode(&buf));
+        Ok(())
+    }
+}
+
+impl Engine for Trzsz {
+    fn feed(&mut self, data: &[u8]) {
+        let mut rest = data;
+        while !rest.is_empty() {
+            if self.outcome.is_some() {
+                self.leftover.extend_from_slice(rest);
+                return;
+            }
+            let Some(nl) = rest.iter().position(|&b| b == b'\n') else {
+                self.line.extend_from_slice(rest);
+                if self.line.len() > MAX_LINE {
+                    self.fail("Message from the remote is too long".into());
+                }
+                return;
+            };
+            self.line.extend_from_slice(&rest[..nl]);
+            rest = &rest[nl + 1..];
+            let line = std::mem::take(&mut self.line);
+            self.handle_line(&line);
+        }
+    }
+
+    fn wants_transmit(&self) -> bool {
+        !self.out.is_empty()
+    }
+
+    fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        (!self.out.is_empty()).then(|| std::mem::take(&mut self.out))
+    }
+
+    fn take_events(&mut self) -> Vec<FileEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    fn take_leftover(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.leftover)
+    }
+
+    fn cancel(&mut self, reason: &str) {
+        self.fail(reason.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("reach-trzsz-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The one message the client has queued, split into type and payload.
+    fn sent(t: &mut Trzsz) -> (String, String) {
+        let out = String::from_utf8(t.poll_transmit().expect("nothing sent")).unwrap();
+        let body = out.strip_prefix('#').and_then(|s| s.strip_suffix('\n')).unwrap();
+        assert!(!body.contains('\n'), "more than one message: {:?}", out);
+        let (kind, payload) = body.split_once(':').unwrap();
+        (kind.to_string(), payload.to_string())
+    }
+
+    fn text(payload: &str) -> String {
+        String::from_utf8(decode(payload).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn uploads_a_file() {
+        let dir = temp_dir("up");
+        std::fs::write(dir.join("hello.txt"), b"hello world").unwrap();
+        let mut t = Trzsz::send(vec![dir.join("hello.txt")]);
+
+        let (kind, act) = sent(&mut t);
+        assert_eq!(kind, "ACT");
+        let act: serde_json::Value = serde_json::from_str(&text(&act)).unwrap();
+        assert_eq!(act["confirm"], true);
+        assert_eq!(act["binary"], false);
+
+        t.feed(format!("#CFG:{}\n", encode(br#"{"lang":"go","bufsize":10485760,"timeout":20}"#)).as_bytes());
+        assert_eq!(sent(&mut t), ("NUM".into(), "1".into()));
+        t.feed(b"#SUCC:1\n");
+        let (kind, name) = sent(&mut t);
+        assert_eq!((kind.as_str(), text(&name).as_str()), ("NAME", "hello.txt"));
+        t.feed(format!("#SUCC:{}\n", encode(b"hello.txt")).as_bytes());
+        assert_eq!(sent(&mut t), ("SIZE".into(), "11".into()));
+        t.feed(b"#SUCC:11\n");
+        let (kind, data) = sent(&mut t);
+        assert_eq!((kind.as_str(), text(&data).as_str()), ("DATA", "hello world"));
+        t.feed(b"#SUCC:11\n");
+        let (kind, digest) = sent(&mut t);
+        assert_eq!(kind, "MD5");
+        assert_eq!(decode(&digest).unwrap(), md5::compute(b"hello world").0);
+        t.feed(format!("#SUCC:{}\r\n", digest).as_bytes());
+        assert_eq!(sent(&mut t).0, "EXIT");
+        assert_eq!(t.outcome(), Some(&Outcome::Done));
+        assert!(t.take_events().contains(&FileEvent::Sent));
+    }
+
+    #[test]
+    fn downloads_a_file_split_across_chunks() {
+        let dir = temp_dir("down");
+        let mut t = Trzsz::receive(dir.clone());
+        assert_eq!(sent(&mut t).0, "ACT");
+
+        let content = vec![b'x'; 5000];
+        let mut server = String::new();
+        server.push_str(&format!("#CFG:{}\n", encode(br#"{"lang":"go"}"#)));
+        server.push_str("#NUM:1\n");
+        server.push_str(&format!("#NAME:{}\n", encode(b"../../etc/passwd")));
+        server.push_str("#SIZE:5000\n");
+        server.push_str(&format!("#DATA:{}\n", encode(&content[..3000])));
+        server.push_str(&format!("#DATA:{}\n", encode(&content[3000..])));
+        server.push_str(&format!("#MD5:{}\n", encode(&md5::compute(&content).0)));
+        // Junk the terminal put in front of a message is skipped.
+        let server = server.replace("#NUM", "\x1b[0m#NUM");
+        for piece in server.as_bytes().chunks(5) {
+            t.feed(piece);
+        }
+        t.feed(b"\r\nSaved 1 file\r\n$ ");
+
+        assert_eq!(std::fs::read(dir.join("passwd")).unwrap(), content);
+        assert_eq!(t.outcome(), Some(&Outcome::Done));
+        assert_eq!(t.take_leftover(), b"\r\nSaved 1 file\r\n$ ");
+        let replies = String::from_utf8(t.poll_transmit().unwrap()).unwrap();
+        let kinds: Vec<&str> = replies.lines().map(|l| &l[1..l.find(':').unwrap()]).collect();
+        assert_eq!(kinds, ["SUCC", "SUCC", "SUCC", "SUCC", "SUCC", "SUCC", "EXIT"]);
+    }
+
+    #[test]
+    fn bad_checksum_fails_and_removes_the_file() {
+        let dir = temp_dir("md5");
+        let mut t = Trzsz::receive(dir.clone());
+        let _ = t.poll_transmit();
+        t.feed(format!("#CFG:{}\n#NUM:1\n#NAME:{}\n#SIZE:3\n", encode(b"{}"), encode(b"a")).as_bytes());
+        t.feed(format!("#DATA:{}\n#MD5:{}\n", encode(b"abc"), encode(&[0u8; 16])).as_bytes());
+        assert!(matches!(t.outcome(), Some(Outcome::Failed(_))));
+        assert!(!dir.join("a").exists());
+        assert!(String::from_utf8(t.poll_transmit().unwrap()).unwrap().ends_with(&format!(
+            "#fail:{}\n",
+            encode(b"Checksum mismatch; the download is corrupt")
+        )));
+    }
+
+    #[test]
+    fn remote_failure_ends_the_transfer() {
+        let mut t = Trzsz::send(Vec::new());
+        let _ = t.poll_transmit();
+        t.feed(format!("#FAIL:{}\n", encode(b"No such file")).as_bytes());
+        assert_eq!(t.outcome(), Some(&Outcome::Failed("No such file".into())));
+    }
+}
@@ -0,0 +1,977 @@
+//! ZMODEM as spoken by lrzsz's `sz` and `rz`. Either end can be ours: a
+//! remote `sz` makes us the receiver, a remote `rz` the sender. Positions
+//! are 32-bit on the wire, so files over 4 GiB aren't supported.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::session::{unique_path, Engine, FileEvent, Outcome};
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+const XON: u8 = 0x11;
+
+// Frame types
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCAN: u8 = 16;
+
+// Data subpacket terminators
+const ZCRCE: u8 = b'h'; // frame ends, header follows
+const ZCRCG: u8 = b'i'; // frame continues
+const ZCRCQ: u8 = b'j'; // frame continues, ZACK expected
+const ZCRCW: u8 = b'k'; // frame ends, ZACK expected
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// ZRINIT capabilities (ZF0)
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+const ESCCTL: u8 = 0x40;
+
+/// ZFILE ZF0: binary transfer, no newline conversion.
+const ZCBIN: u8 = 1;
+
+/// What lrzsz sends to abort: CANs, then backspaces to rub them out of a
+/// terminal that isn't listening.
+pub(crate) const CANCEL: &[u8] = b"\x18\x18\x18\x18\x18\x18\x18\x18\x18\x18\x08\x08\x08\x08\x08\x08\x08\x08\x08\x08";
+
+/// Payload per data subpacket; the most every receiver accepts.
+const SUBPACKET: usize = 1024;
+/// Longest subpacket we accept, in case the sender uses 8k blocks.
+const MAX_SUBPACKET: usize = 8192;
+/// Raw bytes per `poll_transmit` while streaming a file.
+const TRANSMIT_CHUNK: usize = 16 * 1024;
+
+/// CRC-16/XMODEM, as used for hex and `ZBIN` headers and subpackets.
+fn crc16(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn crc32(data: &[u8], end: Option<u8>) -> u32 {
+    let mut h = crc32fast::Hasher::new();
+    h.update(data);
+    if let Some(end) = end {
+        h.update(&[end]);
+    }
+    h.finalize()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    kind: u8,
+    /// ZP0..ZP3; for flag frames ZF0 is the last byte.
+    data: [u8; 4],
+}
+
+impl Header {
+    fn new(kind: u8, flags: u8) -> Self {
+        Self { kind, data: [0, 0, 0, flags] }
+    }
+
+    fn at(kind: u8, pos: u64) -> Self {
+        Self { kind, data: (pos as u32).to_le_bytes() }
+    }
+
+    fn pos(&self) -> u64 {
+        u32::from_le_bytes(self.data) as u64
+    }
+
+    fn flags(&self) -> u8 {
+        self.data[3]
+    }
+
+    fn bytes(&self) -> [u8; 5] {
+        let [a, b, c, d] = self.data;
+        [self.kind, a, b, c, d]
+    }
+}
+
+/// Characters escaped with ZDLE. Beyond what the spec requires, CR and LF are
+/// always escaped: raw they could wake the `~` escape of an `ssh` client
+/// between us and the remote.
+fn needs_escape(b: u8, escape_ctl: bool) -> bool {
+    match b & 0x7f {
+        ZDLE | 0x10 | XON | 0x13 | b'\r' | b'\n' => true,
+        c => escape_ctl && c < 0x20,
+    }
+}
+
+fn escape_into(out: &mut Vec<u8>, bytes: &[u8], escape_ctl: bool) {
+    for &b in bytes {
+        if needs_escape(b, escape_ctl) {
+            out.push(ZDLE);
+            out.push(b ^ 0x40);
+        } else {
+            out.push(b);
+        }
+    }
+}
+
+fn hex_header(h: Header) -> Vec<u8> {
+    let raw = h.bytes();
+    let crc = crc16(0, &raw);
+    let mut out = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+    for b in raw.iter().chain(&crc.to_be_bytes()) {
+        out.extend_from_slice(format!("{:02x}", b).as_bytes());
+    }
+    out.extend_from_slice(b"\r\x8a");
+    if h.kind != ZACK && h.kind != ZFIN {
+        out.push(XON);
+    }
+    out
+}
+
+fn bin_header(h: Header, use_crc32: bool, escape_ctl: bool) -> Vec<u8> {
+    let raw = h.bytes();
+    let mut out = vec![ZPAD, ZDLE, if use_crc32 { ZBIN32 } else { ZBIN }];
+    escape_into(&mut out, &raw, escape_ctl);
+    if use_crc32 {
+        escape_into(&mut out, &crc32(&raw, None).to_le_bytes(), escape_ctl);
+    } else {
+        escape_into(&mut out, &crc16(0, &raw).to_be_bytes(), escape_ctl);
+    }
+    out
+}
+
+fn subpacket(out: &mut Vec<u8>, data: &[u8], end: u8, use_crc32: bool, escape_ctl: bool) {
+    escape_into(out, data, escape_ctl);
+    out.push(ZDLE);
+    out.push(end);
+    if use_crc32 {
+        escape_into(out, &crc32(data, Some(end)).to_le_bytes(), escape_ctl);
+    } else {
+        escape_into(out, &crc16(crc16(0, data), &[end]).to_be_bytes(), escape_ctl);
+    }
+    if end == ZCRCW {
+        out.push(XON);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Frame {
+    Header { header: Header, crc32: bool },
+    Data { data: Vec<u8>, end: u8 },
+    /// A header or subpacket failed its CRC or was malformed.
+    Corrupt,
+    /// Five CANs in a row: the other end gave up.
+    Aborted,
+}
+
+#[derive(Debug)]
+enum ReadState {
+    /// Looking for a header.
+    Seek,
+    Pad,
+    PadDle,
+    Hex(Vec<u8>),
+    Bin { crc32: bool, buf: Vec<u8>, dle: bool },
+    Data { crc32: bool, buf: Vec<u8>, dle: bool, end: Option<u8>, crc: Vec<u8> },
+}
+
+/// Splits the incoming byte stream into frames. Data subpackets are only
+/// parsed after a header that carries them, when told to with `expect_data`.
+#[derive(Debug)]
+struct Reader {
+    state: ReadState,
+    cans: usize,
+}
+
+impl Reader {
+    fn new() -> Self {
+        Self { state: ReadState::Seek, cans: 0 }
+    }
+
+    fn expect_data(&mut self, crc32: bool) {
+        self.state = ReadState::Data { crc32, buf: Vec::new(), dle: false, end: None, crc: Vec::new() };
+    }
+
+    /// Consume `input[*pos..]` up to the end of the next frame.
+    fn read(&mut self, input: &[u8], pos: &mut usize) -> Option<Frame> {
+        while *pos < input.len() {
+            let b = input[*pos];
+            *pos += 1;
+            if b == ZDLE {
+                self.cans += 1;
+                if self.cans >= 5 {
+                    self.state = ReadState::Seek;
+                    return Some(Frame::Aborted);
+                }
+            } else {
+                self.cans = 0;
+            }
+            if let Some(frame) = self.step(b) {
+                return Some(frame);
+            }
+        }
+        None
+    }
+
+    fn step(&mut self, b: u8) -> Option<Frame> {
+        match &mut self.state {
+            ReadState::Seek => {
+                if b == ZPAD {
+                    self.state = ReadState::Pad;
+                }
+            }
+            ReadState::Pad => {
+                self.state = match b {
+                    ZPAD => ReadState::Pad,
+                    ZDLE => ReadState::PadDle,
+                    _ => ReadState::Seek,
+                };
+            }
+            ReadState::PadDle => {
+                self.state = match b {
+                    ZHEX => ReadState::Hex(Vec::with_capacity(14)),
+                    ZBIN => ReadState::Bin { crc32: false, buf: Vec::with_capacity(7), dle: false },
+                    ZBIN32 => ReadState::Bin { crc32: true, buf: Vec::with_capacity(9), dle: false },
+                    _ => ReadState::Seek,
+                };
+            }
+            ReadState::Hex(digits) => {
+                if !b.is_ascii_hexdigit() {
+                    self.state = ReadState::Seek;
+                    return Some(Frame::Corrupt);
+                }
+                digits.push(b);
+                if digits.len() == 14 {
+                    let bytes: Vec<u8> = digits
+                        .chunks(2)
+                        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap_or("00"), 16).unwrap_or(0))
+                        .collect();
+                    self.state = ReadState::Seek;
+                    let crc = u16::from_be_bytes([bytes[5], bytes[6]]);
+                    if crc16(0, &bytes[..5]) != crc {
+                        return Some(Frame::Corrupt);
+                    }
+                    return Some(Frame::Header { header: header_from(&bytes), crc32: false });
+                }
+            }
+            ReadState::Bin { crc32: use_crc32, buf, dle } => {
+                let byte = match unescape(b, dle)? {
+                    Ok(byte) => byte,
+                    Err(()) => {
+                        self.state = ReadState::Seek;
+                        return Some(Frame::Corrupt);
+                    }
+                };
+                buf.push(byte);
+                let want = if *use_crc32 { 9 } else { 7 };
+                if buf.len() == want {
+                    let ok = if *use_crc32 {
+                        crc32(&buf[..5], None).to_le_bytes() == buf[5..9]
+                    } else {
+                        crc16(0, &buf[..5]).to_be_bytes() == buf[5..7]
+                    };
+                    let frame = if ok {
+                        Frame::Header { header: header_from(buf), crc32: *use_crc32 }
+                    } else {
+                        Frame::Corrupt
+                    };
+                    self.state = ReadState::Seek;
+                    return Some(frame);
+                }
+            }
+            ReadState::Data { crc32: use_crc32, buf, dle, end, crc } => {
+                if *dle && end.is_none() && matches!(b, ZCRCE | ZCRCG | ZCRCQ | ZCRCW) {
+                    *dle = false;
+                    *end = Some(b);
+                    return None;
+                }
+                let byte = match unescape(b, dle)? {
+                    Ok(byte) => byte,
+                    Err(()) => {
+                        self.state = ReadState::Seek;
+                        return Some(Frame::Corrupt);
+                    }
+                };
+                let Some(frame_end) = *end else {
+                    if buf.len() >= MAX_SUBPACKET {
+                        self.state = ReadState::Seek;
+                        return Some(Frame::Corrupt);
+                    }
+                    buf.push(byte);
+                    return None;
+                };
+                crc.push(byte);
+                let want = if *use_crc32 { 4 } else { 2 };
+                if crc.len() < want {
+                    return None;
+                }
+                let ok = if *use_crc32 {
+                    crc32(buf, Some(frame_end)).to_le_bytes()[..] == crc[..]
+                } else {
+                    crc16(crc16(0, buf), &[frame_end]).to_be_bytes()[..] == crc[..]
+                };
+                if !ok {
+                    self.state = ReadState::Seek;
+                    return Some(Frame::Corrupt);
+                }
+                let data = std::mem::take(buf);
+                let use_crc32 = *use_crc32;
+                if frame_end == ZCRCG || frame_end == ZCRCQ {
+                    self.expect_data(use_crc32);
+                } else {
+                    self.state = ReadState::Seek;
+                }
+                return Some(Frame::Data { data, end: frame_end });
+            }
+        }
+        None
+    }
+}
+
+/// Decode one byte of an escaped field. `None` means nothing to add yet (a
+/// ZDLE, or flow control noise); `Some(Err)` a bad escape.
+fn unescape(b: u8, dle: &mut bool) -> Option<Result<u8, ()>> {
+    if *dle {
+        *dle = false;
+        return Some(match b {
+            ZRUB0 => Ok(0x7f),
+            ZRUB1 => Ok(0xff),
+            c if c & 0x60 == 0x40 => Ok(c ^ 0x40),
+            _ => Err(()),
+        });
+    }
+    match b {
+        ZDLE => {
+            *dle = true;
+            None
+        }
+        XON | 0x13 | 0x91 | 0x93 => None,
+        _ => Some(Ok(b)),
+    }
+}
+
+fn header_from(bytes: &[u8]) -> Header {
+    Header { kind: bytes[0], data: [bytes[1], bytes[2], bytes[3], bytes[4]] }
+}
+
+/// The first file name and size in a ZFILE subpacket:
+/// `name\0size mtime mode serial files-left bytes-left\0`.
+fn parse_file_info(data: &[u8]) -> (String, Option<u64>) {
+    let mut parts = data.splitn(2, |&b| b == 0);
+    let name = String::from_utf8_lossy(parts.next().unwrap_or_default()).into_owned();
+    let size = parts
+        .next()
+        .and_then(|meta| meta.split(|&b| b == 0).next())
+        .and_then(|meta| std::str::from_utf8(meta).ok())
+        .and_then(|meta| meta.split_whitespace().next())
+        .and_then(|s| s.parse().ok());
+    (name, size)
+}
+
+struct Incoming {
+    file: File,
+    path: PathBuf,
+    received: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecvState {
+    /// ZRINIT sent; waiting for ZSINIT, ZFILE or ZFIN.
+    WaitFile,
+    WaitSinitData,
+    WaitFileInfo,
+    /// ZRPOS sent; waiting for ZDATA at the position asked for.
+    WaitData,
+    Data,
+}
+
+struct Receiver {
+    dir: PathBuf,
+    state: RecvState,
+    file: Option<Incoming>,
+    answered_init: bool,
+}
+
+struct Outgoing {
+    file: File,
+    total: u64,
+    sent: u64,
+    /// Bytes sent since the receiver last acknowledged, for windowed receivers.
+    unacked: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SendState {
+    /// Waiting for the receiver's ZRINIT.
+    WaitInit,
+    /// ZFILE sent; waiting for ZRPOS or ZSKIP.
+    WaitPos,
+    Sending,
+    /// The receiver's buffer is full; waiting for ZACK.
+    WaitAck,
+    /// ZEOF sent; waiting for ZRINIT.
+    WaitEof,
+    /// ZFIN sent; waiting for the receiver's ZFIN.
+    WaitFin,
+}
+
+struct Sender {
+    files: VecDeque<PathBuf>,
+    state: SendState,
+    file: Option<Outgoing>,
+    crc32: bool,
+    escape_ctl: bool,
+    /// Receiver buffer size from ZRINIT; 0 means it can take a full stream.
+    window: u64,
+    /// ZFILE header and subpacket, resent on ZNAK.
+    last_offer: Vec<u8>,
+}
+
+enum Role {
+    Receive(Receiver),
+    Send(Sender),
+}
+
+pub struct Zmodem {
+    reader: Reader,
+    role: Role,
+    out: Vec<u8>,
+    events: Vec<FileEvent>,
+    outcome: Option<Outcome>,
+    leftover: Vec<u8>,
+}
+
+impl Zmodem {
+    /// Receive what a remote `sz` sends into `dir`.
+    pub fn receive(dir: PathBuf) -> Self {
+        Self::new(Role::Receive(Receiver {
+            dir,
+            state: RecvState::WaitFile,
+            file: None,
+            answered_init: false,
+        }))
+    }
+
+    /// Send `files` to a remote `rz`.
+    pub fn send(files: Vec<PathBuf>) -> Self {
+        Self::new(Role::Send(Sender {
+            files: files.into(),
+            state: SendState::WaitInit,
+            file: None,
+            crc32: false,
+            escape_ctl: false,
+            window: 0,
+            last_offer: Vec::new(),
+        }))
+    }
+
+    fn new(role: Role) -> Self {
+        Self {
+            reader: Reader::new(),
+            role,
+            out: Vec::new(),
+            events: Vec::new(),
+            outcome: None,
+            leftover: Vec::new(),
+        }
+    }
+
+    fn fail(&mut self, message: String) {
+        if self.outcome.is_some() {
+            return;
+        }
+        let in_flight = match &mut self.role {
+            Role::Receive(r) => r
+                .file
+                .take()
+                .map(|incoming| {
+                    let _ = std::fs::remove_file(&incoming.path);
+                })
+                .is_some(),
+            Role::Send(s) => s.file.take().is_some(),
+        };
+        if in_flight {
+            self.events.push(FileEvent::Failed(message.clone()));
+        }
+        self.out.extend_from_slice(CANCEL);
+        self.outcome = Some(Outcome::Failed(message));
+    }
+
+    fn handle(&mut self, frame: Frame) {
+        if frame == Frame::Aborted {
+            self.fail("Cancelled by the remote side".into());
+            return;
+        }
+        if let Frame::Header { header, .. } = &frame {
+            if matches!(header.kind, ZABORT | ZFERR | ZCAN) {
+                self.fail("The remote side aborted the transfer".into());
+                return;
+            }
+        }
+        match self.role {
+            Role::Receive(_) => self.handle_receive(frame),
+            Role::Send(_) => self.handle_send(frame),
+        }
+    }
+
+    fn handle_receive(&mut self, frame: Frame) {
+        let Role::Receive(r) = &mut self.role else { return };
+        match frame {
+            Frame::Header { header, crc32 } => match header.kind {
+                // Repeats pile up while the user picks a folder; one answer
+                // is enough.
+                ZRQINIT if !r.answered_init => {
+                    r.answered_init = true;
+                    self.out.extend(zrinit());
+                }
+                ZSINIT => {
+                    r.state = RecvState::WaitSinitData;
+                    self.reader.expect_data(crc32);
+                }
+                ZFILE => {
+                    r.answered_init = true;
+                    r.state = RecvState::WaitFileInfo;
+                    self.reader.expect_data(crc32);
+                }
+                ZDATA => {
+                    let Some(incoming) = &r.file else { return };
+                    if header.pos() == incoming.received {
+                        r.state = RecvState::Data;
+                        self.reader.expect_data(crc32);
+                    } else {
+                        self.out.extend(hex_header(Header::at(ZRPOS, incoming.received)));
+                    }
+                }
+                // An EOF at the wrong offset is stale; the sender will follow
+                // our ZRPOS.
+                ZEOF if r.file.as_ref().is_some_and(|f| f.received == header.pos()) => {
+                    if let Some(incoming) = r.file.take() {
+                        let _ = incoming.file.sync_all();
+                        self.events.push(FileEvent::Done(incoming.path));
+                    }
+                    r.state = RecvState::WaitFile;
+                    self.out.extend(zrinit());
+                }
+                ZFIN => {
+                    self.out.extend(hex_header(Header::new(ZFIN, 0)));
+                    self.outcome = Some(Outcome::Done);
+                }
+                _ => {}
+            },
+            Frame::Data { data, end } => match r.state {
+                RecvState::WaitSinitData => {
+                    r.state = RecvState::WaitFile;
+                    self.out.extend(hex_header(Header::new(ZACK, 0)));
+                }
+                RecvState::WaitFileInfo => {
+                    let (name, size) = parse_file_info(&data);
+                    let Some(base) = Path::new(&name).file_name().map(|n| n.to_owned()) else {
+                        r.state = RecvState::WaitFile;
+                        self.out.extend(hex_header(Header::new(ZSKIP, 0)));
+                        return;
+                    };
+                    let path = unique_path(&r.dir, &base.to_string_lossy());
+                    self.events.push(FileEvent::Started {
+                        name: base.to_string_lossy().into_owned(),
+                        total: size.unwrap_or(0),
+                    });
+                    match File::create(&path) {
+                        Ok(file) => {
+                            r.file = Some(Incoming { file, path, received: 0 });
+                            r.state = RecvState::WaitData;
+                            self.out.extend(hex_header(Header::at(ZRPOS, 0)));
+                        }
+                        Err(e) => {
+                            self.events.push(FileEvent::Failed(format!("{}: {}", path.display(), e)));
+                            r.state = RecvState::WaitFile;
+                            self.out.extend(hex_header(Header::new(ZSKIP, 0)));
+                        }
+                    }
+                }
+                RecvState::Data => {
+                    let Some(incoming) = &mut r.file else { return };
+                    if let Err(e) = incoming.file.write_all(&data) {
+                        let message = format!("{}: {}", incoming.path.display(), e);
+                        self.fail(message);
+                        return;
+                    }
+                    incoming.received += data.len() as u64;
+                    let received = incoming.received;
+                    self.events.push(FileEvent::Progress(received));
+                    match end {
+                        ZCRCW => {
+                            r.state = RecvState::WaitData;
+                            self.out.extend(hex_header(Header::at(ZACK, received)));
+                        }
+                        ZCRCQ => self.out.extend(hex_header(Header::at(ZACK, received))),
+                        ZCRCE => r.state = RecvState::WaitData,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            },
+            Frame::Corrupt => match (r.state, &r.file) {
+                (RecvState::Data | RecvState::WaitData, Some(incoming)) => {
+                    r.state = RecvState::WaitData;
+                    self.out.extend(hex_header(Header::at(ZRPOS, incoming.received)));
+                }
+                (RecvState::WaitFileInfo, _) => {
+                    r.state = RecvState::WaitFile;
+                    self.out.extend(hex_header(Header::new(ZNAK, 0)));
+                }
+                _ => {}
+            },
+            Frame::Aborted => {}
+        }
+    }
+
+    fn handle_send(&mut self, frame: Frame) {
+        let Role::Send(s) = &mut self.role else { return };
+        let Frame::Header { header, .. } = frame else { return };
+        match (header.kind, s.state) {
+            (ZRINIT, SendState::WaitInit | SendState::WaitEof) => {
+                let flags = header.flags();
+                s.crc32 = flags & CANFC32 != 0;
+                s.escape_ctl = flags & ESCCTL != 0;
+                s.window = u16::from_le_bytes([header.data[0], header.data[1]]) as u64;
+                if s.state == SendState::WaitEof && s.file.take().is_some() {
+                    self.events.push(FileEvent::Sent);
+                }
+                self.offer_next();
+            }
+            (ZRPOS, SendState::WaitPos | SendState::Sending | SendState::WaitAck | SendState::WaitEof) => {
+                let Some(out) = &mut s.file else { return };
+                if let Err(e) = out.file.seek(SeekFrom::Start(header.pos())) {
+                    self.fail(e.to_string());
+                    return;
+                }
+                out.sent = header.pos();
+                out.unacked = 0;
+                s.state = SendState::Sending;
+                self.out.extend(bin_header(Header::at(ZDATA, header.pos()), s.crc32, s.escape_ctl));
+            }
+            (ZSKIP, SendState::WaitPos | SendState::Sending | SendState::WaitAck | SendState::WaitEof) => {
+                s.file = None;
+                self.events.push(FileEvent::Failed("Skipped by the remote side".into()));
+                self.offer_next();
+            }
+            (ZACK, SendState::WaitAck) => {
+                let Some(out) = &mut s.file else { return };
+                out.unacked = 0;
+                s.state = SendState::Sending;
+                self.out.extend(bin_header(Header::at(ZDATA, out.sent), s.crc32, s.escape_ctl));
+            }
+            (ZNAK, SendState::WaitPos) => {
+                let offer = s.last_offer.clone();
+                self.out.extend(offer);
+            }
+            (ZFIN, SendState::WaitFin) => {
+                self.out.extend_from_slice(b"OO");
+                self.outcome = Some(Outcome::Done);
+            }
+            _ => {}
+        }
+    }
+
+    /// Offer the next file that opens, or finish the session.
+    fn offer_next(&mut self) {
+        let Role::Send(s) = &mut self.role else { return };
+        while let Some(path) = s.files.pop_front() {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            let opened = File::open(&path).and_then(|f| {
+                let meta = f.metadata()?;
+                Ok((f, meta))
+            });
+            let (file, meta) = match opened {
+                Ok(opened) => opened,
+                Err(e) => {
+                    self.events.push(FileEvent::Started { name, total: 0 });
+                    self.events.push(FileEvent::Failed(e.to_string()));
+                    continue;
+                }
+            };
+            let total = meta.len();
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let bytes_left: u64 = total
+                + s.files.iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum::<u64>();
+            let info = format!(
+                "{}\0{} {:o} {:o} 0 {} {}\0",
+                name,
+                total,
+                mtime,
+                0o100644,
+                s.files.len() + 1,
+                bytes_left
+            );
+            let mut offer = bin_header(Header::new(ZFILE, ZCBIN), s.crc32, s.escape_ctl);
+            subpacket(&mut offer, info.as_bytes(), ZCRCW, s.crc32, s.escape_ctl);
+            self.out.extend_from_slice(&offer);
+            s.last_offer = offer;
+            s.file = Some(Outgoing { file, total, sent: 0, unacked: 0 });
+            s.state = SendState::WaitPos;
+            self.events.push(FileEvent::Started { name, total });
+            return;
+        }
+        s.state = SendState::WaitFin;
+        self.out.extend(hex_header(Header::new(ZFIN, 0)));
+    }
+
+    /// The next run of data subpackets for the file being sent.
+    fn stream(&mut self) -> Vec<u8> {
+        let Role::Send(s) = &mut self.role else { return Vec::new() };
+        let Some(out) = &mut s.file else { return Vec::new() };
+        let mut wire = Vec::with_capacity(TRANSMIT_CHUNK + TRANSMIT_CHUNK / 8);
+        let mut block = vec![0u8; SUBPACKET];
+        while wire.len() < TRANSMIT_CHUNK {
+            let mut want = SUBPACKET;
+            if s.window > 0 {
+                want = want.min((s.window - out.unacked) as usize);
+            }
+            let n = match read_full(&mut out.file, &mut block[..want]) {
+                Ok(n) => n,
+                Err(e) => {
+                    let message = e.to_string();
+                    self.fail(message);
+                    return std::mem::take(&mut self.out);
+                }
+            };
+            out.sent += n as u64;
+            out.unacked += n as u64;
+            if n < want || out.sent >= out.total {
+                subpacket(&mut wire, &block[..n], ZCRCE, s.crc32, s.escape_ctl);
+                wire.extend(bin_header(Header::at(ZEOF, out.sent), s.crc32, s.escape_ctl));
+                s.state = SendState::WaitEof;
+                break;
+            }
+            if s.window > 0 && out.unacked >= s.window {
+                subpacket(&mut wire, &block[..n], ZCRCW, s.crc32, s.escape_ctl);
+                s.state = SendState::WaitAck;
+                break;
+            }
+            subpacket(&mut wire, &block[..n], ZCRCG, s.crc32, s.escape_ctl);
+        }
+        self.events.push(FileEvent::Progress(out.sent));
+        wire
+    }
+}
+
+fn zrinit() -> Vec<u8> {
+    hex_header(Header::new(ZRINIT, CANFDX | CANOVIO | CANFC32))
+}
+
+fn read_full(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+impl Engine for Zmodem {
+    fn feed(&mut self, data: &[u8]) {
+        let mut pos = 0;
+        while pos < data.len() {
+            if self.outcome.is_some() {
+                self.leftover.extend_from_slice(&data[pos..]);
+                return;
+            }
+            match self.reader.read(data, &mut pos) {
+                Some(frame) => self.handle(frame),
+                None => break,
+            }
+        }
+    }
+
+    fn wants_transmit(&self) -> bool {
+        !self.out.is_empty()
+            || (self.outcome.is_none() && matches!(&self.role, Role::Send(s) if s.state == SendState::Sending))
+    }
+
+    fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        if !self.out.is_empty() {
+            return Some(std::mem::take(&mut self.out));
+        }
+        if self.wants_transmit() {
+            return Some(self.stream());
+        }
+        None
+    }
+
+    fn take_events(&mut self) -> Vec<FileEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    fn take_leftover(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.leftover)
+    }
+
+    fn trailer(&self) -> &'static [u8] {
+        // The end of the remote's ZFIN header, then from `sz` the "OO" it
+        // signs off with after ours.
+        match self.role {
+            Role::Receive(_) => b"\r\x8aOO",
+            Role::Send(_) => b"\r\x8a",
+        }
+    }
+
+    fn cancel(&mut self, reason: &str) {
+        self.fail(reason.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("reach-zmodem-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Shuttle bytes between two engines until both are done, cutting every
+    /// transmission into small pieces to exercise the parsers' resumption.
+    fn run(sender: &mut Zmodem, receiver: &mut Zmodem) {
+        for _ in 0..100_000 {
+            if sender.outcome.is_some() && receiver.outcome.is_some() && !sender.wants_transmit() {
+                return;
+            }
+            while let Some(bytes) = sender.poll_transmit() {
+                for piece in bytes.chunks(333) {
+                    receiver.feed(piece);
+                }
+            }
+            while let Some(bytes) = receiver.poll_transmit() {
+                for piece in bytes.chunks(7) {
+                    sender.feed(piece);
+                }
+            }
+        }
+        panic!("transfer did not finish");
+    }
+
+    #[test]
+    fn hex_header_matches_lrzsz() {
+        // What `rz` prints when it starts.
+        assert_eq!(zrinit(), b"**\x18B0100000023be50\r\x8a\x11");
+        assert_eq!(hex_header(Header::new(ZRQINIT, 0)), b"**\x18B00000000000000\r\x8a\x11");
+    }
+
+    #[test]
+    fn reader_unescapes_and_checks_crc() {
+        let payload: Vec<u8> = (0..=255u8).chain([ZDLE, ZDLE, b'\r', b'\n']).collect();
+        for use_crc32 in [false, true] {
+            let mut wire = bin_header(Header::at(ZDATA, 7), use_crc32, true);
+            subpacket(&mut wire, &payload, ZCRCE, use_crc32, true);
+            let mut reader = Reader::new();
+            let mut pos = 0;
+            let Some(Frame::Header { header, crc32 }) = reader.read(&wire, &mut pos) else { panic!() };
+            assert_eq!((header.kind, header.pos(), crc32), (ZDATA, 7, use_crc32));
+            reader.expect_data(crc32);
+            assert_eq!(reader.read(&wire, &mut pos), Some(Frame::Data { data: payload.clone(), end: ZCRCE }));
+            assert_eq!(pos, wire.len());
+
+            // Flip one payload byte.
+            let mut bad = wire.clone();
+            let at = bad.len() / 2;
+            bad[at] ^= 0x01;
+            let mut reader = Reader::new();
+            let mut pos = 0;
+            let _ = reader.read(&bad, &mut pos);
+            reader.expect_data(use_crc32);
+            assert_eq!(reader.read(&bad, &mut pos), Some(Frame::Corrupt));
+        }
+    }
+
+    #[test]
+    fn five_cans_abort() {
+        let mut receiver = Zmodem::receive(temp_dir("abort"));
+        receiver.feed(b"**\x18B00000000000000\r\x8a\x11");
+        receiver.feed(CANCEL);
+        assert!(matches!(receiver.outcome(), Some(Outcome::Failed(_))));
+    }
+
+    #[test]
+    fn sends_and_receives_files() {
+        let src = temp_dir("src");
+        let dst = temp_dir("dst");
+        let big: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(src.join("big.bin"), &big).unwrap();
+        std::fs::write(src.join("empty"), b"").unwrap();
+        std::fs::write(dst.join("big.bin"), b"already here").unwrap();
+
+        let mut sender = Zmodem::send(vec![src.join("big.bin"), src.join("missing"), src.join("empty")]);
+        let mut receiver = Zmodem::receive(dst.clone());
+        // The remote `sz` announces itself; the remote `rz` answers with ZRINIT.
+        receiver.feed(&hex_header(Header::new(ZRQINIT, 0)));
+        run(&mut sender, &mut receiver);
+
+        assert_eq!(sender.outcome(), Some(&Outcome::Done));
+        assert_eq!(receiver.outcome(), Some(&Outcome::Done));
+        assert_eq!(std::fs::read(dst.join("big (1).bin")).unwrap(), big);
+        assert_eq!(std::fs::read(dst.join("empty")).unwrap(), b"");
+        assert_eq!(std::fs::read(dst.join("big.bin")).unwrap(), b"already here");
+
+        let sent = sender.take_events();
+        assert!(sent.contains(&FileEvent::Progress(100_000)));
+        assert_eq!(sent.iter().filter(|e| matches!(e, FileEvent::Failed(_))).count(), 1);
+        assert_eq!(sent.iter().filter(|e| **e == FileEvent::Sent).count(), 2);
+        let received = receiver.take_events();
+        assert_eq!(received.iter().filter(|e| matches!(e, FileEvent::Done(_))).count(), 2);
+        assert_eq!(receiver.trailer(), b"\r\x8aOO");
+    }
+
+    #[test]
+    fn honours_a_receiver_window() {
+        let src = temp_dir("win-src");
+        let data = vec![0x5au8; 5000];
+        std::fs::write(src.join("f"), &data).unwrap();
+        let mut sender = Zmodem::send(vec![src.join("f")]);
+        // ZRINIT with a 2048-byte buffer and no full-duplex streaming.
+        sender.feed(&hex_header(Header { kind: ZRINIT, data: [0x00, 0x08, 0, 0] }));
+        sender.feed(&hex_header(Header::at(ZRPOS, 0)));
+        let offer_and_header = sender.poll_transmit().unwrap();
+        let streamed = sender.poll_transmit().unwrap();
+        assert!(offer_and_header.starts_with(&[ZPAD, ZDLE, ZBIN]));
+        assert!(streamed.windows(2).any(|w| w == [ZDLE, ZCRCW]));
+        assert!(!sender.wants_transmit());
+        sender.feed(&hex_header(Header::at(ZACK, 2048)));
+        assert!(sender.wants_transmit());
+    }
+}
@@ -79,6 +79,7 @@ pub async fn credential_is_locked(state: State<'_, AppState>) -> Result<bool, St
 pub async fn credential_lock(state: State<'_, AppState>) -> Result<(), String> {
     let mut manager = state.vault_manager.lock().await;
     manager.lock();
+    state.ssh_agent.clear();
     tracing::info!("Credential vault locked");
     Ok(())
 }
@@ -144,13 +145,22 @@ pub async fn credential_get_password(
         return Ok(None);
     }
 
-    let vault_id = match get_credentials_vault_id_if_exists(&manager) {
+    load_password(&manager, &session_id).await
+}
+
+/// Read the saved password (or key passphrase) for a session, if any.
+/// Shared with `ssh_connect_session`. Caller must have checked the lock state.
+pub(crate) async fn load_password(
+    manager: &crate::vault::VaultManager,
+    session_id: &str,
+) -> Result<Option<String>, String> {
+    let vault_id = match get_credentials_vault_id_if_exists(manager) {
         Some(id) => id,
         None => return Ok(None),
     };
 
     // O(1) lookup by primary key (session_id = secret_id)
-    match manager.read_secret(&vault_id, &session_id).await {
+    match manager.read_secret(&vault_id, session_id).await {
         Ok(plaintext) => {
             use secrecy::ExposeSecret;
             let password = String::from_utf8(plaintext.expose_secret().clone())
@@ -208,6 +218,113 @@ pub async fn credential_delete_password(
     Ok(())
 }
 
+/// Secret ID of a session's TOTP seed in the credentials vault. Kept next to
+/// the saved password rather than in the session JSON, so it is never part of
+/// `session_list` output or a shared session.
+fn totp_secret_id(session_id: &str) -> String {
+    format!("{}:totp", session_id)
+}
+
+/// Save the TOTP secret (base32 or `otpauth://` URI) used to answer
+/// keyboard-interactive verification-code prompts for a session.
+#[tauri::command]
+#[tracing::instrument(skip(secret, state))]
+pub async fn credential_save_totp(
+    state: State<'_, AppState>,
+    session_id: String,
+    secret: String,
+) -> Result<(), String> {
+    // Reject typos now rather than at the next login.
+    crate::ssh::totp::TotpSpec::parse(&secret)?;
+
+    let manager = state.vault_manager.lock().await;
+
+    if manager.is_locked() {
+        return Err("Vault is locked. Set a master password first.".to_string());
+    }
+
+    let vault_id = get_credentials_vault_id(&manager)?;
+    let secret_id = totp_secret_id(&session_id);
+    let plaintext = SecretBox::new(Box::new(secret.trim().as_bytes().to_vec()));
+
+    if manager.secret_exists(&vault_id, &secret_id).await {
+        manager
+            .update_secret(&vault_id, &secret_id, plaintext)
+            .await
+            .map_err(|e| e.to_string())?;
+    } else {
+        manager
+            .create_secret_with_id(&vault_id, &secret_id, &secret_id, SecretCategory::Credential, plaintext)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    tracing::info!("Saved encrypted TOTP secret for session {}", session_id);
+    Ok(())
+}
+
+/// Check if a TOTP secret is saved for a given session. O(1) lookup.
+#[tauri::command]
+#[tracing::instrument(skip(state))]
+pub async fn credential_has_totp(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<bool, String> {
+    let manager = state.vault_manager.lock().await;
+
+    if manager.is_locked() {
+        return Ok(false);
+    }
+
+    match get_credentials_vault_id_if_exists(&manager) {
+        Some(vault_id) => Ok(manager.secret_exists(&vault_id, &totp_secret_id(&session_id)).await),
+        None => Ok(false),
+    }
+}
+
+/// Delete a saved TOTP secret for a session.
+#[tauri::command]
+#[tracing::instrument(skip(state))]
+pub async fn credential_delete_totp(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let manager = state.vault_manager.lock().await;
+
+    if manager.is_locked() {
+        return Err("Vault is locked".to_string());
+    }
+
+    if let Some(vault_id) = get_credentials_vault_id_if_exists(&manager) {
+        let _ = manager.delete_secret(&vault_id, &totp_secret_id(&session_id)).await;
+        tracing::info!("Deleted saved TOTP secret for session {}", session_id);
+    }
+
+    Ok(())
+}
+
+/// Read and parse a session's saved TOTP secret, if any. Never leaves the
+/// backend — only `ssh_connect_session` uses it.
+pub(crate) async fn load_totp(
+    manager: &crate::vault::VaultManager,
+    session_id: &str,
+) -> Result<Option<crate::ssh::totp::TotpSpec>, String> {
+    let Some(vault_id) = get_credentials_vault_id_if_exists(manager) else {
+        return Ok(None);
+    };
+    match manager.read_secret(&vault_id, &totp_secret_id(session_id)).await {
+        Ok(plaintext) => {
+            use secrecy::ExposeSecret;
+            let secret = zeroize::Zeroizing::new(
+                String::from_utf8(plaintext.expose_secret().clone())
+                    .map_err(|e| format!("Invalid UTF-8 in decrypted TOTP secret: {}", e))?,
+            );
+            crate::ssh::totp::TotpSpec::parse(&secret).map(Some)
+        }
+        Err(_) => Ok(None),
+    }
+}
+
 /// Ensure the credentials vault exists and is open (O(1) lookup).
 async fn ensure_credentials_vault(
     manager: &mut crate::vault::VaultManager,
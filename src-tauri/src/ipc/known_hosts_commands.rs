@@ -0,0 +1,88 @@
+//! IPC commands for host-key trust: pinned host keys (known_hosts), trusted
+//! host CAs and certificate pinning.
+
+use std::path::PathBuf;
+
+use crate::ssh::client::expand_tilde;
+use crate::ssh::host_ca::{self, TrustedHostCa};
+use crate::ssh::known_hosts::{self, ImportSummary, KnownHostEntry, KnownHosts};
+
+/// List all pinned host keys, `@revoked` entries included.
+#[tauri::command]
+pub async fn known_hosts_list() -> Result<Vec<KnownHostEntry>, String> {
+    Ok(KnownHosts::load().entries().to_vec())
+}
+
+/// Forget a pinned host key; the next connect to that host prompts again.
+#[tauri::command]
+pub async fn known_hosts_remove(id: String) -> Result<(), String> {
+    let mut known = KnownHosts::load();
+    known.remove(&id)?;
+    known.save()
+}
+
+/// Replace the key pinned for `host:port` with `public_key` — an OpenSSH
+/// public key or an `ssh-keyscan` output line. Returns the new fingerprint.
+#[tauri::command]
+pub async fn known_hosts_repin(host: String, port: u16, public_key: String) -> Result<String, String> {
+    let (key_type, blob) = known_hosts::parse_public_key(&public_key)?;
+    let mut known = KnownHosts::load();
+    let fingerprint = known.pin(&host, port, &key_type, &blob);
+    known.save()?;
+    Ok(fingerprint)
+}
+
+/// Merge an OpenSSH known_hosts file (default `~/.ssh/known_hosts`).
+#[tauri::command]
+pub async fn known_hosts_import(path: Option<String>) -> Result<ImportSummary, String> {
+    let path: PathBuf = match path.filter(|p| !p.trim().is_empty()) {
+        Some(p) => expand_tilde(&p),
+        None => known_hosts::openssh_known_hosts_path()
+            .ok_or_else(|| "Could not determine home directory".to_string())?,
+    };
+    let content = known_hosts::read_known_hosts_file(&path)?;
+    let mut known = KnownHosts::load();
+    let summary = known.import(&content);
+    known.save()?;
+    tracing::info!("Imported known hosts from {}: {:?}", path.display(), summary);
+    Ok(summary)
+}
+
+/// Render the pinned keys and trusted CAs as an OpenSSH known_hosts file.
+#[tauri::command]
+pub async fn known_hosts_export() -> Result<String, String> {
+    Ok(KnownHosts::load().export())
+}
+
+/// List the trusted host certificate authorities.
+#[tauri::command]
+pub async fn host_ca_list() -> Result<Vec<TrustedHostCa>, String> {
+    Ok(host_ca::load())
+}
+
+/// Trust a CA public key for hosts matching `patterns` (OpenSSH pattern list).
+#[tauri::command]
+pub async fn host_ca_add(
+    patterns: String,
+    public_key: String,
+    comment: Option<String>,
+) -> Result<TrustedHostCa, String> {
+    host_ca::add(&patterns, &public_key, comment)
+}
+
+/// Stop trusting a host CA.
+#[tauri::command]
+pub async fn host_ca_remove(id: String) -> Result<(), String> {
+    host_ca::remove(&id)
+}
+
+/// Verify a host certificate against the trusted CAs and trust it for
+/// `host:port` while it stays valid. Returns the certified key's fingerprint.
+#[tauri::command]
+pub async fn host_ca_trust_certificate(
+    host: String,
+    port: u16,
+    certificate: String,
+) -> Result<String, String> {
+    host_ca::trust_certificate(&host, port, &certificate)
+}
@@ -6,12 +6,15 @@ pub mod settings_commands;
 pub mod monitoring_commands;
 #[cfg(desktop)]
 pub mod pty_commands;
+pub mod recording_commands;
 #[cfg(desktop)]
 pub mod serial_commands;
 pub mod session_commands;
 pub mod sftp_commands;
 pub mod ssh_commands;
 pub mod sshconfig_commands;
+pub mod stream_commands;
+pub mod known_hosts_commands;
 pub mod ansible_commands;
 pub mod tofu_commands;
 pub mod toolchain_commands;
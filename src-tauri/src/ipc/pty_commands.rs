@@ -1,10 +1,15 @@
+use tauri::ipc::Channel;
+
 use crate::state::AppState;
+use crate::stream::encoding;
+use crate::stream::OutputSink;
 
 /// Spawn a new local PTY session.
 ///
 /// Creates a pseudo-terminal running the specified shell (or the platform
-/// default). The PTY output is streamed to the frontend via `pty-data-{id}`
-/// events, and `pty-exit-{id}` is emitted when the process exits.
+/// default) in `encoding` (a WHATWG label, UTF-8 when unset). Output is held
+/// until a view calls `pty_attach`, and `pty-exit-{id}` is emitted when the
+/// process exits.
 #[tauri::command]
 pub async fn pty_spawn(
     app: tauri::AppHandle,
@@ -13,16 +18,39 @@ pub async fn pty_spawn(
     shell: Option<String>,
     cols: u16,
     rows: u16,
+    encoding: Option<String>,
 ) -> Result<String, String> {
     tracing::info!("IPC pty_spawn: id={}, shell={:?}, {}x{}", id, shell, cols, rows);
 
+    let encoding = encoding::lookup(encoding.as_deref()).map_err(|e| e.to_string())?;
+    let agent_socket = state.ssh_agent.shell_socket();
     let mut manager = state
         .pty_manager
         .lock()
         .map_err(|e| format!("Failed to lock PTY manager: {}", e))?;
 
     manager
-        .spawn(&id, shell, cols, rows, app)
+        .spawn(&id, shell, cols, rows, agent_socket, encoding, app)
+        .map_err(|e| e.to_string())
+}
+
+/// A terminal view is ready for a PTY's output: it arrives as raw bytes on
+/// `on_data`, starting with whatever the shell printed before. The view
+/// acknowledges rendered output with `stream_ack`.
+#[tauri::command]
+pub async fn pty_attach(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    on_data: Channel,
+) -> Result<(), String> {
+    let manager = state
+        .pty_manager
+        .lock()
+        .map_err(|e| format!("Failed to lock PTY manager: {}", e))?;
+
+    let flow = state.streams.register(&id);
+    manager
+        .attach(&id, OutputSink::new(on_data, flow))
         .map_err(|e| e.to_string())
 }
 
@@ -40,7 +68,9 @@ pub async fn pty_write(
 
     manager
         .write(&id, &data)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    state.recordings.input(&id, &data);
+    Ok(())
 }
 
 /// Resize an existing PTY session.
@@ -60,7 +90,9 @@ pub async fn pty_resize(
 
     manager
         .resize(&id, cols, rows)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    state.recordings.resize(&id, cols, rows);
+    Ok(())
 }
 
 /// Close a PTY session, killing the child process.
@@ -76,6 +108,9 @@ pub async fn pty_close(
         .lock()
         .map_err(|e| format!("Failed to lock PTY manager: {}", e))?;
 
+    // Wake the output pump if it's waiting on the view, so the reader sees
+    // the shell go.
+    state.streams.remove(&id);
     manager
         .close(&id)
         .map_err(|e| e.to_string())
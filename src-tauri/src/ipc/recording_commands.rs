@@ -0,0 +1,65 @@
+use tauri::State;
+
+use crate::recording::cast::RecordingKind;
+use crate::recording::manager::{RecordingConfig, RecordingInfo};
+use crate::state::AppState;
+
+#[tauri::command]
+pub async fn recording_get_config(state: State<'_, AppState>) -> Result<RecordingConfig, String> {
+    Ok(state.recordings.config())
+}
+
+/// Apply the recording settings. Retention is enforced straight away.
+#[tauri::command]
+pub async fn recording_set_config(
+    state: State<'_, AppState>,
+    config: RecordingConfig,
+) -> Result<(), String> {
+    state.recordings.set_config(config);
+    Ok(())
+}
+
+/// Start recording a terminal that is already open (SSH connection id, PTY id
+/// or serial port name). Returns the recording's file name.
+#[tauri::command]
+pub async fn recording_start(
+    state: State<'_, AppState>,
+    id: String,
+    kind: RecordingKind,
+    title: String,
+    cols: u16,
+    rows: u16,
+) -> Result<String, String> {
+    state
+        .recordings
+        .start(&id, kind, &title, cols, rows)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn recording_stop(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state.recordings.stop(&id);
+    Ok(())
+}
+
+/// Ids of the terminals currently being recorded.
+#[tauri::command]
+pub async fn recording_active(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.recordings.active_ids())
+}
+
+#[tauri::command]
+pub async fn recording_list(state: State<'_, AppState>) -> Result<Vec<RecordingInfo>, String> {
+    state.recordings.list().map_err(|e| e.to_string())
+}
+
+/// The `.cast` file contents, for playback.
+#[tauri::command]
+pub async fn recording_read(state: State<'_, AppState>, name: String) -> Result<String, String> {
+    state.recordings.read(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn recording_delete(state: State<'_, AppState>, name: String) -> Result<(), String> {
+    state.recordings.delete(&name).map_err(|e| e.to_string())
+}
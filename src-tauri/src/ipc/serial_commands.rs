@@ -1,5 +1,11 @@
+use std::path::PathBuf;
+
+use tauri::ipc::Channel;
+
 use crate::serial::port::{self, SerialPortInfo};
 use crate::state::AppState;
+use crate::stream::encoding;
+use crate::stream::OutputSink;
 
 /// List available serial ports on the system.
 #[tauri::command]
@@ -9,18 +15,31 @@ pub async fn serial_list_ports(
     port::list_ports().map_err(|e| e.to_string())
 }
 
-/// Open a serial port connection.
+/// Open a serial port connection. What the device sends arrives as raw
+/// bytes on `on_data`, transcoded to UTF-8 from `encoding` (a WHATWG label,
+/// UTF-8 when unset); the view acknowledges rendered output with
+/// `stream_ack`.
 #[tauri::command]
 pub async fn serial_open(
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
     port_name: String,
     baud_rate: u32,
+    encoding: Option<String>,
+    on_data: Channel,
 ) -> Result<(), String> {
+    let encoding = encoding::lookup(encoding.as_deref()).map_err(|e| e.to_string())?;
     let mut serial_manager = state.serial_manager.lock().await;
+    if serial_manager.is_open(&port_name) {
+        return Err(port::SerialError::AlreadyOpen(port_name).to_string());
+    }
+    let flow = state.streams.register(&port_name);
     serial_manager
-        .open_port(&port_name, baud_rate, &app_handle)
-        .map_err(|e| e.to_string())
+        .open_port(&port_name, baud_rate, encoding, OutputSink::new(on_data, flow), &app_handle)
+        .map_err(|e| {
+            state.streams.remove(&port_name);
+            e.to_string()
+        })
 }
 
 /// Close an open serial port.
@@ -30,6 +49,7 @@ pub async fn serial_close(
     port_name: String,
 ) -> Result<(), String> {
     let mut serial_manager = state.serial_manager.lock().await;
+    state.streams.remove(&port_name);
     serial_manager
         .close_port(&port_name)
         .await
@@ -46,5 +66,23 @@ pub async fn serial_send(
     let serial_manager = state.serial_manager.lock().await;
     serial_manager
         .send_data(&port_name, data.as_bytes())
+        .map_err(|e| e.to_string())?;
+    state.recordings.input(&port_name, data.as_bytes());
+    Ok(())
+}
+
+/// Answer a ZMODEM/trzsz transfer the device started on
+/// `inband-transfer-{portName}`: files to upload, or a folder to download
+/// into. No paths turns it down.
+#[tauri::command]
+pub async fn serial_transfer_respond(
+    state: tauri::State<'_, AppState>,
+    port_name: String,
+    paths: Option<Vec<String>>,
+) -> Result<(), String> {
+    let paths = paths.map(|p| p.into_iter().map(PathBuf::from).collect());
+    let serial_manager = state.serial_manager.lock().await;
+    serial_manager
+        .transfer_respond(&port_name, paths)
         .map_err(|e| e.to_string())
 }
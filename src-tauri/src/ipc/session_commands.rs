@@ -102,13 +102,23 @@ pub async fn session_get(
         return Err("Vault is locked".to_string());
     }
 
+    load_session(&manager, &session_id).await
+}
+
+/// Load a session by ID from whichever vault holds it. Used by `session_get`
+/// and by `ssh_connect_session`, which resolves credentials without the
+/// webview ever seeing them. Caller must have checked that the vault is unlocked.
+pub(crate) async fn load_session(
+    manager: &crate::vault::VaultManager,
+    session_id: &str,
+) -> Result<SessionConfig, String> {
     // Find which vault contains this session
-    let vault_id = find_session_vault(&manager, &session_id).await
+    let vault_id = find_session_vault(manager, session_id).await
         .ok_or_else(|| format!("Session not found: {}", session_id))?;
 
     // O(1) lookup by primary key
     let plaintext = manager
-        .read_secret(&vault_id, &session_id)
+        .read_secret(&vault_id, session_id)
         .await
         .map_err(|_| format!("Session not found: {}", session_id))?;
 
@@ -137,6 +147,15 @@ pub async fn session_create(
     jump_chain: Option<Vec<JumpHostConfig>>,
     proxy: Option<crate::state::ProxyConfig>,
     shell: Option<String>,
+    keepalive: Option<crate::state::KeepaliveConfig>,
+    crypto: Option<crate::state::CryptoPolicy>,
+    agent_forwarding: Option<crate::state::AgentForwarding>,
+    identities_only: Option<bool>,
+    env: Option<std::collections::HashMap<String, String>>,
+    request_tty: Option<crate::state::RequestTty>,
+    record: Option<bool>,
+    encoding: Option<String>,
+    tunnels: Option<Vec<crate::state::TunnelConfig>>,
 ) -> Result<SessionConfig, String> {
     let mut manager = state.vault_manager.lock().await;
 
@@ -177,6 +196,15 @@ pub async fn session_create(
         jump_chain,
         proxy,
         shell,
+        keepalive,
+        crypto,
+        agent_forwarding,
+        identities_only,
+        env,
+        request_tty,
+        record,
+        encoding,
+        tunnels,
     };
 
     let json = serde_json::to_string(&session).map_err(|e| e.to_string())?;
@@ -1,6 +1,24 @@
-use crate::state::AppState;
-use crate::ssh::client::{AuthParams, ConnectionInfo, JumpHostParams, SshManager, exec_on_connection};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+
+use tauri::ipc::Channel;
+
+use crate::state::{AppState, AuthMethod, JumpHostConfig};
+use crate::ssh::client::{
+    AuthParams, ConnectSpec, ConnectionInfo, JumpHostParams, KeyAuth, KeySource, RemoteCommand,
+    SshManager,
+};
 use crate::plugin::hooks;
+use crate::recording::cast::RecordingKind;
+use crate::ssh::client::expand_tilde;
+use crate::ssh::keygen::{self, DeployOutcome, KeyAlgorithm, PublicKeyInfo};
+use crate::ssh::scrollback::ScrollbackMatch;
+use crate::ssh::vault_agent::{AgentKeyOptions, AgentStatus};
+use crate::stream::encoding;
+use crate::stream::OutputSink;
+use crate::vault::{SecretCategory, VaultManager};
+use secrecy::{ExposeSecret, SecretBox};
 
 /// Parameters for a jump host received from the frontend.
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -13,6 +31,22 @@ pub struct JumpHostConnectParams {
     pub password: Option<String>,
     pub key_path: Option<String>,
     pub key_passphrase: Option<String>,
+    /// Embedded private key (e.g. from a shared session); used instead of
+    /// `key_path` when present.
+    #[serde(default)]
+    pub key_content: Option<String>,
+    /// OpenSSH user certificate to offer with the key.
+    #[serde(default)]
+    pub certificate: Option<String>,
+    /// Saved session this hop stands for; its stored host and credentials
+    /// replace the fields above.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// How to reach this hop when it is the first one.
+    #[serde(default)]
+    pub proxy: Option<crate::state::ProxyConfig>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 fn build_auth(
@@ -20,6 +54,8 @@ fn build_auth(
     password: Option<String>,
     key_path: Option<String>,
     key_passphrase: Option<String>,
+    key_content: Option<String>,
+    certificate: Option<String>,
 ) -> Result<AuthParams, String> {
     // The frontend currently picks one primary method, but the backend
     // cascades through key → agent → password regardless. Populating optional
@@ -31,9 +67,17 @@ fn build_auth(
             auth.password = Some(password.ok_or("Password required for password auth")?);
         }
         "key" => {
-            auth.key = Some(crate::ssh::client::KeyAuth {
-                path: key_path.ok_or("Key path required for key auth")?,
+            // Embedded key material wins over a path: a shared session carries
+            // the key itself, and its path (if any) is the sharer's, not ours.
+            let source = match (key_content.filter(|c| !c.trim().is_empty()), key_path) {
+                (Some(content), _) => KeySource::Embedded(zeroize::Zeroizing::new(content)),
+                (None, Some(path)) => KeySource::Path(path),
+                (None, None) => return Err("Key path or embedded key required for key auth".into()),
+            };
+            auth.key = Some(KeyAuth {
+                source,
                 passphrase: key_passphrase,
+                certificate: certificate.filter(|c| !c.trim().is_empty()),
             });
             // Allow callers to also pass a password as a fallback.
             auth.password = password.filter(|p| !p.is_empty());
@@ -58,19 +102,31 @@ pub async fn ssh_connect(
     password: Option<String>,
     key_path: Option<String>,
     key_passphrase: Option<String>,
+    key_content: Option<String>,
+    certificate: Option<String>,
     cols: u16,
     rows: u16,
     jump_chain: Option<Vec<JumpHostConnectParams>>,
     proxy: Option<crate::state::ProxyConfig>,
     shell: Option<String>,
     inject_colors: Option<bool>,
+    keepalive: Option<crate::state::KeepaliveConfig>,
+    crypto: Option<crate::state::CryptoPolicy>,
+    agent_forwarding: Option<crate::state::AgentForwarding>,
+    identities_only: Option<bool>,
+    env: Option<HashMap<String, String>>,
+    request_tty: Option<crate::state::RequestTty>,
+    record: Option<bool>,
+    encoding: Option<String>,
 ) -> Result<String, String> {
     // Default ON when the frontend doesn't specify (back-compat).
     let inject_colors = inject_colors.unwrap_or(true);
+    let encoding = encoding::lookup(encoding.as_deref()).map_err(|e| e.to_string())?;
     tracing::info!(
-        "ssh_connect IPC: id={}, host={}, port={}, user={}, auth_method='{}', has_key_path={}, has_password={}, has_passphrase={}, has_proxy={}, has_jump={}",
+        "ssh_connect IPC: id={}, host={}, port={}, user={}, auth_method='{}', has_key_path={}, has_key_content={}, has_password={}, has_passphrase={}, has_proxy={}, has_jump={}",
         id, host, port, username, auth_method,
         key_path.as_deref().map(|p| !p.is_empty()).unwrap_or(false),
+        key_content.as_deref().map(|c| !c.is_empty()).unwrap_or(false),
         password.as_deref().map(|p| !p.is_empty()).unwrap_or(false),
         key_passphrase.as_deref().map(|p| !p.is_empty()).unwrap_or(false),
         proxy.is_some(),
@@ -79,59 +135,242 @@ pub async fn ssh_connect(
     if let Some(s) = shell.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
         tracing::info!("ssh_connect: per-session login shell override = '{}'", s);
     }
-    let auth = build_auth(&auth_method, password, key_path, key_passphrase)?;
+    let auth = build_auth(&auth_method, password, key_path, key_passphrase, key_content, certificate)?
+        .with_identities_only(identities_only.unwrap_or(false));
+
+    let jump_chain = jump_chain.unwrap_or_default();
+    let mut jump_params = Vec::with_capacity(jump_chain.len());
+    let mut seen = HashSet::new();
+    for j in jump_chain {
+        if let Some(session_id) = j.session_id {
+            let manager = state.vault_manager.lock().await;
+            if manager.is_locked() {
+                return Err("Vault is locked".to_string());
+            }
+            jump_params.extend(session_as_hops(&manager, session_id, j.timeout_secs, &mut seen).await?);
+            continue;
+        }
+        let jauth = build_auth(
+            &j.auth_method,
+            j.password,
+            j.key_path,
+            j.key_passphrase,
+            j.key_content,
+            j.certificate,
+        )?;
+        jump_params.push(JumpHostParams {
+            host: j.host,
+            port: j.port,
+            username: j.username,
+            auth: jauth,
+            proxy: j.proxy,
+            timeout_secs: j.timeout_secs,
+        });
+    }
+
+    let spec = ConnectSpec {
+        host,
+        port,
+        username,
+        auth,
+        jump_chain: jump_params,
+        proxy,
+        shell,
+        inject_colors,
+        keepalive: keepalive.unwrap_or_default(),
+        crypto: crypto.unwrap_or_default(),
+        agent_forwarding,
+        env: env.unwrap_or_default(),
+        request_tty: request_tty.unwrap_or_default(),
+        encoding,
+    };
+    open_connection(app, &state, id, spec, cols, rows, record).await
+}
+
+/// Connect to a saved session by ID. The session, its credentials and those of
+/// every jump host are read from the vault here, so no secret is serialized
+/// into the webview. Returns the new connection ID (generated when `id` is
+/// not supplied by the caller).
+#[tauri::command]
+pub async fn ssh_connect_session(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    cols: u16,
+    rows: u16,
+    id: Option<String>,
+    inject_colors: Option<bool>,
+) -> Result<String, String> {
+    let (session, saved_secret, totp, jump_params) = {
+        let manager = state.vault_manager.lock().await;
+        if manager.is_locked() {
+            return Err("Vault is locked".to_string());
+        }
+        let session = crate::ipc::session_commands::load_session(&manager, &session_id).await?;
+        let saved = crate::ipc::credential_commands::load_password(&manager, &session_id).await?;
+        let totp = crate::ipc::credential_commands::load_totp(&manager, &session_id).await?;
+        let mut seen = HashSet::from([session_id.clone()]);
+        let chain = session.jump_chain.clone().unwrap_or_default();
+        let jump_params = resolve_jump_chain(&manager, chain, &mut seen).await?;
+        (session, saved, totp, jump_params)
+    };
+
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    tracing::info!(
+        "ssh_connect_session IPC: id={}, session={}, host={}, port={}, has_proxy={}, has_jump={}",
+        id, session_id, session.host, session.port,
+        session.proxy.is_some(),
+        session.jump_chain.as_ref().map(|c| c.len()).unwrap_or(0),
+    );
+
+    let mut auth = auth_from_session(&session.auth_method, saved_secret)?
+        .with_identities_only(session.identities_only.unwrap_or(false));
+    auth.totp = totp;
+    let encoding = encoding::lookup(session.encoding.as_deref()).map_err(|e| e.to_string())?;
+
+    let spec = ConnectSpec {
+        host: session.host,
+        port: session.port,
+        username: session.username,
+        auth,
+        jump_chain: jump_params,
+        proxy: session.proxy,
+        shell: session.shell,
+        inject_colors: inject_colors.unwrap_or(true),
+        keepalive: session.keepalive.unwrap_or_default(),
+        crypto: session.crypto.unwrap_or_default(),
+        agent_forwarding: session.agent_forwarding,
+        env: session.env.unwrap_or_default(),
+        request_tty: session.request_tty.unwrap_or_default(),
+        encoding,
+    };
+    open_connection(app, &state, id, spec, cols, rows, session.record).await
+}
+
+/// Map a stored `AuthMethod` onto connect parameters. `saved_secret` is the
+/// password/passphrase remembered in the credentials vault, used when the
+/// session itself doesn't carry one.
+fn auth_from_session(method: &AuthMethod, saved_secret: Option<String>) -> Result<AuthParams, String> {
+    let non_empty = |s: &Option<String>| s.clone().filter(|v| !v.is_empty());
+    match method {
+        AuthMethod::Password { password } => {
+            let password = non_empty(password)
+                .or(saved_secret)
+                .ok_or("No saved password for this session")?;
+            build_auth("password", Some(password), None, None, None, None)
+        }
+        AuthMethod::Key { path, passphrase, key_content, certificate } => build_auth(
+            "key",
+            None,
+            non_empty(path),
+            non_empty(passphrase).or(saved_secret),
+            key_content.clone(),
+            certificate.clone(),
+        ),
+        AuthMethod::Agent => build_auth("agent", None, None, None, None, None),
+    }
+}
+
+/// Connect parameters for a stored jump chain, expanding hops that stand for
+/// saved sessions. `seen` holds the sessions already on the path, so a chain
+/// that leads back to one of them is refused rather than followed forever.
+fn resolve_jump_chain<'a>(
+    manager: &'a VaultManager,
+    chain: Vec<JumpHostConfig>,
+    seen: &'a mut HashSet<String>,
+) -> Pin<Box<dyn Future<Output = Result<Vec<JumpHostParams>, String>> + Send + 'a>> {
+    Box::pin(async move {
+        let mut hops = Vec::with_capacity(chain.len());
+        for j in chain {
+            match j.session_id {
+                Some(session_id) => {
+                    hops.extend(session_as_hops(manager, session_id, j.timeout_secs, seen).await?);
+                }
+                // A hop entered by hand carries its own credentials. Vault
+                // secrets only reach a hop that names its saved session.
+                None => hops.push(JumpHostParams {
+                    auth: auth_from_session(&j.auth_method, None).map_err(|e| {
+                        format!("Jump host {}@{}:{}: {}", j.username, j.host, j.port, e)
+                    })?,
+                    host: j.host,
+                    port: j.port,
+                    username: j.username,
+                    proxy: j.proxy,
+                    timeout_secs: j.timeout_secs,
+                }),
+            }
+        }
+        Ok(hops)
+    })
+}
+
+/// The hops that reach a saved session used as a jump host: its own jump
+/// chain, then the session itself, with the session's proxy dialling the
+/// first of them.
+fn session_as_hops<'a>(
+    manager: &'a VaultManager,
+    session_id: String,
+    timeout_secs: Option<u64>,
+    seen: &'a mut HashSet<String>,
+) -> Pin<Box<dyn Future<Output = Result<Vec<JumpHostParams>, String>> + Send + 'a>> {
+    Box::pin(async move {
+        if !seen.insert(session_id.clone()) {
+            return Err(format!("Jump chain loops back to session {}", session_id));
+        }
+        let session = crate::ipc::session_commands::load_session(manager, &session_id).await?;
+        let saved = crate::ipc::credential_commands::load_password(manager, &session_id).await?;
+        let mut auth = auth_from_session(&session.auth_method, saved)?
+            .with_identities_only(session.identities_only.unwrap_or(false));
+        auth.totp = crate::ipc::credential_commands::load_totp(manager, &session_id).await?;
+
+        let mut hops = resolve_jump_chain(manager, session.jump_chain.unwrap_or_default(), seen).await?;
+        let mut hop = JumpHostParams {
+            host: session.host,
+            port: session.port,
+            username: session.username,
+            auth,
+            proxy: None,
+            timeout_secs,
+        };
+        match hops.first_mut() {
+            Some(first) if first.proxy.is_none() => first.proxy = session.proxy,
+            Some(_) => {}
+            None => hop.proxy = session.proxy,
+        }
+        hops.push(hop);
+        Ok(hops)
+    })
+}
 
+/// Establish, register and announce a connection. Shared by `ssh_connect`
+/// (credentials from the webview) and `ssh_connect_session` (from the vault).
+/// `record` is the session's own recording choice; `None` follows the
+/// global setting.
+async fn open_connection(
+    app: tauri::AppHandle,
+    state: &AppState,
+    id: String,
+    spec: ConnectSpec,
+    cols: u16,
+    rows: u16,
+    record: Option<bool>,
+) -> Result<String, String> {
     // Establish the connection WITHOUT holding the global ssh_manager lock. The
     // handshake/auth/shell setup can take up to the connect timeout (longer if a
     // host hangs); holding the lock across it would block ssh_send / ssh_resize /
     // ssh_disconnect on every other live connection. We lock only afterwards,
     // briefly, to register the finished connection (a single HashMap insert).
-    let conn = if let Some(chain) = jump_chain {
-        if chain.is_empty() {
-            // No jump hosts, connect directly
-            SshManager::connect(&id, &host, port, &username, auth, cols, rows, app.clone(), proxy, shell, inject_colors)
-                .await
-                .map_err(|e| e.to_string())?
-        } else {
-            // Build jump host params
-            let jump_params: Result<Vec<JumpHostParams>, String> = chain
-                .into_iter()
-                .map(|j| {
-                    let jauth = build_auth(
-                        &j.auth_method,
-                        j.password,
-                        j.key_path,
-                        j.key_passphrase,
-                    )?;
-                    Ok(JumpHostParams {
-                        host: j.host,
-                        port: j.port,
-                        username: j.username,
-                        auth: jauth,
-                    })
-                })
-                .collect();
-
-            SshManager::connect_via_jump(
-                &id,
-                &host,
-                port,
-                &username,
-                auth,
-                jump_params?,
-                cols,
-                rows,
-                app.clone(),
-                shell,
-                inject_colors,
-            )
-            .await
-            .map_err(|e| e.to_string())?
+    let (host, username) = (spec.host.clone(), spec.username.clone());
+
+    // Started before the handshake so the login banner makes it in.
+    let title = format!("{}@{}", username, host);
+    state.recordings.auto_start(&id, RecordingKind::Ssh, &title, cols, rows, record);
+    let conn = match SshManager::establish(&id, std::sync::Arc::new(spec), cols, rows, app.clone()).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            state.recordings.discard(&id);
+            return Err(e.to_string());
         }
-    } else {
-        SshManager::connect(&id, &host, port, &username, auth, cols, rows, app.clone(), proxy, shell, inject_colors)
-            .await
-            .map_err(|e| e.to_string())?
     };
 
     // Register the finished connection under a brief lock, released immediately
@@ -153,6 +392,44 @@ pub async fn ssh_connect(
     Ok(connection_id)
 }
 
+/// Open another shell on an existing connection's SSH session (like OpenSSH
+/// ControlMaster): no new handshake or authentication. Returns the new
+/// connection id, which streams its output once a terminal attaches and is
+/// closed with `ssh_disconnect` like any other.
+#[tauri::command]
+pub async fn ssh_open_channel(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    cols: u16,
+    rows: u16,
+    id: Option<String>,
+    shell: Option<String>,
+    inject_colors: Option<bool>,
+) -> Result<String, String> {
+    let master = {
+        let manager = state.ssh_manager.lock().await;
+        manager.share(&connection_id).map_err(|e| e.to_string())?
+    };
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let conn = SshManager::open_channel(&id, master, cols, rows, app.clone(), shell, inject_colors.unwrap_or(true))
+        .await
+        .map_err(|e| e.to_string())?;
+    let info = state.ssh_manager.lock().await.register(conn);
+    let title = format!("{}@{}", info.username, info.host);
+    state.recordings.auto_start(&info.id, RecordingKind::Ssh, &title, cols, rows, None);
+
+    let hook = hooks::session_connected(&info.id, &info.host, &info.username);
+    let plugin_mgr = state.plugin_manager.clone();
+    tokio::spawn(async move {
+        let mut mgr = plugin_mgr.lock().await;
+        mgr.dispatch_hook(&hook, Some(&app)).await;
+    });
+
+    Ok(info.id)
+}
+
 #[tauri::command]
 pub async fn ssh_send(
     state: tauri::State<'_, AppState>,
@@ -163,15 +440,85 @@ pub async fn ssh_send(
     manager.send_data(&connection_id, &data).map_err(|e| e.to_string())
 }
 
-/// Called by the terminal once its data listener is attached: flushes any
-/// buffered remote output (motd/banner) and switches to live streaming.
+/// Called by the terminal once it's ready for output, which arrives as raw
+/// bytes on `on_data`: flushes any buffered remote output (motd/banner) and
+/// switches to live streaming. The view acknowledges rendered output with
+/// `stream_ack`.
 #[tauri::command]
 pub async fn ssh_ready(
     state: tauri::State<'_, AppState>,
     connection_id: String,
+    on_data: Channel,
+) -> Result<(), String> {
+    let manager = state.ssh_manager.lock().await;
+    let flow = state.streams.register(&connection_id);
+    manager
+        .mark_ready(&connection_id, OutputSink::new(on_data, flow))
+        .map_err(|e| e.to_string())
+}
+
+/// Attach a terminal to a running connection, e.g. a tab reopened after it
+/// was closed or the window reloaded. The connection's scrollback is replayed
+/// on `on_data` before live output resumes.
+#[tauri::command]
+pub async fn ssh_attach(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    on_data: Channel,
+) -> Result<(), String> {
+    let manager = state.ssh_manager.lock().await;
+    let flow = state.streams.register(&connection_id);
+    manager
+        .attach(&connection_id, OutputSink::new(on_data, flow))
+        .map_err(|e| e.to_string())
+}
+
+/// Stop streaming a connection's output without closing it. Output keeps
+/// collecting in the scrollback until a terminal attaches again.
+#[tauri::command]
+pub async fn ssh_detach(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+) -> Result<(), String> {
+    let manager = state.ssh_manager.lock().await;
+    state.streams.remove(&connection_id);
+    manager.detach(&connection_id).map_err(|e| e.to_string())
+}
+
+/// Find lines of a connection's scrollback containing `query`, escape codes
+/// removed. Case-insensitive unless `case_sensitive` is set; at most `limit`
+/// (default 200) hits, oldest first.
+#[tauri::command]
+pub async fn ssh_scrollback_search(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    query: String,
+    case_sensitive: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<ScrollbackMatch>, String> {
+    if !state.ssh_manager.lock().await.is_connected(&connection_id) {
+        return Err(format!("Connection not found: {}", connection_id));
+    }
+    Ok(state.scrollback.search(
+        &connection_id,
+        &query,
+        case_sensitive.unwrap_or(false),
+        limit.unwrap_or(200),
+    ))
+}
+
+/// Answer a ZMODEM/trzsz transfer the remote started on `inband-transfer-{id}`:
+/// the files to upload, or a single folder to download into. No paths (or
+/// an empty list) turns the transfer down.
+#[tauri::command]
+pub async fn ssh_transfer_respond(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    paths: Option<Vec<String>>,
 ) -> Result<(), String> {
+    let paths = paths.map(|p| p.iter().map(|path| expand_tilde(path)).collect());
     let manager = state.ssh_manager.lock().await;
-    manager.mark_ready(&connection_id).map_err(|e| e.to_string())
+    manager.transfer_respond(&connection_id, paths).map_err(|e| e.to_string())
 }
 
 /// The frontend's host-key verification dialog reports the user's decision,
@@ -181,6 +528,47 @@ pub fn ssh_hostkey_response(prompt_id: String, accept: bool) {
     crate::ssh::client::resolve_hostkey_prompt(&prompt_id, accept);
 }
 
+/// The frontend's agent confirmation dialog allows or refuses one signature
+/// request, from a remote host (forwarding) or a local program (built-in agent).
+#[tauri::command]
+pub fn ssh_agent_confirm_response(prompt_id: String, allow: bool) {
+    crate::ssh::agent_forward::resolve_agent_prompt(&prompt_id, allow);
+}
+
+/// State of the built-in SSH agent and the vault keys it can serve.
+#[tauri::command]
+pub async fn ssh_agent_status(state: tauri::State<'_, AppState>) -> Result<AgentStatus, String> {
+    let vault = state.vault_manager.lock().await;
+    Ok(state.ssh_agent.status(&vault).await)
+}
+
+/// Turn the built-in SSH agent on or off. Applies to terminals opened afterwards.
+#[tauri::command]
+pub async fn ssh_agent_set_enabled(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    state.ssh_agent.set_enabled(enabled, &app)
+}
+
+/// Set how the built-in agent offers one vault key.
+#[tauri::command]
+pub async fn ssh_agent_set_key_options(
+    state: tauri::State<'_, AppState>,
+    secret_id: String,
+    options: AgentKeyOptions,
+) -> Result<(), String> {
+    state.ssh_agent.set_key_options(&secret_id, options)
+}
+
+/// The frontend's keyboard-interactive dialog reports the user's answers (one
+/// per prompt, in order), or `None` if they cancelled.
+#[tauri::command]
+pub fn ssh_auth_prompt_response(prompt_id: String, responses: Option<Vec<String>>) {
+    crate::ssh::client::resolve_auth_prompt(&prompt_id, responses);
+}
+
 #[tauri::command]
 pub async fn ssh_resize(
     state: tauri::State<'_, AppState>,
@@ -201,6 +589,8 @@ pub async fn ssh_disconnect(
     let mut manager = state.ssh_manager.lock().await;
     manager.disconnect(&connection_id).map_err(|e| e.to_string())?;
     drop(manager);
+    state.recordings.stop(&connection_id);
+    state.scrollback.remove(&connection_id);
 
     // Fire-and-forget hook dispatch (see ssh_connect for rationale).
     let hook = hooks::session_disconnected(&connection_id);
@@ -236,8 +626,8 @@ pub async fn ssh_detect_os(
     };
 
     // Try /etc/os-release first (standard on modern Linux)
-    if let Ok(output) = exec_on_connection(&handle, "cat /etc/os-release 2>/dev/null").await {
-        for line in output.lines() {
+    if let Ok(output) = RemoteCommand::new("cat /etc/os-release 2>/dev/null").run(&handle).await {
+        for line in output.stdout_lossy().lines() {
             // Match the ID= line (not ID_LIKE=)
             if let Some(rest) = line.strip_prefix("ID=") {
                 let id = rest.trim().trim_matches('"').to_lowercase();
@@ -249,8 +639,8 @@ pub async fn ssh_detect_os(
     }
 
     // Fallback: uname -s for non-Linux systems
-    if let Ok(output) = exec_on_connection(&handle, "uname -s 2>/dev/null").await {
-        let os = output.trim().to_lowercase();
+    if let Ok(output) = RemoteCommand::new("uname -s 2>/dev/null").run(&handle).await {
+        let os = output.stdout_lossy().trim().to_lowercase();
         if !os.is_empty() {
             return Ok(os);
         }
@@ -266,3 +656,187 @@ pub async fn ssh_detect_os(
 pub fn inspect_key_file(path: String) -> crate::ssh::keyfile::KeyFileInfo {
     crate::ssh::keyfile::classify_path(&path)
 }
+
+/// Where a newly generated or imported key goes.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum KeyDestination {
+    /// An `ssh_key` secret in the given vault (the vault encrypts it).
+    Vault { vault_id: String, name: String },
+    /// `path` and `path.pub` on disk, optionally passphrase-protected.
+    File {
+        path: String,
+        #[serde(default)]
+        passphrase: Option<String>,
+    },
+}
+
+/// Where to read a private key from to export its public half or import it.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum PrivateKeySource {
+    File {
+        path: String,
+        #[serde(default)]
+        passphrase: Option<String>,
+    },
+    Vault { vault_id: String, secret_id: String },
+    Content {
+        content: String,
+        #[serde(default)]
+        passphrase: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedKey {
+    pub public: PublicKeyInfo,
+    /// Set when stored in a vault.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_id: Option<String>,
+    /// Set when written to disk: the private key's path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Generate an SSH keypair into a vault or onto disk.
+#[tauri::command]
+pub async fn ssh_key_generate(
+    state: tauri::State<'_, AppState>,
+    algorithm: KeyAlgorithm,
+    rsa_bits: Option<usize>,
+    comment: Option<String>,
+    destination: KeyDestination,
+) -> Result<GeneratedKey, String> {
+    let passphrase = match &destination {
+        KeyDestination::File { passphrase, .. } => passphrase.clone(),
+        KeyDestination::Vault { .. } => None,
+    };
+    let comment = comment.unwrap_or_default();
+    // RSA generation takes a noticeable while; keep it off the async workers.
+    let (private, public) = tokio::task::spawn_blocking(move || {
+        keygen::generate(algorithm, rsa_bits, &comment, passphrase.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    store_key(&state, private, public, destination, "Generated").await
+}
+
+/// Save a key pair to its destination. `verb` only feeds the log line.
+async fn store_key(
+    state: &AppState,
+    private: zeroize::Zeroizing<String>,
+    public: PublicKeyInfo,
+    destination: KeyDestination,
+    verb: &str,
+) -> Result<GeneratedKey, String> {
+    match destination {
+        KeyDestination::Vault { vault_id, name } => {
+            let manager = state.vault_manager.lock().await;
+            let secret_id = manager
+                .create_secret(
+                    &vault_id,
+                    &name,
+                    SecretCategory::SshKey,
+                    SecretBox::new(Box::new(private.as_bytes().to_vec())),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            tracing::info!("{} {} key '{}' into vault {}", verb, public.algorithm, name, vault_id);
+            Ok(GeneratedKey { public, secret_id: Some(secret_id), path: None })
+        }
+        KeyDestination::File { path, .. } => {
+            let expanded = expand_tilde(&path);
+            keygen::write_key_pair(&expanded, &private, &public)?;
+            tracing::info!("{} {} key at {}", verb, public.algorithm, expanded.display());
+            Ok(GeneratedKey {
+                public,
+                secret_id: None,
+                path: Some(expanded.to_string_lossy().into_owned()),
+            })
+        }
+    }
+}
+
+/// The text of a private key and the passphrase that came with it.
+async fn read_key_source(
+    state: &AppState,
+    source: PrivateKeySource,
+) -> Result<(zeroize::Zeroizing<String>, Option<String>), String> {
+    match source {
+        PrivateKeySource::File { path, passphrase } => {
+            let expanded = expand_tilde(&path);
+            let content = zeroize::Zeroizing::new(
+                std::fs::read_to_string(&expanded)
+                    .map_err(|e| format!("Cannot read {}: {}", expanded.display(), e))?,
+            );
+            Ok((content, passphrase))
+        }
+        PrivateKeySource::Vault { vault_id, secret_id } => {
+            let manager = state.vault_manager.lock().await;
+            let secret = manager
+                .read_secret(&vault_id, &secret_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            let content = zeroize::Zeroizing::new(String::from_utf8_lossy(secret.expose_secret()).into_owned());
+            Ok((content, None))
+        }
+        PrivateKeySource::Content { content, passphrase } => {
+            Ok((zeroize::Zeroizing::new(content), passphrase))
+        }
+    }
+}
+
+/// The OpenSSH public key and fingerprint of a private key.
+#[tauri::command]
+pub async fn ssh_key_public(
+    state: tauri::State<'_, AppState>,
+    source: PrivateKeySource,
+) -> Result<PublicKeyInfo, String> {
+    let (content, passphrase) = read_key_source(&state, source).await?;
+    keygen::public_key_of(&content, passphrase.as_deref())
+}
+
+/// Convert a PuTTY `.ppk` key to OpenSSH format and save it into a vault or
+/// onto disk. The file destination's passphrase re-protects the converted key.
+#[tauri::command]
+pub async fn ssh_key_import(
+    state: tauri::State<'_, AppState>,
+    source: PrivateKeySource,
+    destination: KeyDestination,
+) -> Result<GeneratedKey, String> {
+    let (content, passphrase) = read_key_source(&state, source).await?;
+    if !crate::ssh::ppk::is_ppk(&content) {
+        return Err("Not a PuTTY private key file".into());
+    }
+    let new_passphrase = match &destination {
+        KeyDestination::File { passphrase, .. } => passphrase.clone(),
+        KeyDestination::Vault { .. } => None,
+    };
+    // Argon2 with PuTTY's defaults is deliberately slow.
+    let (private, public) = tokio::task::spawn_blocking(move || {
+        keygen::import_ppk(&content, passphrase.as_deref(), new_passphrase.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    store_key(&state, private, public, destination, "Imported").await
+}
+
+/// Install a public key in `~/.ssh/authorized_keys` on a connected host, the
+/// way `ssh-copy-id` does. Installing a key that is already there is a no-op.
+#[tauri::command]
+pub async fn ssh_key_deploy(
+    state: tauri::State<'_, AppState>,
+    connection_id: String,
+    public_key: String,
+) -> Result<DeployOutcome, String> {
+    let handle = state
+        .ssh_manager
+        .lock()
+        .await
+        .get_handle(&connection_id)
+        .map_err(|e| e.to_string())?;
+    keygen::deploy(&handle, &public_key).await
+}
@@ -1,6 +1,11 @@
 //! IPC commands for parsing and importing SSH config files.
 
+use tauri::State;
+
+use crate::ipc::session_commands::{session_list, session_list_folders};
 use crate::ssh::config::{self, SshHostEntry};
+use crate::ssh::config_export::{self, ExportFilter};
+use crate::state::AppState;
 
 /// List all named hosts from ~/.ssh/config.
 #[tauri::command]
@@ -13,13 +18,16 @@ pub async fn sshconfig_list_hosts() -> Result<Vec<SshHostEntry>, String> {
     }
 }
 
-/// Resolve a single host from ~/.ssh/config with full details.
+/// Resolve a single host from ~/.ssh/config with full details. Its `Match
+/// exec` commands run on a blocking thread.
 #[tauri::command]
 pub async fn sshconfig_resolve_host(hostname: String) -> Result<SshHostEntry, String> {
     let config = config::parse_ssh_config()?;
 
     match config {
-        Some(cfg) => Ok(config::resolve_host(&cfg, &hostname)),
+        Some(cfg) => tokio::task::spawn_blocking(move || config::resolve_host(&cfg, &hostname))
+            .await
+            .map_err(|e| e.to_string()),
         None => Err("No SSH config file found".to_string()),
     }
 }
@@ -35,3 +43,34 @@ pub async fn sshconfig_exists() -> Result<bool, String> {
         None => Ok(false),
     }
 }
+
+/// Render saved sessions (optionally one folder tree and/or tag) as an
+/// ssh_config fragment. Secrets are never included.
+#[tauri::command]
+pub async fn sshconfig_export(state: State<'_, AppState>, filter: ExportFilter) -> Result<String, String> {
+    render_export(state, &filter).await
+}
+
+/// Write the export to `~/.ssh/reach.conf`, optionally adding an `Include`
+/// for it to `~/.ssh/config`. Returns the path written.
+#[tauri::command]
+pub async fn sshconfig_export_write(
+    state: State<'_, AppState>,
+    filter: ExportFilter,
+    add_include: bool,
+) -> Result<String, String> {
+    let content = render_export(state, &filter).await?;
+    let path = config_export::write_managed(&config_export::ssh_dir()?, &content, add_include)?;
+    tracing::info!("Exported sessions to {}", path.display());
+    Ok(path.display().to_string())
+}
+
+async fn render_export(state: State<'_, AppState>, filter: &ExportFilter) -> Result<String, String> {
+    if state.vault_manager.lock().await.is_locked() {
+        return Err("Vault is locked".to_string());
+    }
+    let sessions = session_list(state.clone()).await?;
+    let folders = session_list_folders(state).await?;
+    let selected = config_export::select(&sessions, &folders, filter);
+    Ok(config_export::render(&selected, &sessions))
+}
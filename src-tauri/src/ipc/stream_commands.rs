@@ -0,0 +1,8 @@
+use crate::state::AppState;
+
+/// The view of terminal `id` (SSH connection id, PTY id or serial port name)
+/// has rendered `bytes` more of the output sent to it.
+#[tauri::command]
+pub fn stream_ack(state: tauri::State<'_, AppState>, id: String, bytes: usize) {
+    state.streams.ack(&id, bytes);
+}
@@ -10,7 +10,7 @@ fn silent_async_command(program: impl AsRef<std::ffi::OsStr>) -> tokio::process:
     cmd
 }
 
-use crate::ssh::client::exec_on_connection;
+use crate::ssh::client::RemoteCommand;
 use crate::state::AppState;
 use crate::tofu::runner;
 use crate::tofu::types::{
@@ -439,9 +439,11 @@ pub async fn tofu_state_list_resources(
                 "cd {} && tofu state list",
                 runner::shell_escape(&project_path)
             );
-            exec_on_connection(&handle, &cmd)
+            RemoteCommand::new(cmd)
+                .run(&handle)
                 .await
                 .map_err(|e| e.to_string())?
+                .stdout_lossy()
         }
     };
 
@@ -525,9 +527,11 @@ pub async fn tofu_get_output_values(
                 "cd {} && tofu output -json",
                 runner::shell_escape(&project_path)
             );
-            exec_on_connection(&handle, &cmd)
+            RemoteCommand::new(cmd)
+                .run(&handle)
                 .await
                 .map_err(|e| e.to_string())?
+                .stdout_lossy()
         }
     };
 
@@ -1054,9 +1058,21 @@ async fn run_tofu_sync(
                 runner::shell_escape(working_dir),
                 args.join(" ")
             );
-            exec_on_connection(&handle, &cmd)
+            let output = RemoteCommand::new(cmd)
+                .idle_timeout(Some(std::time::Duration::from_secs(300)))
+                .run(&handle)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(|e| e.to_string())?;
+
+            if !output.success() {
+                return Err(format!(
+                    "Command failed (exit {}): {}",
+                    output.exit_code_or_default(),
+                    output.stderr_lossy()
+                ));
+            }
+
+            Ok(output.stdout_lossy())
         }
     }
 }
@@ -1110,9 +1126,19 @@ async fn run_tofu_sync_full(
                 runner::shell_escape(working_dir),
                 args.join(" ")
             );
-            exec_on_connection(&handle, &cmd)
+            let output = RemoteCommand::new(cmd)
+                .idle_timeout(Some(std::time::Duration::from_secs(300)))
+                .run(&handle)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(|e| e.to_string())?;
+            let stdout = output.stdout_lossy();
+
+            // fmt returns exit code 3 when files need formatting (check mode)
+            if output.success() || output.exit_code == Some(3) {
+                Ok(stdout)
+            } else {
+                Err(format!("{}{}", output.stderr_lossy(), stdout))
+            }
         }
     }
 }
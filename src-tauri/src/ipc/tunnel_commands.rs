@@ -11,6 +11,7 @@ pub async fn tunnel_create(
     remote_host: String,
     remote_port: u16,
     connection_id: String,
+    local_host: Option<String>,
 ) -> Result<TunnelConfig, String> {
     let tunnel = TunnelManager::create_tunnel(
         tunnel_type,
@@ -18,6 +19,7 @@ pub async fn tunnel_create(
         &remote_host,
         remote_port,
         &connection_id,
+        local_host,
     );
 
     let mut tunnels = state.tunnels.write().await;
@@ -35,7 +37,7 @@ pub async fn tunnel_start(
     tunnel_id: String,
 ) -> Result<(), String> {
     // Get the SSH handle for this tunnel's connection
-    let (handle, mut tunnel_config) = {
+    let (handle, remote_forwards, mut tunnel_config) = {
         let tunnels = state.tunnels.read().await;
         let tunnel = tunnels
             .get(&tunnel_id)
@@ -45,24 +47,29 @@ pub async fn tunnel_start(
         let handle = ssh_manager
             .get_handle(&tunnel.connection_id)
             .map_err(|e| e.to_string())?;
+        let remote_forwards = ssh_manager
+            .get_remote_forwards(&tunnel.connection_id)
+            .map_err(|e| e.to_string())?;
 
-        (handle, tunnel.clone())
+        (handle, remote_forwards, tunnel.clone())
     };
 
     // Start the tunnel via TunnelManager
     {
         let mut tunnel_manager = state.tunnel_manager.lock().await;
         tunnel_manager
-            .start_tunnel(&mut tunnel_config, &handle)
+            .start_tunnel(&mut tunnel_config, &handle, &remote_forwards)
             .await
             .map_err(|e| e.to_string())?;
     }
 
-    // Update tunnel config in state to mark it active
+    // Update tunnel config in state to mark it active. A Remote tunnel asked
+    // for port 0 now carries the port the server actually allocated.
     let local_port = tunnel_config.local_port;
     let mut tunnels = state.tunnels.write().await;
     if let Some(t) = tunnels.get_mut(&tunnel_id) {
         t.active = true;
+        t.remote_port = tunnel_config.remote_port;
     }
     drop(tunnels);
 
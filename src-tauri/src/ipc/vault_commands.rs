@@ -35,6 +35,7 @@ pub async fn vault_unlock(
 pub async fn vault_lock(state: State<'_, AppState>) -> Result<(), String> {
     let mut manager = state.vault_manager.lock().await;
     manager.lock();
+    state.ssh_agent.clear();
     Ok(())
 }
 
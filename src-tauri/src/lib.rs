@@ -1,15 +1,18 @@
 pub mod ansible;
+pub mod inband;
 pub mod plugin;
 pub mod ipc;
 pub mod monitoring;
 #[cfg(desktop)]
 pub mod pty;
+pub mod recording;
 #[cfg(desktop)]
 pub mod serial;
 pub mod session;
 pub mod sftp;
 pub mod ssh;
 pub mod state;
+pub mod stream;
 pub mod tofu;
 pub mod toolchain;
 pub mod tunnel;
@@ -31,10 +34,13 @@ use ipc::monitoring_commands::*;
 use ipc::pty_commands::*;
 #[cfg(desktop)]
 use ipc::serial_commands::*;
+use ipc::recording_commands::*;
 use ipc::session_commands::*;
 use ipc::sftp_commands::*;
 use ipc::ssh_commands::*;
 use ipc::sshconfig_commands::*;
+use ipc::stream_commands::*;
+use ipc::known_hosts_commands::*;
 use ipc::tofu_commands::*;
 use ipc::toolchain_commands::*;
 use ipc::tunnel_commands::*;
@@ -138,18 +144,45 @@ pub fn run() {
         builder = builder.invoke_handler(tauri::generate_handler![
             // SSH commands
             ssh_connect,
+            ssh_connect_session,
+            ssh_open_channel,
             ssh_disconnect,
             ssh_send,
             ssh_ready,
+            ssh_attach,
+            ssh_detach,
+            ssh_scrollback_search,
+            ssh_transfer_respond,
             ssh_hostkey_response,
+            ssh_agent_confirm_response,
+            ssh_agent_status,
+            ssh_agent_set_enabled,
+            ssh_agent_set_key_options,
+            ssh_auth_prompt_response,
             ssh_resize,
             ssh_list_connections,
             ssh_detect_os,
             inspect_key_file,
+            ssh_key_generate,
+            ssh_key_public,
+            ssh_key_import,
+            ssh_key_deploy,
             // SSH Config commands
             sshconfig_list_hosts,
             sshconfig_resolve_host,
             sshconfig_exists,
+            sshconfig_export,
+            sshconfig_export_write,
+            // Host key trust commands
+            known_hosts_list,
+            known_hosts_remove,
+            known_hosts_repin,
+            known_hosts_import,
+            known_hosts_export,
+            host_ca_list,
+            host_ca_add,
+            host_ca_remove,
+            host_ca_trust_certificate,
             // SFTP commands
             sftp_list_dir,
             sftp_upload,
@@ -177,6 +210,7 @@ pub fn run() {
             tunnel_list,
             // PTY commands
             pty_spawn,
+            pty_attach,
             pty_write,
             pty_resize,
             pty_close,
@@ -185,6 +219,7 @@ pub fn run() {
             serial_open,
             serial_close,
             serial_send,
+            serial_transfer_respond,
             // Monitoring commands
             monitoring_start,
             monitoring_stop,
@@ -202,6 +237,9 @@ pub fn run() {
             credential_get_password,
             credential_has_password,
             credential_delete_password,
+            credential_save_totp,
+            credential_has_totp,
+            credential_delete_totp,
             // Settings commands
             settings_get_all,
             settings_get,
@@ -342,6 +380,17 @@ pub fn run() {
             snippet_create,
             snippet_update,
             snippet_delete,
+            // Recording commands
+            recording_get_config,
+            recording_set_config,
+            recording_start,
+            recording_stop,
+            recording_active,
+            recording_list,
+            recording_read,
+            recording_delete,
+            // Terminal output flow control
+            stream_ack,
             // Tray commands
             set_close_to_tray,
             get_close_to_tray,
@@ -354,18 +403,45 @@ pub fn run() {
         builder = builder.invoke_handler(tauri::generate_handler![
             // SSH commands
             ssh_connect,
+            ssh_connect_session,
+            ssh_open_channel,
             ssh_disconnect,
             ssh_send,
             ssh_ready,
+            ssh_attach,
+            ssh_detach,
+            ssh_scrollback_search,
+            ssh_transfer_respond,
             ssh_hostkey_response,
+            ssh_agent_confirm_response,
+            ssh_agent_status,
+            ssh_agent_set_enabled,
+            ssh_agent_set_key_options,
+            ssh_auth_prompt_response,
             ssh_resize,
             ssh_list_connections,
             ssh_detect_os,
             inspect_key_file,
+            ssh_key_generate,
+            ssh_key_public,
+            ssh_key_import,
+            ssh_key_deploy,
             // SSH Config commands
             sshconfig_list_hosts,
             sshconfig_resolve_host,
             sshconfig_exists,
+            sshconfig_export,
+            sshconfig_export_write,
+            // Host key trust commands
+            known_hosts_list,
+            known_hosts_remove,
+            known_hosts_repin,
+            known_hosts_import,
+            known_hosts_export,
+            host_ca_list,
+            host_ca_add,
+            host_ca_remove,
+            host_ca_trust_certificate,
             // SFTP commands
             sftp_list_dir,
             sftp_upload,
@@ -408,6 +484,9 @@ pub fn run() {
             credential_get_password,
             credential_has_password,
             credential_delete_password,
+            credential_save_totp,
+            credential_has_totp,
+            credential_delete_totp,
             // Settings commands
             settings_get_all,
             settings_get,
@@ -548,6 +627,17 @@ pub fn run() {
             snippet_create,
             snippet_update,
             snippet_delete,
+            // Recording commands
+            recording_get_config,
+            recording_set_config,
+            recording_start,
+            recording_stop,
+            recording_active,
+            recording_list,
+            recording_read,
+            recording_delete,
+            // Terminal output flow control
+            stream_ack,
             // Tray commands
             set_close_to_tray,
             get_close_to_tray,
@@ -580,6 +670,17 @@ pub fn run() {
             tracing::info!("App data dir: {:?}", data_dir);
             app.manage(AppState::new());
 
+            // Built-in SSH agent for local terminals, if the user turned it on.
+            #[cfg(desktop)]
+            {
+                let agent = app.state::<AppState>().ssh_agent.clone();
+                if agent.enabled() {
+                    if let Err(e) = agent.start(app.handle()) {
+                        tracing::warn!("SSH agent not started: {}", e);
+                    }
+                }
+            }
+
             // Build system tray
             #[cfg(desktop)]
             {
@@ -3,7 +3,7 @@ use tauri::Emitter;
 use thiserror::Error;
 use tokio::task::JoinHandle;
 
-use crate::ssh::client::{SharedHandle, exec_on_connection};
+use crate::ssh::client::{RemoteCommand, SharedHandle};
 use crate::state::{AppState, SystemStats};
 use tauri::Manager;
 
@@ -132,8 +132,9 @@ async fn monitoring_loop(
         prev_cpu = cpu2;
 
         // Fetch memory, disk, users
-        match exec_on_connection(&handle, STATS_COMMAND).await {
+        match RemoteCommand::new(STATS_COMMAND).run(&handle).await {
             Ok(output) => {
+                let output = output.stdout_lossy();
                 tracing::info!("Monitoring data: {} bytes, sections: {}", output.len(), output.matches("===REACH_SEP===").count());
 
                 let sections: Vec<&str> = output.split("===REACH_SEP===").collect();
@@ -188,7 +189,7 @@ async fn monitoring_loop(
 }
 
 async fn read_cpu_snapshot(handle: &SharedHandle) -> Option<CpuSnapshot> {
-    let output = exec_on_connection(handle, CPU_COMMAND).await.ok()?;
+    let output = RemoteCommand::new(CPU_COMMAND).run(handle).await.ok()?.stdout_lossy();
     for line in output.lines() {
         if line.starts_with("cpu ") {
             let values: Vec<u64> = line
@@ -210,7 +211,7 @@ async fn read_cpu_snapshot(handle: &SharedHandle) -> Option<CpuSnapshot> {
 
 /// Read total RX/TX bytes across all non-loopback interfaces from /proc/net/dev.
 async fn read_net_snapshot(handle: &SharedHandle) -> Option<NetSnapshot> {
-    let output = exec_on_connection(handle, NET_COMMAND).await.ok()?;
+    let output = RemoteCommand::new(NET_COMMAND).run(handle).await.ok()?.stdout_lossy();
     let mut rx_total: u64 = 0;
     let mut tx_total: u64 = 0;
 
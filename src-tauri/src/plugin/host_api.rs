@@ -138,11 +138,11 @@ fn inject_ssh_api(
                         .get_handle(&conn_id)
                         .map_err(|e| mlua::Error::external(e.to_string()))?
                 };
-                let output =
-                    crate::ssh::client::exec_on_connection(&handle, &cmd)
-                        .await
-                        .map_err(|e| mlua::Error::external(e.to_string()))?;
-                Ok(output)
+                let output = crate::ssh::client::RemoteCommand::new(cmd)
+                    .run(&handle)
+                    .await
+                    .map_err(|e| mlua::Error::external(e.to_string()))?;
+                Ok(output.stdout_lossy())
             })?,
         )?;
     }
@@ -1,9 +1,19 @@
 use std::collections::HashMap;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, MasterPty, Child};
-use tauri::Emitter;
+use std::sync::Arc;
+use encoding_rs::Encoding;
+use tauri::{Emitter, Manager};
 use thiserror::Error;
 
+use crate::recording::cast::RecordingKind;
+use crate::recording::manager::RecordingManager;
+use crate::state::AppState;
+use crate::stream::batch::{Pump, PumpHandle};
+use crate::stream::encoding::{InputEncoder, OutputDecoder};
+use crate::stream::OutputSink;
+
 #[derive(Debug, Error)]
 pub enum PtyError {
     #[error("PTY spawn failed: {0}")]
@@ -21,7 +31,9 @@ pub enum PtyError {
 struct PtyInstance {
     master: Box<dyn MasterPty + Send>,
     writer: Box<dyn Write + Send>,
+    encoder: InputEncoder,
     child: Box<dyn Child + Send + Sync>,
+    output: PumpHandle,
     cols: u16,
     rows: u16,
 }
@@ -44,14 +56,21 @@ impl PtyManager {
     /// - Windows: `powershell.exe`
     /// - Unix: `$SHELL` env var, fallback to `/bin/bash`
     ///
-    /// Starts a background reader thread that emits `pty-data-{id}` events
-    /// with output data and `pty-exit-{id}` when the process exits.
+    /// `agent_socket`, when set, becomes the shell's `SSH_AUTH_SOCK` so it
+    /// uses Reach's built-in agent instead of the system one. `encoding` is
+    /// what the shell reads and writes; output is transcoded to UTF-8.
+    ///
+    /// Starts a background reader thread. Its output is held until a view
+    /// [`attach`](PtyManager::attach)es; `pty-exit-{id}` is emitted when the
+    /// process exits.
     pub fn spawn(
         &mut self,
         id: &str,
         shell: Option<String>,
         cols: u16,
         rows: u16,
+        agent_socket: Option<PathBuf>,
+        encoding: &'static Encoding,
         app_handle: tauri::AppHandle,
     ) -> Result<String, PtyError> {
         let shell_path = match shell {
@@ -81,6 +100,9 @@ impl PtyManager {
         // xterm-256color matches the xterm.js frontend.
         cmd.env("TERM", "xterm-256color");
         cmd.env("COLORTERM", "truecolor");
+        if let Some(socket) = agent_socket {
+            cmd.env("SSH_AUTH_SOCK", socket);
+        }
 
         // On Windows, pass -NoLogo to PowerShell for a cleaner experience.
         #[cfg(target_os = "windows")]
@@ -107,32 +129,59 @@ impl PtyManager {
             .take_writer()
             .map_err(|e| PtyError::SpawnFailed(format!("Failed to take PTY writer: {}", e)))?;
 
+        let pump = Pump::spawn(format!("pty-output-{}", id))
+            .map_err(|e| PtyError::SpawnFailed(format!("Failed to spawn output thread: {}", e)))?;
+
         // Store the instance.
         let instance = PtyInstance {
             master: pair.master,
             writer,
+            encoder: InputEncoder::new(encoding),
             child,
+            output: pump.handle(),
             cols,
             rows,
         };
         let id_owned = id.to_string();
         self.instances.insert(id_owned.clone(), instance);
 
+        let recordings = app_handle.state::<AppState>().recordings.clone();
+        recordings.auto_start(id, RecordingKind::Local, &shell_path, cols, rows, None);
+
         // Spawn a blocking reader thread (NOT a tokio task) because
         // portable-pty's read is blocking I/O.
         let reader_id = id_owned.clone();
         let handle = app_handle.clone();
+        let reader_recordings = recordings.clone();
+        let decoder = OutputDecoder::new(encoding);
         std::thread::Builder::new()
             .name(format!("pty-reader-{}", reader_id))
             .spawn(move || {
-                pty_reader_loop(reader, &reader_id, &handle);
+                pty_reader_loop(reader, decoder, &reader_id, &handle, &reader_recordings, pump);
             })
-            .map_err(|e| PtyError::SpawnFailed(format!("Failed to spawn reader thread: {}", e)))?;
+            .map_err(|e| {
+                recordings.discard(id);
+                PtyError::SpawnFailed(format!("Failed to spawn reader thread: {}", e))
+            })?;
 
         tracing::info!("PTY '{}' spawned successfully", id);
         Ok(id.to_string())
     }
 
+    /// Send the PTY's output to `sink`, starting with what it produced before
+    /// anything was listening.
+    pub fn attach(&self, id: &str, sink: OutputSink) -> Result<(), PtyError> {
+        let instance = self
+            .instances
+            .get(id)
+            .ok_or_else(|| PtyError::NotFound(id.to_string()))?;
+
+        if !instance.output.attach(sink) {
+            return Err(PtyError::ReadError("output stream has ended".to_string()));
+        }
+        Ok(())
+    }
+
     /// Write data (user keystrokes) to the PTY.
     pub fn write(&mut self, id: &str, data: &[u8]) -> Result<(), PtyError> {
         let instance = self
@@ -140,9 +189,10 @@ impl PtyManager {
             .get_mut(id)
             .ok_or_else(|| PtyError::NotFound(id.to_string()))?;
 
+        let data = instance.encoder.encode(data);
         instance
             .writer
-            .write_all(data)
+            .write_all(&data)
             .map_err(|e| PtyError::WriteError(format!("{}", e)))?;
 
         instance
@@ -205,15 +255,18 @@ impl Default for PtyManager {
 
 /// Blocking read loop that runs on a dedicated OS thread.
 ///
-/// Reads chunks from the PTY master reader and emits them as
-/// `pty-data-{id}` events. When the read returns 0 or errors,
-/// emits `pty-exit-{id}` and exits.
+/// Reads chunks from the PTY master reader, transcodes them to UTF-8 and
+/// hands them to the output pump, which blocks while the view is behind.
+/// When the read returns 0 or errors, flushes the pump, emits
+/// `pty-exit-{id}`, ends any recording of the session and exits.
 fn pty_reader_loop(
     mut reader: Box<dyn Read + Send>,
+    mut decoder: OutputDecoder,
     id: &str,
     app_handle: &tauri::AppHandle,
+    recordings: &Arc<RecordingManager>,
+    pump: Pump,
 ) {
-    let data_event = format!("pty-data-{}", id);
     let exit_event = format!("pty-exit-{}", id);
     let mut buf = [0u8; 4096];
 
@@ -225,9 +278,13 @@ fn pty_reader_loop(
                 break;
             }
             Ok(n) => {
-                let payload = String::from_utf8_lossy(&buf[..n]).to_string();
-                if let Err(e) = app_handle.emit(&data_event, payload) {
-                    tracing::error!("Failed to emit '{}': {}", data_event, e);
+                let data = decoder.decode(&buf[..n]);
+                if data.is_empty() {
+                    continue;
+                }
+                recordings.output(id, &data);
+                if !pump.send(data.into_owned()) {
+                    tracing::error!("PTY '{}' output stream ended", id);
                     break;
                 }
             }
@@ -238,10 +295,14 @@ fn pty_reader_loop(
         }
     }
 
+    pump.finish();
+    app_handle.state::<AppState>().streams.remove(id);
+
     // Notify frontend that the PTY has exited.
     if let Err(e) = app_handle.emit(&exit_event, ()) {
         tracing::error!("Failed to emit '{}': {}", exit_event, e);
     }
+    recordings.stop(id);
 
     tracing::info!("PTY '{}' reader thread exiting", id);
 }
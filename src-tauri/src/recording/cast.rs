@@ -0,0 +1,334 @@
+//! asciinema v2 `.cast` files: a JSON header line followed by one
+//! `[seconds, code, data]` event per line (`o` output, `i` input, `r` resize).
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// The kind of terminal a recording was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingKind {
+    Ssh,
+    Local,
+    Serial,
+}
+
+impl RecordingKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RecordingKind::Ssh => "ssh",
+            RecordingKind::Local => "local",
+            RecordingKind::Serial => "serial",
+        }
+    }
+}
+
+/// First line of a cast file. `reach_kind` is our own addition; players
+/// ignore header keys they don't know.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u8,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default)]
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reach_kind: Option<RecordingKind>,
+}
+
+impl CastHeader {
+    pub fn new(width: u16, height: u16, timestamp: u64, title: Option<String>, kind: RecordingKind) -> Self {
+        Self {
+            version: 2,
+            width,
+            height,
+            timestamp,
+            title,
+            env: HashMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+            reach_kind: Some(kind),
+        }
+    }
+}
+
+/// Longest stretch of trailing output kept to recognise a password prompt.
+const PROMPT_TAIL_MAX: usize = 256;
+
+/// Writes one recording. Output is decoded as UTF-8 across chunk boundaries;
+/// input, when recorded at all, is dropped while the terminal is sitting at
+/// something that looks like a password prompt, up to the next Enter.
+pub struct CastWriter<W: Write> {
+    out: W,
+    started: Instant,
+    record_input: bool,
+    carry: Vec<u8>,
+    prompt_tail: String,
+    redacting: bool,
+}
+
+impl<W: Write> CastWriter<W> {
+    pub fn new(mut out: W, header: &CastHeader, record_input: bool) -> io::Result<Self> {
+        serde_json::to_writer(&mut out, header)?;
+        out.write_all(b"\n")?;
+        Ok(Self {
+            out,
+            started: Instant::now(),
+            record_input,
+            carry: Vec::new(),
+            prompt_tail: String::new(),
+            redacting: false,
+        })
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        self.carry.extend_from_slice(data);
+        let keep = incomplete_utf8_tail(&self.carry);
+        let complete: Vec<u8> = self.carry.drain(..self.carry.len() - keep).collect();
+        if complete.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&complete);
+        self.track_prompt(&text);
+        self.event("o", &text)
+    }
+
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        if !self.record_input {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(data);
+        let mut kept = String::with_capacity(text.len());
+        for c in text.chars() {
+            let enter = c == '\r' || c == '\n';
+            if !self.redacting && !enter && is_secret_prompt(&self.prompt_tail) {
+                self.redacting = true;
+            }
+            if enter {
+                // Answered: the prompt no longer applies to what's typed next.
+                self.redacting = false;
+                self.prompt_tail.clear();
+            }
+            if !self.redacting {
+                kept.push(c);
+            }
+        }
+        if kept.is_empty() {
+            return Ok(());
+        }
+        self.event("i", &kept)
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", cols, rows))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Write out any held-back partial character and flush.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.carry.is_empty() {
+            let rest = std::mem::take(&mut self.carry);
+            self.event("o", &String::from_utf8_lossy(&rest))?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        let secs = (self.started.elapsed().as_secs_f64() * 1_000_000.0).round() / 1_000_000.0;
+        serde_json::to_writer(&mut self.out, &(secs, code, data))?;
+        self.out.write_all(b"\n")
+    }
+
+    fn track_prompt(&mut self, text: &str) {
+        match text.rfind('\n') {
+            Some(pos) => self.prompt_tail = text[pos + 1..].to_string(),
+            None => self.prompt_tail.push_str(text),
+        }
+        if self.prompt_tail.len() > PROMPT_TAIL_MAX {
+            let mut cut = self.prompt_tail.len() - PROMPT_TAIL_MAX;
+            while !self.prompt_tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.prompt_tail.drain(..cut);
+        }
+    }
+}
+
+/// Number of bytes at the end of `bytes` that start a UTF-8 sequence the
+/// chunk doesn't finish.
+fn incomplete_utf8_tail(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let b = bytes[bytes.len() - back];
+        if b & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match b {
+            0xF0.. => 4,
+            0xE0.. => 3,
+            0xC0.. => 2,
+            _ => 1,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+/// Whether the last line of output asks for a secret: `Password:`,
+/// `[sudo] password for alice:`, `Enter passphrase for key ...:`, an OTP or
+/// PIN prompt. Colour codes around the prompt are ignored.
+pub fn is_secret_prompt(line: &str) -> bool {
+    let line = strip_ansi(line).to_lowercase();
+    let line = line.trim_end();
+    if !line.ends_with(':') {
+        return false;
+    }
+    if ["password", "passphrase", "passcode", "verification code", "one-time"]
+        .iter()
+        .any(|w| line.contains(w))
+    {
+        return true;
+    }
+    line.split(|c: char| !c.is_alphanumeric())
+        .any(|word| matches!(word, "pin" | "otp" | "totp"))
+}
+
+/// Drop escape sequences (CSI, OSC such as window titles, charset
+/// designations) and keep the printable text.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // CSI: parameters, then one final byte in @..~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // OSC: up to BEL or ST (ESC \)
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' {
+                        if chars.peek() == Some(&'\\') {
+                            chars.next();
+                        }
+                        break;
+                    }
+                }
+            }
+            Some('(' | ')' | '*' | '+') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(buf: &[u8]) -> Vec<(String, String)> {
+        std::str::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|l| {
+                let (_, code, data): (f64, String, String) = serde_json::from_str(l).unwrap();
+                (code, data)
+            })
+            .collect()
+    }
+
+    fn writer(record_input: bool) -> CastWriter<Vec<u8>> {
+        let header = CastHeader::new(120, 40, 1_700_000_000, Some("alice@db1".into()), RecordingKind::Ssh);
+        CastWriter::new(Vec::new(), &header, record_input).unwrap()
+    }
+
+    #[test]
+    fn header_is_asciinema_v2() {
+        let buf = writer(false).finish().unwrap();
+        let first = std::str::from_utf8(&buf).unwrap().lines().next().unwrap();
+        let header: serde_json::Value = serde_json::from_str(first).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 120);
+        assert_eq!(header["height"], 40);
+        assert_eq!(header["title"], "alice@db1");
+        assert_eq!(header["reach_kind"], "ssh");
+    }
+
+    #[test]
+    fn output_joins_utf8_split_across_chunks() {
+        let mut w = writer(false);
+        let bytes = "héllo ✓".as_bytes();
+        w.output(&bytes[..2]).unwrap();
+        w.output(&bytes[2..bytes.len() - 1]).unwrap();
+        w.output(&bytes[bytes.len() - 1..]).unwrap();
+        w.resize(100, 30).unwrap();
+        let ev = events(&w.finish().unwrap());
+        let text: String = ev.iter().filter(|(c, _)| c == "o").map(|(_, d)| d.as_str()).collect();
+        assert_eq!(text, "héllo ✓");
+        assert_eq!(ev.last().unwrap(), &("r".to_string(), "100x30".to_string()));
+    }
+
+    #[test]
+    fn input_is_skipped_unless_enabled() {
+        let mut w = writer(false);
+        w.input(b"ls\r").unwrap();
+        assert!(events(&w.finish().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn input_at_password_prompt_is_redacted() {
+        let mut w = writer(true);
+        w.output(b"$ ").unwrap();
+        w.input(b"sudo -i\r").unwrap();
+        w.output(b"\r\n[sudo] password for alice: ").unwrap();
+        w.input(b"hunter2").unwrap();
+        w.input(b"\r").unwrap();
+        w.output(b"\r\nroot# ").unwrap();
+        w.input(b"id\r").unwrap();
+        let typed: Vec<String> = events(&w.finish().unwrap())
+            .into_iter()
+            .filter(|(c, _)| c == "i")
+            .map(|(_, d)| d)
+            .collect();
+        assert_eq!(typed, ["sudo -i\r", "\r", "id\r"]);
+    }
+
+    #[test]
+    fn recognises_secret_prompts() {
+        assert!(is_secret_prompt("Password: "));
+        assert!(is_secret_prompt("\x1b[1mEnter passphrase for key '/home/a/.ssh/id_ed25519':\x1b[0m "));
+        assert!(is_secret_prompt("Verification code:"));
+        assert!(is_secret_prompt("Enter PIN for 'PIV card':"));
+        assert!(!is_secret_prompt("ping: "));
+        assert!(!is_secret_prompt("alice@db1:~$ "));
+        assert!(!is_secret_prompt("Password changed."));
+    }
+
+    #[test]
+    fn strips_csi_osc_and_charset_sequences() {
+        let raw = "\x1b]0;alice@db1: ~\x07\x1b(B\x1b[01;32malice@db1\x1b[00m:~$ \x1b]2;t\x1b\\ls";
+        assert_eq!(strip_ansi(raw), "alice@db1:~$ ls");
+    }
+}
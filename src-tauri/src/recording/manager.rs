@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::recording::cast::{CastHeader, CastWriter, RecordingKind};
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("Recording not found: {0}")]
+    NotFound(String),
+    #[error("Invalid recording name: {0}")]
+    InvalidName(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// How and where terminals are recorded. Pushed from the frontend settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Record every terminal that doesn't opt out on its own.
+    pub record_all: bool,
+    /// Also record keystrokes, minus whatever is typed at a password prompt.
+    pub record_input: bool,
+    /// Where `.cast` files go; `None` is `recordings/` in the app data dir.
+    pub directory: Option<String>,
+    /// Delete recordings older than this many days; `None` keeps them.
+    pub retention_days: Option<u32>,
+}
+
+/// A recording on disk, as listed for the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    /// File name, which is what `read`/`delete` take.
+    pub name: String,
+    pub title: Option<String>,
+    pub kind: Option<RecordingKind>,
+    pub started_at: u64,
+    pub width: u16,
+    pub height: u16,
+    pub size_bytes: u64,
+    pub duration_secs: Option<f64>,
+    /// Still being written by a live terminal.
+    pub active: bool,
+}
+
+/// How often a live recording is flushed to disk at most.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct ActiveRecording {
+    writer: CastWriter<BufWriter<File>>,
+    name: String,
+    last_flush: Instant,
+}
+
+/// Terminal recordings, keyed by terminal id (SSH connection id, PTY id or
+/// serial port name). The terminal I/O paths call `output`/`input`/`resize`
+/// unconditionally; they're no-ops for terminals that aren't recorded.
+pub struct RecordingManager {
+    config: RwLock<RecordingConfig>,
+    active: Mutex<HashMap<String, ActiveRecording>>,
+}
+
+impl RecordingManager {
+    pub fn new() -> Self {
+        Self {
+            config: RwLock::new(RecordingConfig::default()),
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> RecordingConfig {
+        self.config.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Replace the configuration and apply its retention right away.
+    pub fn set_config(&self, config: RecordingConfig) {
+        if let Ok(mut current) = self.config.write() {
+            *current = config;
+        }
+        self.prune();
+    }
+
+    pub fn directory(&self) -> PathBuf {
+        match self.config().directory.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            Some(dir) => crate::ssh::client::expand_tilde(dir),
+            None => crate::app_data_dir().join("recordings"),
+        }
+    }
+
+    /// Start recording `id` when `requested` says so, or when it's unset and
+    /// everything is being recorded. A failure is logged, never fatal to the
+    /// terminal itself.
+    pub fn auto_start(&self, id: &str, kind: RecordingKind, title: &str, cols: u16, rows: u16, requested: Option<bool>) {
+        if !requested.unwrap_or(self.config().record_all) {
+            return;
+        }
+        if let Err(e) = self.start(id, kind, title, cols, rows) {
+            tracing::warn!("Could not start recording for '{}': {}", id, e);
+        }
+    }
+
+    /// Start recording terminal `id`. Returns the recording's file name; if
+    /// `id` is already being recorded, that recording's name.
+    pub fn start(&self, id: &str, kind: RecordingKind, title: &str, cols: u16, rows: u16) -> Result<String, RecordingError> {
+        let mut active = self.active.lock().map_err(|_| poisoned())?;
+        if let Some(rec) = active.get(id) {
+            return Ok(rec.name.clone());
+        }
+
+        let dir = self.directory();
+        std::fs::create_dir_all(&dir)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let (name, file) = create_unique(&dir, &file_stem(now, kind, title))?;
+
+        let header = CastHeader::new(cols, rows, now, Some(title.to_string()), kind);
+        let writer = CastWriter::new(BufWriter::new(file), &header, self.config().record_input)?;
+        tracing::info!("Recording '{}' to {}", id, dir.join(&name).display());
+        active.insert(id.to_string(), ActiveRecording { writer, name: name.clone(), last_flush: Instant::now() });
+        Ok(name)
+    }
+
+    pub fn output(&self, id: &str, data: &[u8]) {
+        self.with_active(id, |w| w.output(data));
+    }
+
+    pub fn input(&self, id: &str, data: &[u8]) {
+        self.with_active(id, |w| w.input(data));
+    }
+
+    pub fn resize(&self, id: &str, cols: u16, rows: u16) {
+        self.with_active(id, |w| w.resize(cols, rows));
+    }
+
+    pub fn is_recording(&self, id: &str) -> bool {
+        self.active.lock().map(|a| a.contains_key(id)).unwrap_or(false)
+    }
+
+    /// Ids of the terminals currently being recorded.
+    pub fn active_ids(&self) -> Vec<String> {
+        self.active.lock().map(|a| a.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Finish the recording for `id`, if any, then apply retention.
+    pub fn stop(&self, id: &str) {
+        let Some(rec) = self.active.lock().ok().and_then(|mut a| a.remove(id)) else {
+            return;
+        };
+        if let Err(e) = rec.writer.finish() {
+            tracing::warn!("Finishing recording {} failed: {}", rec.name, e);
+        }
+        tracing::info!("Stopped recording '{}' ({})", id, rec.name);
+        self.prune();
+    }
+
+    /// Drop the recording for `id` and its file, for a terminal that never
+    /// came up.
+    pub fn discard(&self, id: &str) {
+        let Some(rec) = self.active.lock().ok().and_then(|mut a| a.remove(id)) else {
+            return;
+        };
+        drop(rec.writer);
+        let _ = std::fs::remove_file(self.directory().join(&rec.name));
+    }
+
+    pub fn list(&self) -> Result<Vec<RecordingInfo>, RecordingError> {
+        let dir = self.directory();
+        // Live recordings are listed too, so get their headers onto disk.
+        let active: Vec<String> = self
+            .active
+            .lock()
+            .map(|mut a| {
+                a.values_mut()
+                    .map(|r| {
+                        let _ = r.writer.flush();
+                        r.name.clone()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut list = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.ends_with(".cast") {
+                continue;
+            }
+            match read_info(&entry.path(), &name) {
+                Ok(mut info) => {
+                    info.active = active.contains(&name);
+                    list.push(info);
+                }
+                Err(e) => tracing::debug!("Skipping unreadable recording {}: {}", name, e),
+            }
+        }
+        list.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| b.name.cmp(&a.name)));
+        Ok(list)
+    }
+
+    /// The full `.cast` text of a recording.
+    pub fn read(&self, name: &str) -> Result<String, RecordingError> {
+        let path = self.path_of(name)?;
+        self.flush_named(name);
+        Ok(std::fs::read_to_string(path)?)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), RecordingError> {
+        let path = self.path_of(name)?;
+        if let Ok(mut active) = self.active.lock() {
+            active.retain(|_, r| r.name != name);
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Delete finished recordings past the retention period. Returns how many
+    /// went.
+    pub fn prune(&self) -> usize {
+        let Some(days) = self.config().retention_days.filter(|d| *d > 0) else {
+            return 0;
+        };
+        let Some(cutoff) = SystemTime::now().checked_sub(Duration::from_secs(u64::from(days) * 86_400)) else {
+            return 0;
+        };
+        let active: Vec<String> = self
+            .active
+            .lock()
+            .map(|a| a.values().map(|r| r.name.clone()).collect())
+            .unwrap_or_default();
+        let Ok(entries) = std::fs::read_dir(self.directory()) else {
+            return 0;
+        };
+
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.ends_with(".cast") || active.contains(&name) {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .map(|modified| modified < cutoff)
+                .unwrap_or(false);
+            if expired && std::fs::remove_file(entry.path()).is_ok() {
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            tracing::info!("Removed {} recording(s) older than {} days", removed, days);
+        }
+        removed
+    }
+
+    fn with_active(&self, id: &str, write: impl FnOnce(&mut CastWriter<BufWriter<File>>) -> std::io::Result<()>) {
+        let Ok(mut active) = self.active.lock() else {
+            return;
+        };
+        let Some(rec) = active.get_mut(id) else {
+            return;
+        };
+        let mut result = write(&mut rec.writer);
+        if result.is_ok() && rec.last_flush.elapsed() >= FLUSH_INTERVAL {
+            rec.last_flush = Instant::now();
+            result = rec.writer.flush();
+        }
+        if let Err(e) = result {
+            // A full disk shouldn't take the terminal down with it.
+            tracing::error!("Recording for '{}' failed, stopping it: {}", id, e);
+            active.remove(id);
+        }
+    }
+
+    fn flush_named(&self, name: &str) {
+        if let Ok(mut active) = self.active.lock() {
+            if let Some(rec) = active.values_mut().find(|r| r.name == name) {
+                let _ = rec.writer.flush();
+            }
+        }
+    }
+
+    /// Resolve a recording name to its path, refusing anything that isn't a
+    /// plain `.cast` file name inside the recordings directory.
+    fn path_of(&self, name: &str) -> Result<PathBuf, RecordingError> {
+        let plain = Path::new(name).file_name().map(|f| f == name).unwrap_or(false);
+        if !plain || !name.ends_with(".cast") {
+            return Err(RecordingError::InvalidName(name.to_string()));
+        }
+        let path = self.directory().join(name);
+        if !path.is_file() {
+            return Err(RecordingError::NotFound(name.to_string()));
+        }
+        Ok(path)
+    }
+}
+
+impl Default for RecordingManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn poisoned() -> RecordingError {
+    RecordingError::Io(std::io::Error::other("recording state poisoned"))
+}
+
+/// `<unix time>-<kind>-<title>`, the title cut down to something safe in a
+/// file name.
+fn file_stem(now: u64, kind: RecordingKind, title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 48 {
+            break;
+        }
+    }
+    let slug = slug.trim_matches(|c| c == '-' || c == '.');
+    if slug.is_empty() {
+        format!("{}-{}", now, kind.as_str())
+    } else {
+        format!("{}-{}-{}", now, kind.as_str(), slug)
+    }
+}
+
+fn create_unique(dir: &Path, stem: &str) -> Result<(String, File), RecordingError> {
+    for n in 1..1000 {
+        let name = if n == 1 { format!("{}.cast", stem) } else { format!("{}-{}.cast", stem, n) };
+        match File::options().write(true).create_new(true).open(dir.join(&name)) {
+            Ok(file) => return Ok((name, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(RecordingError::Io(std::io::Error::other("no free recording file name")))
+}
+
+fn read_info(path: &Path, name: &str) -> Result<RecordingInfo, RecordingError> {
+    let mut file = File::open(path)?;
+    let size_bytes = file.metadata()?.len();
+
+    let mut first = String::new();
+    BufReader::new(&mut file).read_line(&mut first)?;
+    let header: CastHeader = serde_json::from_str(first.trim())
+        .map_err(|e| RecordingError::Io(std::io::Error::other(e)))?;
+
+    Ok(RecordingInfo {
+        name: name.to_string(),
+        title: header.title,
+        kind: header.reach_kind,
+        started_at: header.timestamp,
+        width: header.width,
+        height: header.height,
+        size_bytes,
+        duration_secs: last_event_time(&mut file, size_bytes).ok().flatten(),
+        active: false,
+    })
+}
+
+/// Time of the last complete event, read from the end of the file.
+fn last_event_time(file: &mut File, size: u64) -> std::io::Result<Option<f64>> {
+    const TAIL: u64 = 64 * 1024;
+    let start = size.saturating_sub(TAIL);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let tail = String::from_utf8_lossy(&tail);
+    Ok(tail
+        .lines()
+        .rev()
+        .filter(|l| l.starts_with('['))
+        .find_map(|l| serde_json::from_str::<(f64, String, String)>(l).ok())
+        .map(|(t, _, _)| t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager_in(dir: &Path) -> RecordingManager {
+        let manager = RecordingManager::new();
+        manager.set_config(RecordingConfig {
+            directory: Some(dir.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        manager
+    }
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("reach-rec-{}-{}", tag, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn file_stem_is_safe() {
+        assert_eq!(file_stem(1, RecordingKind::Ssh, "alice@db1.prod"), "1-ssh-alice-db1.prod");
+        assert_eq!(file_stem(1, RecordingKind::Serial, "/dev/ttyUSB0"), "1-serial-dev-ttyUSB0");
+        assert_eq!(file_stem(1, RecordingKind::Local, "../"), "1-local");
+    }
+
+    #[test]
+    fn records_lists_reads_and_deletes() {
+        let dir = temp_dir("cycle");
+        let manager = manager_in(&dir);
+
+        let name = manager.start("conn-1", RecordingKind::Ssh, "alice@db1", 80, 24).unwrap();
+        assert_eq!(manager.start("conn-1", RecordingKind::Ssh, "alice@db1", 80, 24).unwrap(), name);
+        manager.output("conn-1", b"hello\r\n");
+        manager.output("other", b"ignored");
+
+        let listed = manager.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].active);
+        assert_eq!(listed[0].title.as_deref(), Some("alice@db1"));
+        assert_eq!(listed[0].kind, Some(RecordingKind::Ssh));
+
+        manager.stop("conn-1");
+        assert!(!manager.is_recording("conn-1"));
+        let cast = manager.read(&name).unwrap();
+        assert!(cast.lines().nth(1).unwrap().contains("hello"));
+        assert!(!manager.list().unwrap()[0].active);
+
+        assert!(matches!(manager.read("../secrets.cast"), Err(RecordingError::InvalidName(_))));
+        assert!(matches!(manager.read("notes.txt"), Err(RecordingError::InvalidName(_))));
+        manager.delete(&name).unwrap();
+        assert!(manager.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn discard_removes_the_file() {
+        let dir = temp_dir("discard");
+        let manager = manager_in(&dir);
+        manager.start("pty-1", RecordingKind::Local, "bash", 80, 24).unwrap();
+        manager.discard("pty-1");
+        assert!(manager.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn auto_start_follows_the_global_switch_unless_overridden() {
+        let dir = temp_dir("auto");
+        let manager = manager_in(&dir);
+        manager.auto_start("a", RecordingKind::Ssh, "a", 80, 24, None);
+        manager.auto_start("b", RecordingKind::Ssh, "b", 80, 24, Some(true));
+        assert!(!manager.is_recording("a"));
+        assert!(manager.is_recording("b"));
+
+        let mut config = manager.config();
+        config.record_all = true;
+        manager.set_config(config);
+        manager.auto_start("c", RecordingKind::Ssh, "c", 80, 24, None);
+        manager.auto_start("d", RecordingKind::Ssh, "d", 80, 24, Some(false));
+        assert!(manager.is_recording("c"));
+        assert!(!manager.is_recording("d"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
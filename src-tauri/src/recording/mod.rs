@@ -0,0 +1,2 @@
+pub mod cast;
+pub mod manager;
@@ -1,13 +1,24 @@
 use std::collections::HashMap;
-use std::io::Read as IoRead;
-use std::time::Duration;
+use std::io::{Read as IoRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use encoding_rs::Encoding;
 use serde::{Deserialize, Serialize};
-use tauri::Emitter;
+use tauri::Manager;
 use thiserror::Error;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
+use crate::inband::{self, session::InbandSession};
+use crate::recording::cast::RecordingKind;
+use crate::state::AppState;
+use crate::stream::batch::Pump;
+use crate::stream::encoding::{InputEncoder, OutputDecoder};
+use crate::stream::OutputSink;
+
 #[derive(Debug, Error)]
 pub enum SerialError {
     #[error("Port not found: {0}")]
@@ -30,9 +41,18 @@ pub struct SerialPortInfo {
     pub port_type: String,
 }
 
+/// For the reader thread, which runs the port's in-band transfers.
+enum InbandCommand {
+    /// The user's answer to a transfer request.
+    Respond(Option<Vec<PathBuf>>),
+    /// Keystrokes typed while a transfer has the line.
+    Input(Vec<u8>),
+}
+
 /// An active serial port with a write sender and reader task handle.
 struct ActiveSerialPort {
     write_tx: mpsc::UnboundedSender<Vec<u8>>,
+    inband_tx: std::sync::mpsc::Sender<InbandCommand>,
     shutdown_tx: mpsc::Sender<()>,
     task: JoinHandle<()>,
 }
@@ -49,11 +69,15 @@ impl SerialManager {
         }
     }
 
-    /// Open a serial port, spawn read/write tasks, emit data events.
+    /// Open a serial port and spawn read/write tasks; what the device sends
+    /// goes to `output`. The device talks `encoding`: its output is
+    /// transcoded to UTF-8 and keystrokes are encoded back.
     pub fn open_port(
         &mut self,
         port_name: &str,
         baud_rate: u32,
+        encoding: &'static Encoding,
+        output: OutputSink,
         app_handle: &tauri::AppHandle,
     ) -> Result<(), SerialError> {
         if self.ports.contains_key(port_name) {
@@ -72,41 +96,94 @@ impl SerialManager {
         let write_port = port
             .try_clone()
             .map_err(|e| SerialError::IoError(format!("Failed to clone port: {}", e)))?;
+        // The reader thread answers ZMODEM/trzsz transfers on its own handle.
+        let mut transfer_port = port
+            .try_clone()
+            .map_err(|e| SerialError::IoError(format!("Failed to clone port: {}", e)))?;
 
         let (write_tx, mut write_rx) = mpsc::unbounded_channel::<Vec<u8>>();
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let (inband_tx, inband_rx) = std::sync::mpsc::channel::<InbandCommand>();
+        let writer_inband_tx = inband_tx.clone();
+        let busy = Arc::new(AtomicBool::new(false));
+        let reader_busy = busy.clone();
+
+        let pump = Pump::spawn(format!("serial-output-{}", port_name))
+            .map_err(|e| SerialError::IoError(format!("Failed to spawn output thread: {}", e)))?;
+        pump.handle().attach(output);
 
-        let data_event = format!("serial-data-{}", port_name);
         let handle = app_handle.clone();
         let pname = port_name.to_string();
 
+        let recordings = app_handle.state::<AppState>().recordings.clone();
+        let title = format!("{} @ {}", port_name, baud_rate);
+        recordings.auto_start(port_name, RecordingKind::Serial, &title, 80, 24, None);
+
         // Spawn a combined read/write task using blocking operations on a thread
         let task = tokio::spawn(async move {
             let mut read_port = port;
             let mut write_port = write_port;
+            let mut encoder = InputEncoder::new(encoding);
 
             // Spawn a blocking reader on a dedicated thread
-            let reader_data_event = data_event.clone();
             let reader_handle = handle.clone();
             let reader_pname = pname.clone();
+            let reader_recordings = recordings.clone();
             let (reader_shutdown_tx, reader_shutdown_rx) = std::sync::mpsc::channel::<()>();
 
             let reader_thread = std::thread::spawn(move || {
                 let mut buf = vec![0u8; 1024];
+                let mut decoder = OutputDecoder::new(encoding);
+                let mut inband = InbandSession::new(reader_pname.clone());
+                let mut last_tick = Instant::now();
                 loop {
                     // Check for shutdown
                     if reader_shutdown_rx.try_recv().is_ok() {
                         break;
                     }
+
+                    while let Ok(cmd) = inband_rx.try_recv() {
+                        match cmd {
+                            InbandCommand::Respond(paths) => inband.respond(paths),
+                            InbandCommand::Input(data) => {
+                                if inband.input(&data) {
+                                    // The transfer ended before these arrived.
+                                    let _ = transfer_port.write_all(&data);
+                                }
+                            }
+                        }
+                    }
+                    if inband.is_busy() && last_tick.elapsed() >= Duration::from_secs(1) {
+                        last_tick = Instant::now();
+                        inband.tick();
+                    }
+                    // Blocking writes pace an upload to the line speed.
+                    if let Some(bytes) = inband.poll_transmit() {
+                        if let Err(e) = transfer_port.write_all(&bytes).and_then(|_| transfer_port.flush()) {
+                            tracing::error!("Serial {}: write error: {}", reader_pname, e);
+                            inband.cancel("Write to the port failed");
+                        }
+                    }
+                    inband::emit_notices(&reader_handle, &mut inband);
+                    reader_busy.store(inband.is_busy(), Ordering::Relaxed);
+                    // With more to send, only read what's already there.
+                    if inband.wants_transmit() && read_port.bytes_to_read().unwrap_or(0) == 0 {
+                        continue;
+                    }
+
                     match read_port.read(&mut buf) {
                         Ok(0) => break,
                         Ok(n) => {
-                            let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                            if let Err(e) = reader_handle.emit(&reader_data_event, &data) {
-                                tracing::error!(
-                                    "Serial {}: failed to emit data: {}",
-                                    reader_pname, e
-                                );
+                            let shown = inband.output(&buf[..n], true);
+                            inband::emit_notices(&reader_handle, &mut inband);
+                            reader_busy.store(inband.is_busy(), Ordering::Relaxed);
+                            let shown = decoder.decode(&shown);
+                            if shown.is_empty() {
+                                continue;
+                            }
+                            reader_recordings.output(&reader_pname, &shown);
+                            if !pump.send(shown.into_owned()) {
+                                tracing::error!("Serial {}: output stream ended", reader_pname);
                                 break;
                             }
                         }
@@ -120,6 +197,14 @@ impl SerialManager {
                         }
                     }
                 }
+                if inband.is_busy() {
+                    inband.cancel("Port closed");
+                    while let Some(bytes) = inband.poll_transmit() {
+                        let _ = transfer_port.write_all(&bytes);
+                    }
+                    inband::emit_notices(&reader_handle, &mut inband);
+                }
+                pump.finish();
                 tracing::info!("Serial {}: reader thread exiting", reader_pname);
             });
 
@@ -129,7 +214,13 @@ impl SerialManager {
                     data = write_rx.recv() => {
                         match data {
                             Some(bytes) => {
-                                use std::io::Write;
+                                // A transfer has the line; its session decides
+                                // what keystrokes do (Ctrl+C cancels).
+                                if busy.load(Ordering::Relaxed) {
+                                    let _ = writer_inband_tx.send(InbandCommand::Input(bytes));
+                                    continue;
+                                }
+                                let bytes = encoder.encode(&bytes);
                                 if let Err(e) = write_port.write_all(&bytes) {
                                     tracing::error!("Serial {}: write error: {}", pname, e);
                                     break;
@@ -155,6 +246,7 @@ impl SerialManager {
             // Signal reader thread to stop
             let _ = reader_shutdown_tx.send(());
             let _ = reader_thread.join();
+            recordings.stop(&pname);
             tracing::info!("Serial {}: task exiting", pname);
         });
 
@@ -162,6 +254,7 @@ impl SerialManager {
             port_name.to_string(),
             ActiveSerialPort {
                 write_tx,
+                inband_tx,
                 shutdown_tx,
                 task,
             },
@@ -184,6 +277,19 @@ impl SerialManager {
             .map_err(|e| SerialError::IoError(format!("Send failed: {}", e)))
     }
 
+    /// Answer the ZMODEM/trzsz transfer the device started on `port_name`.
+    pub fn transfer_respond(&self, port_name: &str, paths: Option<Vec<PathBuf>>) -> Result<(), SerialError> {
+        let active = self
+            .ports
+            .get(port_name)
+            .ok_or_else(|| SerialError::NotOpen(port_name.to_string()))?;
+
+        active
+            .inband_tx
+            .send(InbandCommand::Respond(paths))
+            .map_err(|e| SerialError::IoError(format!("Send failed: {}", e)))
+    }
+
     /// Close an open serial port.
     pub async fn close_port(&mut self, port_name: &str) -> Result<(), SerialError> {
         let active = self
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use crate::ssh::client::{SharedHandle, exec_on_connection, SshError};
+use crate::ssh::client::{RemoteCommand, SharedHandle, SshError};
 
 #[derive(Debug, Error)]
 pub enum SftpBrowserError {
@@ -40,7 +40,7 @@ pub async fn list_directory(
         shell_escape(path),
         shell_escape(path)
     );
-    let output = exec_on_connection(handle, &command).await?;
+    let output = run_checked(handle, RemoteCommand::new(command), path).await?;
     parse_ls_output(&output, path)
 }
 
@@ -50,7 +50,7 @@ pub async fn make_directory(
     path: &str,
 ) -> Result<(), SftpBrowserError> {
     let command = format!("mkdir -p {}", shell_escape(path));
-    exec_on_connection(handle, &command).await?;
+    run_checked(handle, RemoteCommand::new(command), path).await?;
     Ok(())
 }
 
@@ -60,7 +60,7 @@ pub async fn delete_entry(
     path: &str,
 ) -> Result<(), SftpBrowserError> {
     let command = format!("rm -rf {}", shell_escape(path));
-    exec_on_connection(handle, &command).await?;
+    run_checked(handle, RemoteCommand::new(command), path).await?;
     Ok(())
 }
 
@@ -70,7 +70,7 @@ pub async fn touch_file(
     path: &str,
 ) -> Result<(), SftpBrowserError> {
     let command = format!("touch {}", shell_escape(path));
-    exec_on_connection(handle, &command).await?;
+    run_checked(handle, RemoteCommand::new(command), path).await?;
     Ok(())
 }
 
@@ -81,7 +81,7 @@ pub async fn rename_entry(
     new_path: &str,
 ) -> Result<(), SftpBrowserError> {
     let command = format!("mv {} {}", shell_escape(old_path), shell_escape(new_path));
-    exec_on_connection(handle, &command).await?;
+    run_checked(handle, RemoteCommand::new(command), old_path).await?;
     Ok(())
 }
 
@@ -95,7 +95,7 @@ pub async fn read_text_file(
 ) -> Result<String, SftpBrowserError> {
     // Check file size first
     let stat_cmd = format!("stat -c %s {} 2>/dev/null || stat -f %z {}", shell_escape(path), shell_escape(path));
-    let size_output = exec_on_connection(handle, &stat_cmd).await?;
+    let size_output = run_checked(handle, RemoteCommand::new(stat_cmd), path).await?;
     let size: u64 = size_output
         .trim()
         .parse()
@@ -111,7 +111,7 @@ pub async fn read_text_file(
 
     // Read file via base64 to handle binary-safe transport
     let cmd = format!("base64 {}", shell_escape(path));
-    let b64_output = exec_on_connection(handle, &cmd).await?;
+    let b64_output = run_checked(handle, RemoteCommand::new(cmd), path).await?;
 
     // Remove all whitespace from base64 output (line breaks etc.)
     let b64_clean: String = b64_output.chars().filter(|c| !c.is_whitespace()).collect();
@@ -137,84 +137,55 @@ pub async fn write_text_file(
     content: &str,
 ) -> Result<(), SftpBrowserError> {
     use base64::Engine;
-    use russh::ChannelMsg;
 
     let data = content.as_bytes();
 
     // Empty file: simple truncate
     if data.is_empty() {
         let cmd = format!(": > {}", shell_escape(path));
-        exec_on_connection(handle, &cmd).await?;
+        run_checked(handle, RemoteCommand::new(cmd), path).await?;
         return Ok(());
     }
 
-    // Open a single channel: pipe base64 stdin into decoder, write to file
-    let mut channel = {
-        let guard = handle.lock().await;
-        guard.channel_open_session().await
-            .map_err(|e| SshError::ChannelError(format!("{}", e)))?
-    };
-    channel.exec(true, format!("base64 -d > {}", shell_escape(path))).await
-        .map_err(|e| SshError::ChannelError(format!("{}", e)))?;
-
-    // Stream base64 in 48KB raw chunks (multiple of 3 → clean base64, no mid-stream padding)
+    // Pipe base64 into the decoder on stdin, 48KB raw per line
+    // (multiple of 3 → clean base64, no mid-stream padding)
     let chunk_size: usize = 48 * 1024;
-
+    let mut b64 = Vec::with_capacity(data.len() / 3 * 4 + data.len() / chunk_size + 8);
     for chunk in data.chunks(chunk_size) {
-        let mut b64 = base64::engine::general_purpose::STANDARD.encode(chunk);
-        b64.push('\n');
-
-        channel.data(b64.as_bytes()).await
-            .map_err(|e| SftpBrowserError::ParseError(format!("Channel write error: {}", e)))?;
+        b64.extend_from_slice(base64::engine::general_purpose::STANDARD.encode(chunk).as_bytes());
+        b64.push(b'\n');
     }
 
-    // Close stdin to signal EOF
-    channel.eof().await
-        .map_err(|e| SftpBrowserError::ParseError(format!("EOF signal error: {}", e)))?;
-
-    // Wait for exit
-    let mut got_eof = false;
-    let mut got_exit = false;
-    let mut exit_code: Option<u32> = None;
-    let mut stderr_buf = String::new();
-
-    loop {
-        let msg = tokio::time::timeout(
-            std::time::Duration::from_secs(10),
-            channel.wait(),
-        ).await;
-
-        match msg {
-            Ok(Some(ChannelMsg::ExtendedData { ref data, .. })) => {
-                stderr_buf.push_str(&String::from_utf8_lossy(data));
-            }
-            Ok(Some(ChannelMsg::Eof)) => {
-                got_eof = true;
-                if got_exit { break; }
-            }
-            Ok(Some(ChannelMsg::ExitStatus { exit_status })) => {
-                exit_code = Some(exit_status);
-                got_exit = true;
-                if got_eof { break; }
-            }
-            Ok(None) | Err(_) => break,
-            _ => {}
-        }
-    }
+    let command = RemoteCommand::new(format!("base64 -d > {}", shell_escape(path))).stdin(b64);
+    run_checked(handle, command, path).await?;
+    Ok(())
+}
 
-    if let Some(code) = exit_code {
-        if code != 0 {
-            let msg = stderr_buf.trim();
-            if msg.to_lowercase().contains("permission denied") {
-                return Err(SftpBrowserError::PermissionDenied(path.to_string()));
+/// Run `command` and return its stdout, turning a non-zero exit into an
+/// error built from its stderr.
+async fn run_checked(
+    handle: &SharedHandle,
+    command: RemoteCommand<'_>,
+    path: &str,
+) -> Result<String, SftpBrowserError> {
+    let output = command.run(handle).await?;
+    match output.exit_code {
+        Some(code) if code != 0 => {
+            let stderr = output.stderr_lossy();
+            let msg = stderr.trim();
+            let lower = msg.to_lowercase();
+            if lower.contains("permission denied") {
+                Err(SftpBrowserError::PermissionDenied(path.to_string()))
+            } else if lower.contains("no such file") {
+                Err(SftpBrowserError::PathNotFound(path.to_string()))
+            } else if msg.is_empty() {
+                Err(SftpBrowserError::ParseError(format!("Command failed with exit code {}", code)))
+            } else {
+                Err(SftpBrowserError::ParseError(msg.to_string()))
             }
-            return Err(SftpBrowserError::ParseError(
-                if msg.is_empty() { format!("Write failed with exit code {}", code) } else { msg.to_string() }
-            ));
         }
+        _ => Ok(output.stdout_lossy()),
     }
-
-    Ok(())
 }
 
 fn shell_escape(s: &str) -> String {
@@ -2,7 +2,7 @@ use std::path::Path;
 use thiserror::Error;
 use tauri::Emitter;
 use serde::{Deserialize, Serialize};
-use crate::ssh::client::{SharedHandle, SshError, exec_on_connection};
+use crate::ssh::client::{CancelToken, OutputStream, RemoteCommand, SharedHandle, SshError};
 use base64::Engine;
 
 #[derive(Debug, Error)]
@@ -42,7 +42,6 @@ pub async fn download_file(
     transfer_id: &str,
     app_handle: &tauri::AppHandle,
 ) -> Result<(), TransferError> {
-    use russh::ChannelMsg;
     use std::io::Write;
 
     tracing::info!("Downloading {} to {}", remote_path, local_path);
@@ -53,25 +52,21 @@ pub async fn download_file(
         .unwrap_or_else(|| remote_path.to_string());
 
     // Get file size first (try GNU stat, then BSD stat)
-    let size_output = exec_on_connection(
-        handle,
-        &format!(
-            "stat -c%s {} 2>/dev/null || stat -f%z {} 2>/dev/null",
-            shell_escape(remote_path),
-            shell_escape(remote_path)
-        ),
-    )
+    let size_output = RemoteCommand::new(format!(
+        "stat -c%s {} 2>/dev/null || stat -f%z {} 2>/dev/null",
+        shell_escape(remote_path),
+        shell_escape(remote_path)
+    ))
+    .run(handle)
     .await?;
-    let total_bytes: u64 = size_output.trim().parse().unwrap_or(0);
+    let total_bytes: u64 = size_output.stdout_lossy().trim().parse().unwrap_or(0);
 
     if total_bytes == 0 {
         // Check if the file exists but is empty, or doesn't exist
-        let exists_check = exec_on_connection(
-            handle,
-            &format!("test -f {} && echo EXISTS", shell_escape(remote_path)),
-        )
-        .await?;
-        if !exists_check.trim().contains("EXISTS") {
+        let exists_check = RemoteCommand::new(format!("test -f {}", shell_escape(remote_path)))
+            .run(handle)
+            .await?;
+        if !exists_check.success() {
             return Err(TransferError::FileNotFound(remote_path.to_string()));
         }
         // File exists but is empty -- write an empty file
@@ -83,15 +78,6 @@ pub async fn download_file(
         return Ok(());
     }
 
-    // Open a dedicated channel for streaming the base64 output
-    let mut channel = {
-        let guard = handle.lock().await;
-        guard.channel_open_session().await
-            .map_err(|e| SshError::ChannelError(format!("{}", e)))?
-    };
-    channel.exec(true, format!("base64 {}", shell_escape(remote_path))).await
-        .map_err(|e| SshError::ChannelError(format!("{}", e)))?;
-
     // Create/truncate the local file
     let mut file = std::fs::File::create(local_path)
         .map_err(|e| TransferError::IoError(format!("Failed to create local file: {}", e)))?;
@@ -99,8 +85,8 @@ pub async fn download_file(
     let mut b64_buffer = String::new();
     let mut bytes_written: u64 = 0;
     let mut last_progress_bytes: u64 = 0;
-    let mut got_eof = false;
-    let mut got_exit = false;
+    let mut write_error: Option<TransferError> = None;
+    let cancel = CancelToken::new();
 
     // Emit initial progress
     let _ = app_handle.emit(
@@ -114,69 +100,48 @@ pub async fn download_file(
         },
     );
 
-    loop {
-        let msg = tokio::time::timeout(
-            std::time::Duration::from_secs(30),
-            channel.wait(),
-        ).await;
-
-        match msg {
-            Ok(Some(ChannelMsg::Data { ref data })) => {
-                b64_buffer.push_str(&String::from_utf8_lossy(data));
-
-                // Process complete base64 lines (76 chars each = 57 raw bytes)
-                while let Some(newline_pos) = b64_buffer.find('\n') {
-                    let line: String = b64_buffer[..newline_pos]
-                        .chars()
-                        .filter(|c| !c.is_whitespace())
-                        .collect();
-                    b64_buffer = b64_buffer[newline_pos + 1..].to_string();
-
-                    if line.is_empty() {
-                        continue;
-                    }
-
-                    let decoded = base64::engine::general_purpose::STANDARD
-                        .decode(&line)
-                        .map_err(|e| TransferError::IoError(format!("Base64 decode error: {}", e)))?;
-
-                    file.write_all(&decoded)
-                        .map_err(|e| TransferError::IoError(format!("Write error: {}", e)))?;
-
-                    bytes_written += decoded.len() as u64;
-                }
-
-                // Emit progress every ~64KB of decoded data
-                if bytes_written - last_progress_bytes >= 65536 {
-                    last_progress_bytes = bytes_written;
-                    let percent = (bytes_written as f64 / total_bytes as f64 * 100.0).min(100.0);
-                    let _ = app_handle.emit(
-                        &format!("transfer-progress-{}", transfer_id),
-                        &TransferProgress {
-                            id: transfer_id.to_string(),
-                            filename: filename.clone(),
-                            bytes_transferred: bytes_written,
-                            total_bytes,
-                            percent,
-                        },
-                    );
-                }
+    // Stream the base64 output on a dedicated channel, decoding line by line
+    let result = RemoteCommand::new(format!("base64 {}", shell_escape(remote_path)))
+        .idle_timeout(Some(std::time::Duration::from_secs(30)))
+        .cancel_on(cancel.clone())
+        .on_output(|stream, data| {
+            if stream != OutputStream::Stdout || write_error.is_some() {
+                return;
             }
-            Ok(Some(ChannelMsg::ExtendedData { .. })) => {
-                // stderr — ignore
-            }
-            Ok(Some(ChannelMsg::Eof)) => {
-                got_eof = true;
-                if got_exit { break; }
+            b64_buffer.push_str(&String::from_utf8_lossy(data));
+
+            match decode_complete_lines(&mut b64_buffer, &mut file) {
+                Ok(n) => bytes_written += n,
+                Err(e) => {
+                    // Stop the remote side rather than stream into a dead file
+                    write_error = Some(e);
+                    cancel.cancel();
+                    return;
+                }
             }
-            Ok(Some(ChannelMsg::ExitStatus { .. })) => {
-                got_exit = true;
-                if got_eof { break; }
+
+            // Emit progress every ~64KB of decoded data
+            if bytes_written - last_progress_bytes >= 65536 {
+                last_progress_bytes = bytes_written;
+                let percent = (bytes_written as f64 / total_bytes as f64 * 100.0).min(100.0);
+                let _ = app_handle.emit(
+                    &format!("transfer-progress-{}", transfer_id),
+                    &TransferProgress {
+                        id: transfer_id.to_string(),
+                        filename: filename.clone(),
+                        bytes_transferred: bytes_written,
+                        total_bytes,
+                        percent,
+                    },
+                );
             }
-            Ok(None) | Err(_) => break,
-            _ => {}
-        }
+        })
+        .run(handle)
+        .await;
+    if let Some(e) = write_error {
+        return Err(e);
     }
+    result?;
 
     // Decode any remaining data in the buffer (last partial line)
     let remaining: String = b64_buffer.chars().filter(|c| !c.is_whitespace()).collect();
@@ -221,8 +186,6 @@ pub async fn upload_file(
     transfer_id: &str,
     app_handle: &tauri::AppHandle,
 ) -> Result<(), TransferError> {
-    use russh::ChannelMsg;
-
     tracing::info!("Uploading {} to {}", local_path, remote_path);
 
     let filename = Path::new(local_path)
@@ -238,10 +201,9 @@ pub async fn upload_file(
 
     // Handle empty files
     if total_bytes == 0 {
-        let _ = exec_on_connection(
-            handle,
-            &format!(": > {}", shell_escape(remote_path)),
-        ).await?;
+        RemoteCommand::new(format!(": > {}", shell_escape(remote_path)))
+            .run(handle)
+            .await?;
         let _ = app_handle.emit(&format!("transfer-complete-{}", transfer_id), ());
         tracing::info!("Upload complete: {} (empty file)", remote_path);
         return Ok(());
@@ -259,88 +221,61 @@ pub async fn upload_file(
         },
     );
 
-    // Open a single channel: pipe base64 stdin into decoder, write to file
-    let mut channel = {
-        let guard = handle.lock().await;
-        guard.channel_open_session().await
-            .map_err(|e| SshError::ChannelError(format!("{}", e)))?
-    };
-    channel.exec(true, format!("base64 -d > {}", shell_escape(remote_path))).await
-        .map_err(|e| SshError::ChannelError(format!("{}", e)))?;
-
     // Stream base64-encoded data in chunks through the channel's stdin.
     // 48KB raw → 64KB base64 (multiple of 3 avoids padding mid-stream).
     let chunk_size: usize = 48 * 1024;
-    let mut bytes_sent: u64 = 0;
-    let mut last_progress_bytes: u64 = 0;
+    let (tx, rx) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
 
-    for chunk in data.chunks(chunk_size) {
-        let mut b64 = base64::engine::general_purpose::STANDARD.encode(chunk);
-        b64.push('\n');
-
-        channel.data(b64.as_bytes()).await
-            .map_err(|e| TransferError::IoError(format!("Channel write error: {}", e)))?;
-
-        bytes_sent += chunk.len() as u64;
-
-        // Emit progress every ~64KB of raw data
-        if bytes_sent - last_progress_bytes >= 65536 || bytes_sent == total_bytes {
-            last_progress_bytes = bytes_sent;
-            let percent = (bytes_sent as f64 / total_bytes as f64 * 100.0).min(100.0);
-            let _ = app_handle.emit(
-                &format!("transfer-progress-{}", transfer_id),
-                &TransferProgress {
-                    id: transfer_id.to_string(),
-                    filename: filename.clone(),
-                    bytes_transferred: bytes_sent,
-                    total_bytes,
-                    percent,
-                },
-            );
-        }
-    }
+    let feed = async {
+        let mut bytes_sent: u64 = 0;
+        let mut last_progress_bytes: u64 = 0;
 
-    // Close stdin to signal EOF to base64 -d
-    channel.eof().await
-        .map_err(|e| TransferError::IoError(format!("EOF signal error: {}", e)))?;
-
-    // Wait for the remote command to finish
-    let mut got_eof = false;
-    let mut got_exit = false;
-    let mut exit_code: Option<u32> = None;
-    let mut stderr_buf = String::new();
-
-    loop {
-        let msg = tokio::time::timeout(
-            std::time::Duration::from_secs(30),
-            channel.wait(),
-        ).await;
-
-        match msg {
-            Ok(Some(ChannelMsg::ExtendedData { ref data, .. })) => {
-                stderr_buf.push_str(&String::from_utf8_lossy(data));
-            }
-            Ok(Some(ChannelMsg::Eof)) => {
-                got_eof = true;
-                if got_exit { break; }
+        for chunk in data.chunks(chunk_size) {
+            let mut b64 = base64::engine::general_purpose::STANDARD.encode(chunk);
+            b64.push('\n');
+
+            // The command has ended (failed or timed out); its error wins
+            if tx.send(b64.into_bytes()).await.is_err() {
+                return;
             }
-            Ok(Some(ChannelMsg::ExitStatus { exit_status })) => {
-                exit_code = Some(exit_status);
-                got_exit = true;
-                if got_eof { break; }
+
+            bytes_sent += chunk.len() as u64;
+
+            // Emit progress every ~64KB of raw data
+            if bytes_sent - last_progress_bytes >= 65536 || bytes_sent == total_bytes {
+                last_progress_bytes = bytes_sent;
+                let percent = (bytes_sent as f64 / total_bytes as f64 * 100.0).min(100.0);
+                let _ = app_handle.emit(
+                    &format!("transfer-progress-{}", transfer_id),
+                    &TransferProgress {
+                        id: transfer_id.to_string(),
+                        filename: filename.clone(),
+                        bytes_transferred: bytes_sent,
+                        total_bytes,
+                        percent,
+                    },
+                );
             }
-            Ok(None) | Err(_) => break,
-            _ => {}
         }
-    }
+
+        // Closing stdin signals EOF to base64 -d
+        drop(tx);
+    };
+
+    let command = RemoteCommand::new(format!("base64 -d > {}", shell_escape(remote_path)))
+        .stdin_stream(rx)
+        .idle_timeout(Some(std::time::Duration::from_secs(30)));
+    let (output, ()) = tokio::join!(command.run(handle), feed);
+    let output = output?;
 
     // Check for errors
-    if let Some(code) = exit_code {
+    if let Some(code) = output.exit_code {
         if code != 0 {
-            let msg = if stderr_buf.trim().is_empty() {
+            let stderr = output.stderr_lossy();
+            let msg = if stderr.trim().is_empty() {
                 format!("Remote base64 -d exited with code {}", code)
             } else {
-                stderr_buf.trim().to_string()
+                stderr.trim().to_string()
             };
             return Err(TransferError::IoError(msg));
         }
@@ -363,6 +298,35 @@ pub async fn upload_file(
     Ok(())
 }
 
+/// Decode and write every complete base64 line in `buffer` (76 chars each =
+/// 57 raw bytes), leaving any partial line behind. Returns the bytes written.
+fn decode_complete_lines(buffer: &mut String, file: &mut std::fs::File) -> Result<u64, TransferError> {
+    use std::io::Write;
+
+    let mut written: u64 = 0;
+    while let Some(newline_pos) = buffer.find('\n') {
+        let line: String = buffer[..newline_pos]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        buffer.drain(..=newline_pos);
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(&line)
+            .map_err(|e| TransferError::IoError(format!("Base64 decode error: {}", e)))?;
+
+        file.write_all(&decoded)
+            .map_err(|e| TransferError::IoError(format!("Write error: {}", e)))?;
+
+        written += decoded.len() as u64;
+    }
+    Ok(written)
+}
+
 /// Escape a string for safe use in a shell command using single quotes.
 fn shell_escape(s: &str) -> String {
     format!("'{}'", s.replace('\'', "'\\''"))
@@ -0,0 +1,242 @@
+//! SSH agent forwarding (`ssh -A`).
+//!
+//! With forwarding on, the interactive channel asks for
+//! `auth-agent-req@openssh.com`, and every `auth-agent@openssh.com` channel the
+//! server opens afterwards is relayed to the local agent — found the same way
+//! as for agent auth: `SSH_AUTH_SOCK` on Unix, OpenSSH's named pipe and then
+//! Pageant on Windows.
+//!
+//! The relay works message by message rather than as a byte pipe, so in
+//! `Confirm` mode each signature request can be put to the user before it
+//! reaches the agent. A refused request gets `SSH_AGENT_FAILURE`, exactly what
+//! a locked agent would answer.
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::ssh::known_hosts;
+use crate::state::AgentForwarding;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+/// Agent messages are small; anything bigger is a broken or hostile peer.
+const MAX_MESSAGE: usize = 256 * 1024;
+const CONFIRM_TIMEOUT_SECS: u64 = 60;
+
+/// A connection to an SSH agent, whatever the transport.
+pub(crate) trait AgentStream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> AgentStream for T {}
+
+/// Open a raw connection to the user's local SSH agent.
+pub(crate) async fn connect_local_agent() -> std::io::Result<Box<dyn AgentStream>> {
+    #[cfg(unix)]
+    {
+        let path = std::env::var_os("SSH_AUTH_SOCK").ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "SSH_AUTH_SOCK is not set")
+        })?;
+        Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+    }
+    #[cfg(windows)]
+    {
+        match tokio::net::windows::named_pipe::ClientOptions::new().open(r"\\.\pipe\openssh-ssh-agent") {
+            Ok(pipe) => Ok(Box::new(pipe)),
+            Err(e) => {
+                tracing::debug!("OpenSSH Windows agent named pipe unavailable: {}", e);
+                Ok(Box::new(pageant::PageantStream::new()))
+            }
+        }
+    }
+}
+
+/// Relay one forwarded agent channel until either side closes it.
+pub(crate) async fn relay(
+    channel: russh::Channel<russh::client::Msg>,
+    mode: AgentForwarding,
+    host: String,
+    app: Option<tauri::AppHandle>,
+) {
+    let mut agent = match connect_local_agent().await {
+        Ok(agent) => agent,
+        Err(e) => {
+            tracing::warn!("Agent forwarding for {}: local agent unavailable: {}", host, e);
+            let _ = channel.close().await;
+            return;
+        }
+    };
+    let mut remote = channel.into_stream();
+
+    loop {
+        let request = match read_message(&mut remote).await {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("Agent forwarding for {}: {}", host, e);
+                break;
+            }
+        };
+
+        let allowed = mode == AgentForwarding::Allow
+            || request.first() != Some(&SSH_AGENTC_SIGN_REQUEST)
+            || match sign_request_key(&request) {
+                Some(blob) => confirm_signature(app.as_ref(), Some(&host), None, blob).await,
+                None => false,
+            };
+        let response = if allowed {
+            match exchange(&mut agent, &request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::warn!("Agent forwarding for {}: local agent failed: {}", host, e);
+                    break;
+                }
+            }
+        } else {
+            tracing::info!("Agent forwarding for {}: signature request refused", host);
+            vec![SSH_AGENT_FAILURE]
+        };
+
+        if write_message(&mut remote, &response).await.is_err() {
+            break;
+        }
+    }
+    let _ = remote.shutdown().await;
+}
+
+/// Send one request to the agent and read its reply.
+async fn exchange(agent: &mut Box<dyn AgentStream>, request: &[u8]) -> std::io::Result<Vec<u8>> {
+    write_message(agent, request).await?;
+    read_message(agent)
+        .await?
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "agent closed the connection"))
+}
+
+/// Read a length-prefixed agent message. `None` on a clean close.
+pub(crate) async fn read_message<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_be_bytes(header) as usize;
+    if length == 0 || length > MAX_MESSAGE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("bad agent message length {}", length),
+        ));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(body))
+}
+
+pub(crate) async fn write_message<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, body: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(body.len() as u32).to_be_bytes()).await?;
+    writer.write_all(body).await?;
+    writer.flush().await
+}
+
+/// The key blob a sign request names (`byte 13, string key_blob, ...`).
+fn sign_request_key(request: &[u8]) -> Option<&[u8]> {
+    let length = u32::from_be_bytes(request.get(1..5)?.try_into().ok()?) as usize;
+    request.get(5..5 + length)
+}
+
+/// Signature confirmation request emitted as `ssh-agent-confirm`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentConfirmPrompt {
+    prompt_id: String,
+    /// Remote host using a forwarded agent; `None` for a local program using
+    /// the built-in agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    /// Vault name of the key, when it comes from the built-in agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_name: Option<String>,
+    key_type: String,
+    fingerprint: String,
+}
+
+/// Pending signature confirmations, keyed by prompt id — the same park/resolve
+/// pattern as the host-key prompts.
+static AGENT_PROMPTS: std::sync::OnceLock<
+    std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>>,
+> = std::sync::OnceLock::new();
+
+fn agent_prompts() -> &'static std::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<bool>>> {
+    AGENT_PROMPTS.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+/// Resolve a pending signature confirmation with the user's decision.
+pub(crate) fn resolve_agent_prompt(prompt_id: &str, allow: bool) {
+    let sender = agent_prompts().lock().unwrap().remove(prompt_id);
+    if let Some(tx) = sender {
+        let _ = tx.send(allow);
+    }
+}
+
+/// Ask the user whether `host` (or, without one, a local program) may sign
+/// with the key `blob`. Fails closed: no UI or no answer within a minute
+/// means no.
+pub(crate) async fn confirm_signature(
+    app: Option<&tauri::AppHandle>,
+    host: Option<&str>,
+    key_name: Option<&str>,
+    blob: &[u8],
+) -> bool {
+    let Some(app) = app else {
+        return false;
+    };
+    let blob = STANDARD.encode(blob);
+
+    let prompt_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    agent_prompts().lock().unwrap().insert(prompt_id.clone(), tx);
+
+    let payload = AgentConfirmPrompt {
+        prompt_id: prompt_id.clone(),
+        host: host.map(str::to_string),
+        key_name: key_name.map(str::to_string),
+        key_type: known_hosts::blob_key_type(&blob).unwrap_or_default(),
+        fingerprint: format!("SHA256:{}", known_hosts::fingerprint_of(&blob)),
+    };
+    if app.emit("ssh-agent-confirm", &payload).is_err() {
+        agent_prompts().lock().unwrap().remove(&prompt_id);
+        return false;
+    }
+
+    match tokio::time::timeout(std::time::Duration::from_secs(CONFIRM_TIMEOUT_SECS), rx).await {
+        Ok(Ok(allow)) => allow,
+        _ => {
+            agent_prompts().lock().unwrap().remove(&prompt_id);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn messages_round_trip() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        write_message(&mut a, &[11]).await.unwrap();
+        drop(a);
+        assert_eq!(read_message(&mut b).await.unwrap(), Some(vec![11]));
+        assert_eq!(read_message(&mut b).await.unwrap(), None);
+    }
+
+    #[test]
+    fn finds_the_key_in_a_sign_request() {
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST, 0, 0, 0, 3, b'k', b'e', b'y'];
+        request.extend_from_slice(&[0, 0, 0, 1, b'd', 0, 0, 0, 0]);
+        assert_eq!(sign_request_key(&request), Some(&b"key"[..]));
+        assert_eq!(sign_request_key(&[SSH_AGENTC_SIGN_REQUEST, 0, 0, 0, 9]), None);
+    }
+}
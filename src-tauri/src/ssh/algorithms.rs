@@ -0,0 +1,491 @@
+//! Per-session SSH algorithm policy.
+//!
+//! A session's `CryptoPolicy` becomes the russh `Preferred` lists (key
+//! exchange, host key, cipher, MAC, compression), either from a preset or
+//! from explicit per-category overrides in OpenSSH naming.
+//!
+//! russh doesn't report what a handshake settled on, so the target's stream
+//! is wrapped in a [`KexSniffer`] that picks the server's KEXINIT out of the
+//! first bytes it reads — it is sent in the clear, before any keys exist.
+//! Running the RFC 4253 §7.1 negotiation over our lists and the server's
+//! yields exactly what russh chose.
+
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use russh::{cipher, compression, kex, mac, Preferred};
+use russh_keys::key;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::state::{CryptoPolicy, CryptoPreset};
+
+/// Everything this build can speak, most preferred first.
+const KEX: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+    kex::DH_G16_SHA512,
+    kex::DH_G14_SHA256,
+    kex::DH_G14_SHA1,
+    kex::DH_G1_SHA1,
+];
+const HOST_KEY: &[key::Name] = &[
+    key::ED25519,
+    key::ECDSA_SHA2_NISTP256,
+    key::ECDSA_SHA2_NISTP384,
+    key::ECDSA_SHA2_NISTP521,
+    key::RSA_SHA2_512,
+    key::RSA_SHA2_256,
+    key::SSH_RSA,
+];
+const CIPHER: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+    cipher::AES_256_CBC,
+    cipher::AES_192_CBC,
+    cipher::AES_128_CBC,
+];
+const MAC: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+    mac::HMAC_SHA1_ETM,
+    mac::HMAC_SHA1,
+];
+const COMPRESSION: &[compression::Name] = &[compression::NONE, compression::ZLIB, compression::ZLIB_LEGACY];
+
+/// Not algorithms but capability flags carried in the kex list: server-sig-algs
+/// (needed for rsa-sha2 user auth) and strict kex (the Terrapin fix). Always
+/// offered, whatever the policy says.
+const KEX_EXTENSIONS: &[kex::Name] = &[kex::EXTENSION_SUPPORT_AS_CLIENT, kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT];
+
+const MODERN_KEX: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::DH_G16_SHA512,
+    kex::EXTENSION_SUPPORT_AS_CLIENT,
+    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+];
+const MODERN_HOST_KEY: &[key::Name] = &[
+    key::ED25519,
+    key::ECDSA_SHA2_NISTP256,
+    key::ECDSA_SHA2_NISTP384,
+    key::ECDSA_SHA2_NISTP521,
+    key::RSA_SHA2_512,
+    key::RSA_SHA2_256,
+];
+const MODERN_CIPHER: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+];
+const MODERN_MAC: &[mac::Name] = &[mac::HMAC_SHA512_ETM, mac::HMAC_SHA256_ETM];
+const NO_COMPRESSION: &[compression::Name] = &[compression::NONE];
+
+/// The russh algorithm lists for `policy`. Unknown names in an override are
+/// an error rather than silently dropped — a hardened profile that quietly
+/// lost its only cipher would be worse than one that refuses to connect.
+pub fn preferred(policy: &CryptoPolicy) -> Result<Preferred, String> {
+    let base = match policy.preset {
+        CryptoPreset::Default => Preferred::default(),
+        CryptoPreset::Modern => Preferred {
+            kex: Cow::Borrowed(MODERN_KEX),
+            key: Cow::Borrowed(MODERN_HOST_KEY),
+            cipher: Cow::Borrowed(MODERN_CIPHER),
+            mac: Cow::Borrowed(MODERN_MAC),
+            compression: Cow::Borrowed(NO_COMPRESSION),
+            ..Preferred::default()
+        },
+        // Modern first, so capable servers still get strong algorithms.
+        CryptoPreset::Legacy => Preferred {
+            kex: Cow::Owned(with_kex_extensions(KEX.to_vec())),
+            key: Cow::Borrowed(HOST_KEY),
+            cipher: Cow::Borrowed(CIPHER),
+            mac: Cow::Borrowed(MAC),
+            compression: Cow::Borrowed(COMPRESSION),
+            ..Preferred::default()
+        },
+    };
+
+    Ok(Preferred {
+        kex: match &policy.kex {
+            Some(names) => Cow::Owned(with_kex_extensions(pick(names, KEX, "key exchange")?)),
+            None => base.kex,
+        },
+        key: match &policy.host_key {
+            Some(names) => Cow::Owned(pick(names, HOST_KEY, "host key")?),
+            None => base.key,
+        },
+        cipher: match &policy.cipher {
+            Some(names) => Cow::Owned(pick(names, CIPHER, "cipher")?),
+            None => base.cipher,
+        },
+        mac: match &policy.mac {
+            Some(names) => Cow::Owned(pick(names, MAC, "MAC")?),
+            None => base.mac,
+        },
+        compression: match &policy.compression {
+            Some(names) => Cow::Owned(pick(names, COMPRESSION, "compression")?),
+            None => base.compression,
+        },
+        ..base
+    })
+}
+
+/// Map OpenSSH algorithm names onto the supported russh names, keeping order.
+fn pick<N: Clone + AsRef<str>>(names: &[String], supported: &[N], what: &str) -> Result<Vec<N>, String> {
+    let picked = names
+        .iter()
+        .map(|name| {
+            supported
+                .iter()
+                .find(|n| n.as_ref() == name.trim())
+                .cloned()
+                .ok_or_else(|| {
+                    let known: Vec<&str> = supported.iter().map(AsRef::as_ref).collect();
+                    format!("Unsupported {} algorithm '{}' (supported: {})", what, name.trim(), known.join(", "))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if picked.is_empty() {
+        return Err(format!("At least one {} algorithm is required", what));
+    }
+    Ok(picked)
+}
+
+fn with_kex_extensions(mut names: Vec<kex::Name>) -> Vec<kex::Name> {
+    for ext in KEX_EXTENSIONS {
+        if !names.iter().any(|n| n.as_ref() == ext.as_ref()) {
+            names.push(*ext);
+        }
+    }
+    names
+}
+
+fn is_kex_extension(name: &str) -> bool {
+    KEX_EXTENSIONS.iter().any(|e| e.as_ref() == name)
+}
+
+/// AEAD ciphers authenticate on their own; no MAC is negotiated with them.
+fn is_aead(cipher_name: &str) -> bool {
+    cipher_name == cipher::CHACHA20_POLY1305.as_ref() || cipher_name.ends_with("-gcm@openssh.com")
+}
+
+/// A per-direction pair, as the SSH transport negotiates ciphers, MACs and
+/// compression independently for each direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Directional<T = String> {
+    pub client_to_server: T,
+    pub server_to_client: T,
+}
+
+/// The algorithms a connection's transport ended up using.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NegotiatedAlgorithms {
+    pub kex: String,
+    pub host_key: String,
+    pub cipher: Directional,
+    /// `None` where the cipher is AEAD (chacha20-poly1305, AES-GCM).
+    pub mac: Directional<Option<String>>,
+    pub compression: Directional,
+}
+
+/// The name-lists from a server's KEXINIT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServerAlgorithms {
+    kex: Vec<String>,
+    host_key: Vec<String>,
+    cipher: Directional<Vec<String>>,
+    mac: Directional<Vec<String>>,
+    compression: Directional<Vec<String>>,
+}
+
+/// RFC 4253 §7.1: the client's first choice the server also supports.
+fn first_common<N: AsRef<str>>(ours: &[N], theirs: &[String]) -> Option<String> {
+    ours.iter()
+        .map(AsRef::as_ref)
+        .filter(|name| !is_kex_extension(name))
+        .find(|name| theirs.iter().any(|t| t == name))
+        .map(str::to_string)
+}
+
+/// What `preferred` and the server's KEXINIT negotiate to. `None` when they
+/// share nothing in some category — the handshake would have failed anyway.
+pub(crate) fn negotiate(preferred: &Preferred, server: &ServerAlgorithms) -> Option<NegotiatedAlgorithms> {
+    let cipher = Directional {
+        client_to_server: first_common(&preferred.cipher, &server.cipher.client_to_server)?,
+        server_to_client: first_common(&preferred.cipher, &server.cipher.server_to_client)?,
+    };
+    let mac_for = |cipher_name: &str, theirs: &[String]| -> Option<Option<String>> {
+        if is_aead(cipher_name) {
+            Some(None)
+        } else {
+            first_common(&preferred.mac, theirs).map(Some)
+        }
+    };
+    let mac = Directional {
+        client_to_server: mac_for(&cipher.client_to_server, &server.mac.client_to_server)?,
+        server_to_client: mac_for(&cipher.server_to_client, &server.mac.server_to_client)?,
+    };
+    Some(NegotiatedAlgorithms {
+        kex: first_common(&preferred.kex, &server.kex)?,
+        host_key: first_common(&preferred.key, &server.host_key)?,
+        cipher,
+        mac,
+        compression: Directional {
+            client_to_server: first_common(&preferred.compression, &server.compression.client_to_server)?,
+            server_to_client: first_common(&preferred.compression, &server.compression.server_to_client)?,
+        },
+    })
+}
+
+/// Where a [`KexSniffer`] leaves the server's algorithm lists once seen.
+pub(crate) type SniffedKex = Arc<Mutex<Option<ServerAlgorithms>>>;
+
+/// Give up looking for the KEXINIT after this much input.
+const MAX_SNIFF: usize = 64 * 1024;
+const SSH_MSG_KEXINIT: u8 = 20;
+
+/// Transparent stream wrapper that records the server's KEXINIT as it passes
+/// through, then gets out of the way.
+pub(crate) struct KexSniffer<S> {
+    inner: S,
+    seen: Vec<u8>,
+    done: bool,
+    found: SniffedKex,
+}
+
+impl<S> KexSniffer<S> {
+    pub(crate) fn new(inner: S) -> (Self, SniffedKex) {
+        let found = SniffedKex::default();
+        let sniffer = Self { inner, seen: Vec::new(), done: false, found: found.clone() };
+        (sniffer, found)
+    }
+
+    fn observe(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            self.finish();
+            return;
+        }
+        self.seen.extend_from_slice(bytes);
+        match parse_server_kexinit(&self.seen) {
+            Parse::Found(algorithms) => {
+                if let Ok(mut slot) = self.found.lock() {
+                    *slot = Some(algorithms);
+                }
+                self.finish();
+            }
+            Parse::Incomplete if self.seen.len() < MAX_SNIFF => {}
+            Parse::Incomplete | Parse::Invalid => self.finish(),
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.seen = Vec::new();
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for KexSniffer<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if !this.done && matches!(poll, Poll::Ready(Ok(()))) {
+            this.observe(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for KexSniffer<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The negotiated algorithms, once the handshake that `sniffed` watched is done.
+pub(crate) fn negotiated(preferred: &Preferred, sniffed: &SniffedKex) -> Option<NegotiatedAlgorithms> {
+    let server = sniffed.lock().ok()?.clone()?;
+    negotiate(preferred, &server)
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Found(ServerAlgorithms),
+}
+
+/// Find the KEXINIT in the first bytes a server sends: optional banner lines,
+/// the `SSH-2.0-...` identification line, then the first binary packet.
+fn parse_server_kexinit(data: &[u8]) -> Parse {
+    let mut start = 0;
+    let packet = loop {
+        let Some(newline) = data[start..].iter().position(|b| *b == b'\n') else {
+            return Parse::Incomplete;
+        };
+        let line = &data[start..start + newline];
+        start += newline + 1;
+        if line.starts_with(b"SSH-") {
+            break &data[start..];
+        }
+    };
+
+    let Some(header) = packet.get(..5) else {
+        return Parse::Incomplete;
+    };
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let padding = header[4] as usize;
+    if length < padding + 2 || length > MAX_SNIFF {
+        return Parse::Invalid;
+    }
+    if packet.len() < 4 + length {
+        return Parse::Incomplete;
+    }
+    let payload = &packet[5..4 + length - padding];
+    if payload.first() != Some(&SSH_MSG_KEXINIT) {
+        return Parse::Invalid;
+    }
+
+    // Message type and 16-byte cookie, then the name-lists in fixed order.
+    let mut rest = payload.get(17..).unwrap_or_default();
+    let mut lists = Vec::with_capacity(8);
+    for _ in 0..8 {
+        match read_name_list(&mut rest) {
+            Some(list) => lists.push(list),
+            None => return Parse::Invalid,
+        }
+    }
+    let mut lists = lists.into_iter();
+    let mut next = || lists.next().unwrap_or_default();
+    Parse::Found(ServerAlgorithms {
+        kex: next(),
+        host_key: next(),
+        cipher: Directional { client_to_server: next(), server_to_client: next() },
+        mac: Directional { client_to_server: next(), server_to_client: next() },
+        compression: Directional { client_to_server: next(), server_to_client: next() },
+    })
+}
+
+fn read_name_list(rest: &mut &[u8]) -> Option<Vec<String>> {
+    let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    let raw = rest.get(4..4 + length)?;
+    *rest = &rest[4 + length..];
+    let names = std::str::from_utf8(raw).ok()?;
+    Some(names.split(',').filter(|n| !n.is_empty()).map(str::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kexinit_packet(lists: [&str; 10]) -> Vec<u8> {
+        let mut payload = vec![SSH_MSG_KEXINIT];
+        payload.extend_from_slice(&[0u8; 16]);
+        for list in lists {
+            payload.extend_from_slice(&(list.len() as u32).to_be_bytes());
+            payload.extend_from_slice(list.as_bytes());
+        }
+        payload.push(0); // first_kex_packet_follows
+        payload.extend_from_slice(&[0u8; 4]);
+        // At least 4 bytes of padding, whole packet a multiple of 8.
+        let padding = (8 - (payload.len() + 5) % 8) % 8;
+        let padding = if padding < 4 { padding + 8 } else { padding };
+        let mut packet = ((payload.len() + padding + 1) as u32).to_be_bytes().to_vec();
+        packet.push(padding as u8);
+        packet.extend_from_slice(&payload);
+        packet.extend(std::iter::repeat(0).take(padding));
+        packet
+    }
+
+    fn legacy_server() -> Vec<u8> {
+        let mut data = b"Welcome\r\nSSH-2.0-Cisco-1.25\r\n".to_vec();
+        data.extend(kexinit_packet([
+            "diffie-hellman-group14-sha1,diffie-hellman-group1-sha1",
+            "ssh-rsa",
+            "aes128-cbc,aes256-ctr",
+            "aes256-ctr",
+            "hmac-sha1",
+            "hmac-sha1",
+            "none",
+            "none",
+            "",
+            "",
+        ]));
+        data
+    }
+
+    #[test]
+    fn parses_kexinit_after_banner() {
+        let data = legacy_server();
+        let Parse::Found(server) = parse_server_kexinit(&data) else {
+            panic!("KEXINIT not found");
+        };
+        assert_eq!(server.host_key, vec!["ssh-rsa"]);
+        assert_eq!(server.cipher.client_to_server, vec!["aes128-cbc", "aes256-ctr"]);
+        assert!(matches!(parse_server_kexinit(&data[..data.len() - 3]), Parse::Incomplete));
+    }
+
+    #[test]
+    fn legacy_preset_negotiates_with_old_gear() {
+        let Parse::Found(server) = parse_server_kexinit(&legacy_server()) else {
+            panic!("KEXINIT not found");
+        };
+        let modern = preferred(&CryptoPolicy { preset: CryptoPreset::Modern, ..Default::default() }).unwrap();
+        assert!(negotiate(&modern, &server).is_none());
+
+        let legacy = preferred(&CryptoPolicy { preset: CryptoPreset::Legacy, ..Default::default() }).unwrap();
+        let negotiated = negotiate(&legacy, &server).unwrap();
+        assert_eq!(negotiated.kex, "diffie-hellman-group14-sha1");
+        assert_eq!(negotiated.host_key, "ssh-rsa");
+        assert_eq!(negotiated.cipher.client_to_server, "aes256-ctr");
+        assert_eq!(negotiated.mac.server_to_client.as_deref(), Some("hmac-sha1"));
+    }
+
+    #[test]
+    fn aead_ciphers_have_no_mac() {
+        let server = ServerAlgorithms {
+            kex: vec!["curve25519-sha256".into()],
+            host_key: vec!["ssh-ed25519".into()],
+            cipher: Directional { client_to_server: vec!["chacha20-poly1305@openssh.com".into()], server_to_client: vec!["chacha20-poly1305@openssh.com".into()] },
+            mac: Directional { client_to_server: vec!["hmac-sha2-256".into()], server_to_client: vec!["hmac-sha2-256".into()] },
+            compression: Directional { client_to_server: vec!["none".into()], server_to_client: vec!["none".into()] },
+        };
+        let modern = preferred(&CryptoPolicy { preset: CryptoPreset::Modern, ..Default::default() }).unwrap();
+        let negotiated = negotiate(&modern, &server).unwrap();
+        assert_eq!(negotiated.mac.client_to_server, None);
+    }
+
+    #[test]
+    fn overrides_are_validated_and_keep_extensions() {
+        let policy = CryptoPolicy { kex: Some(vec!["diffie-hellman-group14-sha1".into()]), ..Default::default() };
+        let lists = preferred(&policy).unwrap();
+        let kex: Vec<&str> = lists.kex.iter().map(AsRef::as_ref).collect();
+        assert_eq!(kex[0], "diffie-hellman-group14-sha1");
+        assert!(kex.contains(&kex::EXTENSION_SUPPORT_AS_CLIENT.as_ref()));
+
+        let bad = CryptoPolicy { cipher: Some(vec!["rc4".into()]), ..Default::default() };
+        assert!(preferred(&bad).unwrap_err().contains("rc4"));
+        let empty = CryptoPolicy { mac: Some(Vec::new()), ..Default::default() };
+        assert!(preferred(&empty).is_err());
+    }
+}
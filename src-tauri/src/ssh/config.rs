@@ -1,7 +1,23 @@
+//! Reader for `~/.ssh/config` with OpenSSH's semantics: `Include` (with
+//! globs) is expanded in place, `Host` and `Match` blocks are evaluated in
+//! file order, and the first value obtained for an option wins — so `Host *`
+//! defaults and catch-all blocks apply to imported hosts as they do for `ssh`.
+
 use serde::{Deserialize, Serialize};
-use ssh2_config::{ParseRule, SshConfig};
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::ssh::pattern::{match_pattern_list, wildcard_match};
+use crate::ssh::proxy_command;
+use crate::state::{RequestTty, TunnelConfig, TunnelType};
+use crate::tunnel::manager::TunnelManager;
+
+/// OpenSSH's limit on nested `Include`s.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// How long a `Match exec` command may run before it counts as false.
+const EXEC_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// A resolved SSH host from ~/.ssh/config.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +34,22 @@ pub struct SshHostEntry {
     pub identity_files: Vec<String>,
     /// ProxyJump chain, ordered outermost-first
     pub proxy_jump: Vec<JumpHostEntry>,
+    /// ProxyCommand line, tokens (`%h`, `%p`, `%r`) left unexpanded
+    pub proxy_command: Option<String>,
+    /// IdentitiesOnly: don't fall back to agent identities
+    pub identities_only: bool,
+    /// LocalForward / RemoteForward / DynamicForward, as inactive tunnels
+    pub forwards: Vec<TunnelConfig>,
+    /// ServerAliveInterval in seconds
+    pub server_alive_interval: Option<u64>,
+    /// ServerAliveCountMax
+    pub server_alive_count_max: Option<u32>,
+    /// ForwardAgent
+    pub forward_agent: bool,
+    /// SetEnv variables
+    pub set_env: HashMap<String, String>,
+    /// RequestTTY
+    pub request_tty: Option<RequestTty>,
 }
 
 /// A single jump host in a ProxyJump chain.
@@ -29,6 +61,60 @@ pub struct JumpHostEntry {
     pub identity_files: Vec<String>,
 }
 
+/// A parsed ssh_config, `Include`s expanded in place.
+#[derive(Debug, Default)]
+pub struct SshConfig {
+    blocks: Vec<Block>,
+}
+
+/// A run of options and the conditions under which they apply: the
+/// `Host`/`Match` line that opened it, plus those of the blocks enclosing the
+/// `Include` it came from. All must hold.
+#[derive(Debug, Clone)]
+struct Block {
+    conditions: Vec<Condition>,
+    options: Vec<Directive>,
+}
+
+/// One option line; the keyword is lowercased.
+#[derive(Debug, Clone, PartialEq)]
+struct Directive {
+    keyword: String,
+    args: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    /// `Host` patterns, `!` negating.
+    Host(Vec<String>),
+    /// `Match` criteria, all of which must hold.
+    Match(Vec<Criterion>),
+}
+
+#[derive(Debug, Clone)]
+struct Criterion {
+    negated: bool,
+    kind: CriterionKind,
+}
+
+#[derive(Debug, Clone)]
+enum CriterionKind {
+    All,
+    Canonical,
+    Final,
+    Host(String),
+    OriginalHost(String),
+    User(String),
+    LocalUser(String),
+    Exec(String),
+    /// `address`, `localport`, `tagged`, ... — depend on the live connection,
+    /// so they never match here.
+    Unsupported(String),
+}
+
+/// Options whose value is the raw rest of the line, as OpenSSH reads them.
+const COMMAND_KEYWORDS: &[&str] = &["proxycommand", "localcommand", "remotecommand", "knownhostscommand"];
+
 /// Get the path to ~/.ssh/config (cross-platform).
 fn ssh_config_path() -> Option<PathBuf> {
     dirs::home_dir().map(|h| h.join(".ssh").join("config"))
@@ -45,123 +131,505 @@ pub fn parse_ssh_config() -> Result<Option<SshConfig>, String> {
         return Ok(None);
     }
 
-    let file = std::fs::File::open(&path)
+    let content = std::fs::read_to_string(&path)
         .map_err(|e| format!("Failed to open SSH config: {}", e))?;
-    let mut reader = BufReader::new(file);
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    SshConfig::parse(&content, &base)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse SSH config: {}", e))
+}
+
+impl SshConfig {
+    /// Parse config text. Relative `Include` paths resolve against `base`
+    /// (`~/.ssh` for the user's config).
+    pub fn parse(content: &str, base: &Path) -> Result<Self, String> {
+        let mut config = Self::default();
+        config.parse_into(content, base, &[], 0)?;
+        Ok(config)
+    }
+
+    fn parse_into(&mut self, content: &str, base: &Path, outer: &[Condition], depth: usize) -> Result<(), String> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err("Include nested too deeply".to_string());
+        }
+
+        let mut current = Block { conditions: outer.to_vec(), options: Vec::new() };
+        for (number, line) in content.lines().enumerate() {
+            let Some((keyword, args)) = split_line(line).map_err(|e| format!("line {}: {}", number + 1, e))? else {
+                continue;
+            };
+            match keyword.as_str() {
+                "host" => {
+                    self.push(std::mem::replace(&mut current, Block {
+                        conditions: with(outer, Condition::Host(args)),
+                        options: Vec::new(),
+                    }));
+                }
+                "match" => {
+                    let criteria = parse_criteria(&args).map_err(|e| format!("line {}: {}", number + 1, e))?;
+                    self.push(std::mem::replace(&mut current, Block {
+                        conditions: with(outer, Condition::Match(criteria)),
+                        options: Vec::new(),
+                    }));
+                }
+                "include" => {
+                    // Included files inherit the enclosing block's conditions;
+                    // after them, the block carries on as before.
+                    let conditions = current.conditions.clone();
+                    self.push(std::mem::replace(&mut current, Block {
+                        conditions: conditions.clone(),
+                        options: Vec::new(),
+                    }));
+                    for pattern in &args {
+                        for path in glob_paths(&include_path(pattern, base)) {
+                            let included = std::fs::read_to_string(&path)
+                                .map_err(|e| format!("{}: {}", path.display(), e))?;
+                            self.parse_into(&included, base, &conditions, depth + 1)
+                                .map_err(|e| format!("{}: {}", path.display(), e))?;
+                        }
+                    }
+                }
+                _ => current.options.push(Directive { keyword, args }),
+            }
+        }
+        self.push(current);
+        Ok(())
+    }
+
+    fn push(&mut self, block: Block) {
+        // Empty `Host` blocks are kept: they still name a host.
+        if !block.options.is_empty() || matches!(block.conditions.last(), Some(Condition::Host(_))) {
+            self.blocks.push(block);
+        }
+    }
+
+    /// Names from `Host` lines that stand for one host (no wildcards or
+    /// negation), in file order.
+    fn host_names(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut names = Vec::new();
+        for block in &self.blocks {
+            let Some(Condition::Host(patterns)) = block.conditions.last() else {
+                continue;
+            };
+            for pattern in patterns {
+                if pattern.starts_with('!') || pattern.contains(['*', '?']) {
+                    continue;
+                }
+                if seen.insert(pattern.clone()) {
+                    names.push(pattern.clone());
+                }
+            }
+        }
+        names
+    }
 
-    let config = SshConfig::default()
-        .parse(&mut reader, ParseRule::ALLOW_UNKNOWN_FIELDS)
-        .map_err(|e| format!("Failed to parse SSH config: {}", e))?;
+    /// Every option that applies to `alias`, in the order OpenSSH sees them.
+    /// A second, `final` pass runs when the config has `Match final`.
+    fn query(&self, alias: &str, execs: &mut Execs) -> Resolved {
+        let mut resolved = Resolved::default();
+        self.apply(alias, false, &mut resolved, execs);
+        let has_final = self.blocks.iter().flat_map(|b| &b.conditions).any(|c| {
+            matches!(c, Condition::Match(criteria) if criteria.iter().any(|k| matches!(k.kind, CriterionKind::Final)))
+        });
+        if has_final {
+            self.apply(alias, true, &mut resolved, execs);
+        }
+        resolved
+    }
 
-    Ok(Some(config))
+    fn apply(&self, alias: &str, final_pass: bool, resolved: &mut Resolved, execs: &mut Execs) {
+        for block in &self.blocks {
+            let target = Target {
+                alias,
+                hostname: resolved.hostname(alias),
+                port: resolved.port(),
+                user: resolved.user(),
+                final_pass,
+            };
+            if block.conditions.iter().all(|c| target.matches(c, execs)) {
+                for directive in &block.options {
+                    if !resolved.options.contains(directive) {
+                        resolved.options.push(directive.clone());
+                    }
+                }
+            }
+        }
+    }
 }
 
-/// List all named (non-wildcard) hosts in the SSH config.
-pub fn list_hosts(config: &SshConfig) -> Vec<SshHostEntry> {
-    let mut entries = Vec::new();
+/// The host being resolved, as known at a point in the evaluation.
+struct Target<'a> {
+    alias: &'a str,
+    hostname: String,
+    port: u16,
+    user: String,
+    final_pass: bool,
+}
+
+impl Target<'_> {
+    fn matches(&self, condition: &Condition, execs: &mut Execs) -> bool {
+        match condition {
+            // The final pass matches `Host` against the substituted hostname,
+            // as OpenSSH's second pass does.
+            Condition::Host(patterns) => {
+                let name = if self.final_pass { &self.hostname } else { self.alias };
+                match_pattern_list(name, patterns.iter().map(String::as_str), true)
+            }
+            Condition::Match(criteria) => criteria.iter().all(|c| self.criterion(c, execs) != c.negated),
+        }
+    }
+
+    fn criterion(&self, criterion: &Criterion, execs: &mut Execs) -> bool {
+        let list = |value: &str, patterns: &str, fold_case: bool| {
+            match_pattern_list(value, patterns.split(','), fold_case)
+        };
+        match &criterion.kind {
+            CriterionKind::All => true,
+            // Reach doesn't canonicalize hostnames.
+            CriterionKind::Canonical => false,
+            CriterionKind::Final => self.final_pass,
+            CriterionKind::Host(p) => list(&self.hostname, p, true),
+            CriterionKind::OriginalHost(p) => list(self.alias, p, true),
+            CriterionKind::User(p) => list(&self.user, p, false),
+            CriterionKind::LocalUser(p) => list(&local_user(), p, false),
+            CriterionKind::Exec(command) => {
+                let Execs::Run(cache) = execs else { return false };
+                match self.expand_exec(command) {
+                    Ok(command) => *cache.entry(command.clone()).or_insert_with(|| run_exec(&command)),
+                    Err(e) => {
+                        tracing::warn!("ssh_config: {}", e);
+                        false
+                    }
+                }
+            }
+            CriterionKind::Unsupported(name) => {
+                tracing::debug!("ssh_config: Match {} is not supported, treating as false", name);
+                false
+            }
+        }
+    }
+
+    /// Expand the `%` tokens in a `Match exec` command. The host, alias and
+    /// user go in unquoted, so values the shell would interpret are refused,
+    /// as `ProxyCommand` does.
+    fn expand_exec(&self, command: &str) -> Result<String, String> {
+        let safe = |value: &str, what: &str| {
+            if proxy_command::is_shell_safe(value) {
+                Ok(value.to_string())
+            } else {
+                Err(format!("Match exec: refusing to substitute {} '{}'", what, value))
+            }
+        };
+        let mut out = String::with_capacity(command.len());
+        let mut chars = command.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('h') => out.push_str(&safe(&self.hostname, "host name")?),
+                Some('n') => out.push_str(&safe(self.alias, "host alias")?),
+                Some('p') => out.push_str(&self.port.to_string()),
+                Some('r') => out.push_str(&safe(&self.user, "user name")?),
+                Some('u') => out.push_str(&local_user()),
+                Some('d') => out.push_str(&home_dir_string()),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Whether `Match exec` commands are run, and what they returned so far.
+enum Execs {
+    /// Resolving a host to connect to: run each command once.
+    Run(HashMap<String, bool>),
+    /// Listing hosts: run nothing; `exec` never matches.
+    Skip,
+}
+
+/// Run a `Match exec` command through the shell; true on exit status 0. One
+/// still running after `EXEC_TIMEOUT` is killed and counts as false.
+fn run_exec(command: &str) -> bool {
+    #[cfg(unix)]
+    let mut cmd = {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        let mut cmd = std::process::Command::new(shell);
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.arg("/C").raw_arg(command);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd
+    };
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!("ssh_config: Match exec '{}' failed to run: {}", command, e);
+            return false;
+        }
+    };
+    let deadline = Instant::now() + EXEC_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                tracing::warn!("ssh_config: Match exec '{}' timed out", command);
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+            Err(e) => {
+                tracing::warn!("ssh_config: Match exec '{}' failed: {}", command, e);
+                return false;
+            }
+        }
+    }
+}
 
-    for host in config.get_hosts() {
-        for clause in &host.pattern {
-            // Skip wildcard patterns and negated patterns
-            if clause.negated || clause.pattern.contains('*') || clause.pattern.contains('?') {
+/// The options collected for one host.
+#[derive(Debug, Default)]
+struct Resolved {
+    options: Vec<Directive>,
+}
+
+impl Resolved {
+    /// First value obtained for a single-valued option.
+    fn first(&self, keyword: &str) -> Option<&[String]> {
+        self.options
+            .iter()
+            .find(|d| d.keyword == keyword && !d.args.is_empty())
+            .map(|d| d.args.as_slice())
+    }
+
+    fn first_arg(&self, keyword: &str) -> Option<&str> {
+        self.first(keyword).map(|args| args[0].as_str())
+    }
+
+    /// Every occurrence of a multi-valued option (`IdentityFile`, `SetEnv`,
+    /// the forwards).
+    fn all<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = &'a [String]> + 'a {
+        self.options
+            .iter()
+            .filter(move |d| d.keyword == keyword && !d.args.is_empty())
+            .map(|d| d.args.as_slice())
+    }
+
+    fn flag(&self, keyword: &str) -> Option<bool> {
+        self.first_arg(keyword).map(|v| v.eq_ignore_ascii_case("yes"))
+    }
+
+    /// `HostName` with `%h` expanded, else the alias itself.
+    fn hostname(&self, alias: &str) -> String {
+        match self.first_arg("hostname") {
+            Some(name) => name.replace("%h", alias).replace("%%", "%"),
+            None => alias.to_string(),
+        }
+    }
+
+    fn port(&self) -> u16 {
+        self.first_arg("port").and_then(|p| p.parse().ok()).unwrap_or(22)
+    }
+
+    fn user(&self) -> String {
+        self.first_arg("user").map(str::to_string).unwrap_or_else(local_user)
+    }
+
+    fn identity_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for args in self.all("identityfile") {
+            if args[0].eq_ignore_ascii_case("none") {
                 continue;
             }
+            let path = resolve_tilde(Path::new(&args[0].replace("%d", &home_dir_string())))
+                .display()
+                .to_string();
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    fn proxy_command(&self) -> Option<String> {
+        self.first_arg("proxycommand")
+            .filter(|c| !c.eq_ignore_ascii_case("none"))
+            .map(str::to_string)
+    }
+
+    fn forwards(&self) -> Vec<TunnelConfig> {
+        let mut tunnels = Vec::new();
+        for (keyword, tunnel_type) in [
+            ("localforward", TunnelType::Local),
+            ("remoteforward", TunnelType::Remote),
+            ("dynamicforward", TunnelType::Dynamic),
+        ] {
+            for args in self.all(keyword) {
+                match parse_forward(&tunnel_type, args) {
+                    Some(tunnel) => tunnels.push(tunnel),
+                    None => tracing::debug!("ssh_config: skipping unsupported {} {}", keyword, args.join(" ")),
+                }
+            }
+        }
+        tunnels
+    }
 
-            let name = clause.pattern.clone();
-            let params = config.query(&name);
+    fn set_env(&self) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        for args in self.all("setenv") {
+            for assignment in args {
+                if let Some((name, value)) = assignment.split_once('=') {
+                    env.entry(name.to_string()).or_insert_with(|| value.to_string());
+                }
+            }
+        }
+        env
+    }
 
-            let hostname = params
-                .host_name
-                .clone()
-                .unwrap_or_else(|| name.clone());
-            let port = params.port.unwrap_or(22);
-            let user = params
-                .user
-                .clone()
-                .unwrap_or_else(|| whoami().unwrap_or_else(|| "root".to_string()));
-            let identity_files = params
-                .identity_file
-                .as_ref()
-                .map(|files| {
-                    files
-                        .iter()
-                        .map(|p| resolve_tilde(p).display().to_string())
-                        .collect()
-                })
-                .unwrap_or_default();
+    fn request_tty(&self) -> Option<RequestTty> {
+        match self.first_arg("requesttty")?.to_ascii_lowercase().as_str() {
+            "yes" => Some(RequestTty::Yes),
+            "no" => Some(RequestTty::No),
+            "force" => Some(RequestTty::Force),
+            "auto" => Some(RequestTty::Auto),
+            _ => None,
+        }
+    }
+}
 
-            // Resolve ProxyJump chain
-            let proxy_jump = resolve_proxy_jump(config, &params);
+/// Map a `*Forward` directive onto a tunnel. Unix-socket forwards and remote
+/// dynamic forwards (`RemoteForward` without a destination) have no
+/// equivalent and are skipped.
+fn parse_forward(tunnel_type: &TunnelType, args: &[String]) -> Option<TunnelConfig> {
+    let listen = split_forward(&args[0]);
+    let (bind, port) = match listen.as_slice() {
+        [port] => (None, port.parse::<u16>().ok()?),
+        // An empty or `*` bind address means every interface.
+        [bind, port] if bind.is_empty() || bind == "*" => (Some("0.0.0.0".to_string()), port.parse::<u16>().ok()?),
+        [bind, port] => (Some(bind.clone()), port.parse::<u16>().ok()?),
+        _ => return None,
+    };
+    let destination = || {
+        let target = split_forward(args.get(1)?);
+        match target.as_slice() {
+            [host, port] => Some((host.clone(), port.parse::<u16>().ok()?)),
+            _ => None,
+        }
+    };
+    let tunnel = match tunnel_type {
+        TunnelType::Local => {
+            let (host, host_port) = destination()?;
+            TunnelManager::create_tunnel(TunnelType::Local, port, &host, host_port, "", bind)
+        }
+        TunnelType::Remote => {
+            let (host, host_port) = destination()?;
+            let listen_host = bind.unwrap_or_else(|| "localhost".to_string());
+            TunnelManager::create_tunnel(TunnelType::Remote, host_port, &listen_host, port, "", Some(host))
+        }
+        TunnelType::Dynamic => TunnelManager::create_tunnel(TunnelType::Dynamic, port, "", 0, "", bind),
+    };
+    Some(tunnel)
+}
 
-            entries.push(SshHostEntry {
-                name,
-                hostname,
-                port,
-                user,
-                identity_files,
-                proxy_jump,
-            });
+/// Split `[addr]:port`, `host:port` or `port` on colons outside brackets.
+/// Paths (unix sockets) come back as one element containing a `/`.
+fn split_forward(spec: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut bracketed = false;
+    for c in spec.chars() {
+        match c {
+            '[' => bracketed = true,
+            ']' => bracketed = false,
+            ':' if !bracketed => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
         }
     }
+    parts.push(current);
+    if parts.iter().any(|p| p.contains('/')) {
+        return vec![spec.to_string()];
+    }
+    parts
+}
 
-    entries
+/// List all named (non-wildcard) hosts in the SSH config, each resolved with
+/// every block that applies to it (`Host *` defaults included). No `Match
+/// exec` command is run for a listing: those blocks don't apply until the
+/// host is resolved on its own with [`resolve_host`].
+pub fn list_hosts(config: &SshConfig) -> Vec<SshHostEntry> {
+    config
+        .host_names()
+        .iter()
+        .map(|name| entry(config, name, &mut Execs::Skip))
+        .collect()
 }
 
-/// Resolve a single host by name and return its full config.
+/// Resolve a single host by name and return its full config. Runs the
+/// config's `Match exec` commands, so call it off the async runtime.
 pub fn resolve_host(config: &SshConfig, hostname: &str) -> SshHostEntry {
-    let params = config.query(hostname);
-
-    let resolved_hostname = params
-        .host_name
-        .clone()
-        .unwrap_or_else(|| hostname.to_string());
-    let port = params.port.unwrap_or(22);
-    let user = params
-        .user
-        .clone()
-        .unwrap_or_else(|| whoami().unwrap_or_else(|| "root".to_string()));
-    let identity_files = params
-        .identity_file
-        .as_ref()
-        .map(|files| {
-            files
-                .iter()
-                .map(|p| resolve_tilde(p).display().to_string())
-                .collect()
-        })
-        .unwrap_or_default();
-
-    let proxy_jump = resolve_proxy_jump(config, &params);
+    entry(config, hostname, &mut Execs::Run(HashMap::new()))
+}
+
+fn entry(config: &SshConfig, hostname: &str, execs: &mut Execs) -> SshHostEntry {
+    let params = config.query(hostname, execs);
 
     SshHostEntry {
         name: hostname.to_string(),
-        hostname: resolved_hostname,
-        port,
-        user,
-        identity_files,
-        proxy_jump,
+        hostname: params.hostname(hostname),
+        port: params.port(),
+        user: params.user(),
+        identity_files: params.identity_files(),
+        proxy_jump: resolve_proxy_jump(config, &params, execs),
+        proxy_command: params.proxy_command(),
+        identities_only: params.flag("identitiesonly").unwrap_or(false),
+        forwards: params.forwards(),
+        server_alive_interval: params.first_arg("serveraliveinterval").and_then(|v| v.parse().ok()),
+        server_alive_count_max: params.first_arg("serveralivecountmax").and_then(|v| v.parse().ok()),
+        // "yes", or the path of an agent socket to forward
+        forward_agent: params
+            .first_arg("forwardagent")
+            .is_some_and(|v| !v.eq_ignore_ascii_case("no")),
+        set_env: params.set_env(),
+        request_tty: params.request_tty(),
     }
 }
 
 /// Resolve the ProxyJump chain from a host's params.
 /// Returns jump hosts ordered outermost-first (connect first hop first).
-fn resolve_proxy_jump(
-    config: &SshConfig,
-    params: &ssh2_config::HostParams,
-) -> Vec<JumpHostEntry> {
-    let jump_specs = match &params.proxy_jump {
-        Some(specs) if !specs.is_empty() => specs.clone(),
-        _ => return Vec::new(),
-    };
-
+fn resolve_proxy_jump(config: &SshConfig, params: &Resolved, execs: &mut Execs) -> Vec<JumpHostEntry> {
     let mut chain = Vec::new();
-    let mut seen = std::collections::HashSet::new();
+    push_proxy_jump(config, params, &mut chain, &mut HashSet::new(), execs);
+    chain
+}
 
-    for spec in &jump_specs {
-        resolve_jump_recursive(config, spec, &mut chain, &mut seen);
+fn push_proxy_jump(
+    config: &SshConfig,
+    params: &Resolved,
+    chain: &mut Vec<JumpHostEntry>,
+    seen: &mut HashSet<String>,
+    execs: &mut Execs,
+) {
+    if let Some(specs) = params.first_arg("proxyjump").filter(|s| !s.eq_ignore_ascii_case("none")) {
+        for spec in specs.split(',') {
+            resolve_jump_recursive(config, spec.trim(), chain, seen, execs);
+        }
     }
-
-    chain
 }
 
 /// Recursively resolve a jump host spec (which may itself have ProxyJump).
@@ -169,79 +637,198 @@ fn resolve_jump_recursive(
     config: &SshConfig,
     spec: &str,
     chain: &mut Vec<JumpHostEntry>,
-    seen: &mut std::collections::HashSet<String>,
+    seen: &mut HashSet<String>,
+    execs: &mut Execs,
 ) {
     // Prevent infinite loops
-    if seen.contains(spec) {
+    if spec.is_empty() || seen.contains(spec) {
         return;
     }
     seen.insert(spec.to_string());
 
     // Parse "user@host:port" or "host:port" or "host"
-    let (user_part, host_port) = if let Some(at_pos) = spec.find('@') {
-        (Some(&spec[..at_pos]), &spec[at_pos + 1..])
+    let spec_uri = spec.strip_prefix("ssh://").unwrap_or(spec);
+    let (user_part, host_port) = if let Some(at_pos) = spec_uri.rfind('@') {
+        (Some(&spec_uri[..at_pos]), &spec_uri[at_pos + 1..])
     } else {
-        (None, spec)
+        (None, spec_uri)
     };
 
-    let (host, port) = if let Some(colon_pos) = host_port.rfind(':') {
-        let port_str = &host_port[colon_pos + 1..];
-        if let Ok(p) = port_str.parse::<u16>() {
-            (&host_port[..colon_pos], p)
-        } else {
-            (host_port, 22u16)
-        }
-    } else {
-        (host_port, 22u16)
+    let (host, port) = match split_forward(host_port).as_slice() {
+        [host, port] => match port.parse::<u16>() {
+            Ok(p) => (host.clone(), Some(p)),
+            Err(_) => (host_port.to_string(), None),
+        },
+        _ => (host_port.to_string(), None),
     };
 
     // Query the config for this jump host to resolve its own settings
-    let jump_params = config.query(host);
-
-    let resolved_host = jump_params
-        .host_name
-        .clone()
-        .unwrap_or_else(|| host.to_string());
-    let resolved_port = jump_params.port.unwrap_or(port);
-    let resolved_user = user_part
-        .map(|u| u.to_string())
-        .or_else(|| jump_params.user.clone())
-        .unwrap_or_else(|| whoami().unwrap_or_else(|| "root".to_string()));
-    let identity_files = jump_params
-        .identity_file
-        .as_ref()
-        .map(|files| {
-            files
-                .iter()
-                .map(|p| resolve_tilde(p).display().to_string())
-                .collect()
-        })
-        .unwrap_or_default();
+    let jump_params = config.query(&host, execs);
 
     // If this jump host itself has ProxyJump, resolve those first (outermost first)
-    if let Some(nested_jumps) = &jump_params.proxy_jump {
-        for nested in nested_jumps {
-            resolve_jump_recursive(config, nested, chain, seen);
-        }
-    }
+    push_proxy_jump(config, &jump_params, chain, seen, execs);
 
     chain.push(JumpHostEntry {
-        host: resolved_host,
-        port: resolved_port,
-        user: resolved_user,
-        identity_files,
+        host: jump_params.hostname(&host),
+        port: port.unwrap_or_else(|| jump_params.port()),
+        user: user_part.map(str::to_string).unwrap_or_else(|| jump_params.user()),
+        identity_files: jump_params.identity_files(),
     });
 }
 
+/// Split one config line into its lowercased keyword and arguments, OpenSSH
+/// style: `Keyword value`, `Keyword=value` or `Keyword = value`; double or
+/// single quotes group words; `#` starting a word begins a comment. The
+/// `*Command` options keep the rest of the line verbatim, as in OpenSSH.
+fn split_line(line: &str) -> Result<Option<(String, Vec<String>)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let key_end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let keyword = line[..key_end].to_ascii_lowercase();
+    let mut rest = line[key_end..].trim_start();
+    if let Some(stripped) = rest.strip_prefix('=') {
+        rest = stripped.trim_start();
+    }
+    if COMMAND_KEYWORDS.contains(&keyword.as_str()) {
+        let args = if rest.is_empty() { Vec::new() } else { vec![rest.to_string()] };
+        return Ok(Some((keyword, args)));
+    }
+    Ok(Some((keyword, split_args(rest)?)))
+}
+
+fn split_args(s: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.peek() {
+            None | Some('#') => break,
+            _ => {}
+        }
+        let mut arg = String::new();
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (None, c) if c.is_whitespace() => break,
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), c) if c == q => quote = None,
+                (_, '\\') if matches!(chars.peek(), Some('"' | '\'' | '\\' | ' ')) => {
+                    arg.extend(chars.next());
+                }
+                (_, c) => arg.push(c),
+            }
+        }
+        if quote.is_some() {
+            return Err("unterminated quote".to_string());
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+fn parse_criteria(args: &[String]) -> Result<Vec<Criterion>, String> {
+    let mut criteria = Vec::new();
+    let mut args = args.iter();
+    while let Some(word) = args.next() {
+        let (negated, name) = match word.strip_prefix('!') {
+            Some(name) => (true, name.to_ascii_lowercase()),
+            None => (false, word.to_ascii_lowercase()),
+        };
+        let kind = match name.as_str() {
+            "all" => CriterionKind::All,
+            "canonical" => CriterionKind::Canonical,
+            "final" => CriterionKind::Final,
+            _ => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Match {} needs an argument", name))?
+                    .clone();
+                match name.as_str() {
+                    "host" => CriterionKind::Host(value),
+                    "originalhost" => CriterionKind::OriginalHost(value),
+                    "user" => CriterionKind::User(value),
+                    "localuser" => CriterionKind::LocalUser(value),
+                    "exec" => CriterionKind::Exec(value),
+                    _ => CriterionKind::Unsupported(name),
+                }
+            }
+        };
+        criteria.push(Criterion { negated, kind });
+    }
+    Ok(criteria)
+}
+
+fn with(outer: &[Condition], condition: Condition) -> Vec<Condition> {
+    let mut conditions = outer.to_vec();
+    conditions.push(condition);
+    conditions
+}
+
+/// Where an `Include` argument points: `~` is the home directory and
+/// relative paths are taken from `base`.
+fn include_path(pattern: &str, base: &Path) -> PathBuf {
+    let path = resolve_tilde(Path::new(pattern));
+    if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+/// Expand `*` and `?` in any component of `pattern`, like glob(3): results
+/// are sorted and dotfiles only match patterns that start with a dot.
+/// Nonexistent files are dropped, as OpenSSH ignores them.
+fn glob_paths(pattern: &Path) -> Vec<PathBuf> {
+    let mut found = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?']) {
+            for path in &mut found {
+                path.push(component);
+            }
+            continue;
+        }
+        let mut next = Vec::new();
+        for dir in &found {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|n| (!n.starts_with('.') || part.starts_with('.')) && wildcard_match(&part, n, false))
+                .collect();
+            names.sort();
+            next.extend(names.into_iter().map(|n| dir.join(n)));
+        }
+        found = next;
+    }
+    found.retain(|p| p.is_file());
+    found
+}
+
 /// Resolve ~ to home directory in a path.
-fn resolve_tilde(path: &PathBuf) -> PathBuf {
+fn resolve_tilde(path: &Path) -> PathBuf {
     let s = path.display().to_string();
     if s.starts_with("~/") || s.starts_with("~\\") {
         if let Some(home) = dirs::home_dir() {
             return home.join(&s[2..]);
         }
     }
-    path.clone()
+    path.to_path_buf()
+}
+
+fn home_dir_string() -> String {
+    dirs::home_dir().map(|h| h.display().to_string()).unwrap_or_default()
+}
+
+/// The local user's name, falling back to "root".
+fn local_user() -> String {
+    whoami().unwrap_or_else(|| "root".to_string())
 }
 
 /// Get the current user's username.
@@ -250,3 +837,151 @@ fn whoami() -> Option<String> {
         .or_else(|_| std::env::var("USERNAME"))
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> SshConfig {
+        SshConfig::parse(content, Path::new("/nonexistent")).unwrap()
+    }
+
+    #[test]
+    fn proxy_command_none_disables_it() {
+        let config = parse("Host a\n  ProxyCommand none\nHost *\n  ProxyCommand nc %h %p\n");
+        assert_eq!(resolve_host(&config, "a").proxy_command, None);
+        assert_eq!(resolve_host(&config, "b").proxy_command.as_deref(), Some("nc %h %p"));
+    }
+
+    #[test]
+    fn proxy_command_keeps_the_line_verbatim() {
+        let config = parse(
+            "Host cf\n  ProxyCommand cloudflared access ssh --hostname %h\n\
+             Host q\n  ProxyCommand=sh -c 'nc %h %p'\n",
+        );
+        assert_eq!(
+            resolve_host(&config, "cf").proxy_command.as_deref(),
+            Some("cloudflared access ssh --hostname %h")
+        );
+        assert_eq!(resolve_host(&config, "q").proxy_command.as_deref(), Some("sh -c 'nc %h %p'"));
+    }
+
+    #[test]
+    fn first_value_wins_and_wildcards_supply_defaults() {
+        let config = parse(
+            "Host web\n  HostName %h.example.com\n  User deploy\n\
+             Host *.example.com web\n  User nobody\n  Port 2222\n\
+             Host *\n  ServerAliveInterval 30\n  IdentityFile ~/.ssh/id_a\n  IdentityFile ~/.ssh/id_b\n",
+        );
+        assert_eq!(config.host_names(), vec!["web"]);
+        let web = resolve_host(&config, "web");
+        assert_eq!(web.hostname, "web.example.com");
+        assert_eq!(web.user, "deploy");
+        assert_eq!(web.port, 2222);
+        assert_eq!(web.server_alive_interval, Some(30));
+        assert_eq!(web.identity_files.len(), 2);
+    }
+
+    #[test]
+    fn negated_host_patterns_exclude() {
+        let config = parse("Host * !bastion\n  ProxyJump bastion\n");
+        assert!(resolve_host(&config, "bastion").proxy_jump.is_empty());
+        assert_eq!(resolve_host(&config, "db").proxy_jump[0].host, "bastion");
+    }
+
+    #[test]
+    fn match_host_user_and_exec() {
+        let config = parse(
+            "Host db\n  HostName db.internal\n  User admin\n\
+             Match host *.internal user admin\n  Port 2200\n\
+             Match originalhost db exec \"exit 1\"\n  ForwardAgent yes\n\
+             Match !host *.internal\n  RequestTTY no\n",
+        );
+        let db = resolve_host(&config, "db");
+        assert_eq!(db.port, 2200);
+        assert!(!db.forward_agent);
+        assert_eq!(db.request_tty, None);
+        assert_eq!(resolve_host(&config, "other").request_tty, Some(RequestTty::No));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn match_exec_expands_tokens() {
+        let config = parse("Match exec \"test %n = box && test %p = 22\"\n  IdentitiesOnly yes\n");
+        assert!(resolve_host(&config, "box").identities_only);
+        assert!(!resolve_host(&config, "other").identities_only);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn match_exec_refuses_shell_metacharacters() {
+        let config = parse("Match exec \"false %n\"\n  IdentitiesOnly yes\n");
+        assert!(!resolve_host(&config, "box;true").identities_only);
+        assert!(!resolve_host(&config, "$(true)").identities_only);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listing_runs_no_exec_commands() {
+        let config = parse("Host box\n  User admin\nMatch exec true\n  IdentitiesOnly yes\n");
+        let listed = list_hosts(&config);
+        assert_eq!(listed[0].user, "admin");
+        assert!(!listed[0].identities_only);
+        assert!(resolve_host(&config, "box").identities_only);
+    }
+
+    #[test]
+    fn match_final_sees_substituted_hostname() {
+        let config = parse("Host short\n  HostName long.example.com\nMatch final host long.example.com\n  User late\n");
+        assert_eq!(resolve_host(&config, "short").user, "late");
+    }
+
+    #[test]
+    fn forwards_env_and_flags() {
+        let config = parse(
+            "Host h\n  LocalForward 8080 localhost:80\n  LocalForward [::1]:5432 db:5432\n\
+             \x20 RemoteForward 9000 127.0.0.1:3000\n  DynamicForward 1080\n  RemoteForward 7000\n\
+             \x20 SetEnv LANG=C.UTF-8 \"GREETING=hello world\"\n  SetEnv LANG=fr_FR\n\
+             \x20 IdentitiesOnly yes\n  ServerAliveCountMax 5\n  RequestTTY force\n",
+        );
+        let h = resolve_host(&config, "h");
+        assert_eq!(h.forwards.len(), 4);
+        let local = &h.forwards[1];
+        assert!(matches!(local.tunnel_type, TunnelType::Local));
+        assert_eq!((local.local_host.as_deref(), local.local_port), (Some("::1"), 5432));
+        assert_eq!((local.remote_host.as_str(), local.remote_port), ("db", 5432));
+        let remote = &h.forwards[2];
+        assert!(matches!(remote.tunnel_type, TunnelType::Remote));
+        assert_eq!((remote.remote_host.as_str(), remote.remote_port), ("localhost", 9000));
+        assert_eq!((remote.local_host.as_deref(), remote.local_port), (Some("127.0.0.1"), 3000));
+        assert!(matches!(h.forwards[3].tunnel_type, TunnelType::Dynamic));
+        assert_eq!(h.set_env["LANG"], "C.UTF-8");
+        assert_eq!(h.set_env["GREETING"], "hello world");
+        assert!(h.identities_only);
+        assert_eq!(h.server_alive_count_max, Some(5));
+        assert_eq!(h.request_tty, Some(RequestTty::Force));
+    }
+
+    #[test]
+    fn include_expands_globs_in_place() {
+        let dir = std::env::temp_dir().join(format!("reach-sshcfg-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(dir.join("conf.d/b.conf"), "Host beta\n  Port 2\n").unwrap();
+        std::fs::write(dir.join("conf.d/a.conf"), "Host alpha\n  Port 1\n").unwrap();
+        std::fs::write(dir.join("conf.d/.hidden.conf"), "Host hidden\n  Port 3\n").unwrap();
+        std::fs::write(dir.join("inner"), "User fromhost\n").unwrap();
+
+        let config = SshConfig::parse(
+            "Include conf.d/*.conf\nHost gamma\n  Include inner\n  Port 4\nHost *\n  User default\n",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(config.host_names(), vec!["alpha", "beta", "gamma"]);
+        assert_eq!(resolve_host(&config, "alpha").port, 1);
+        let gamma = resolve_host(&config, "gamma");
+        assert_eq!((gamma.user.as_str(), gamma.port), ("fromhost", 4));
+        assert_eq!(resolve_host(&config, "beta").user, "default");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+//! Trusted host certificate authorities — Reach's equivalent of OpenSSH's
+//! `@cert-authority <patterns> <ca-key>` known_hosts lines.
+//!
+//! A CA is trusted for the hosts its pattern list matches (`*.corp.example`,
+//! `10.0.*`, `!legacy.corp.example`, `[gw.corp.example]:2222`). A host
+//! certificate is accepted when it is a *host* certificate, signed by a CA
+//! trusted for that host, names the host among its principals, and is inside
+//! its validity window. Every failed check produces a sentence the host-key
+//! prompt can show as-is.
+//!
+//! The SSH transport only negotiates plain host-key algorithms, so servers
+//! never present their certificate while connecting and a CA-covered host
+//! prompts like any other. A certificate obtained out of band
+//! (`ssh-keyscan -c`) can be trusted for its host instead. It is stored
+//! whole, never reduced to a pinned key, and re-verified against the CAs and
+//! the clock on every connect: once it expires or its CA is removed, the
+//! host prompts again with the reason.
+//!
+//! Stored as JSON next to `known_hosts.json` in the app data dir.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedHostCa {
+    pub id: String,
+    /// OpenSSH host pattern list, comma-separated; `!` negates.
+    pub patterns: String,
+    /// CA public key in OpenSSH format (`ssh-ed25519 AAAA... comment`).
+    pub public_key: String,
+    /// `SHA256:...` fingerprint of the CA key, for display.
+    pub fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A host certificate trusted for `host:port`, in OpenSSH format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedHostCertificate {
+    pub host: String,
+    pub port: u16,
+    pub certificate: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HostCaStore {
+    cas: Vec<TrustedHostCa>,
+    #[serde(default)]
+    certificates: Vec<TrustedHostCertificate>,
+}
+
+fn store_path() -> PathBuf {
+    crate::app_data_dir().join("ssh").join("host_cas.json")
+}
+
+/// A missing or unreadable store means "nothing trusted".
+fn load_store() -> HostCaStore {
+    std::fs::read_to_string(store_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str::<HostCaStore>(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// All trusted host CAs.
+pub fn load() -> Vec<TrustedHostCa> {
+    load_store().cas
+}
+
+fn save_store(store: &HostCaStore) -> Result<(), String> {
+    let path = store_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    std::fs::write(&path, raw).map_err(|e| e.to_string())
+}
+
+/// Trust `public_key` as a host CA for the hosts matched by `patterns`.
+pub fn add(patterns: &str, public_key: &str, comment: Option<String>) -> Result<TrustedHostCa, String> {
+    let patterns = patterns.trim();
+    if patterns.is_empty() {
+        return Err("A host pattern is required (e.g. *.corp.example)".into());
+    }
+    let key = ssh_key::PublicKey::from_openssh(public_key.trim())
+        .map_err(|e| format!("Invalid CA public key: {}", e))?;
+    let ca = TrustedHostCa {
+        id: uuid::Uuid::new_v4().to_string(),
+        patterns: patterns.to_string(),
+        public_key: public_key.trim().to_string(),
+        fingerprint: key.fingerprint(ssh_key::HashAlg::Sha256).to_string(),
+        comment: comment
+            .filter(|c| !c.trim().is_empty())
+            .or_else(|| Some(key.comment().to_string()).filter(|c| !c.is_empty())),
+    };
+    let mut store = load_store();
+    store.cas.push(ca.clone());
+    save_store(&store)?;
+    Ok(ca)
+}
+
+pub fn remove(id: &str) -> Result<(), String> {
+    let mut store = load_store();
+    let before = store.cas.len();
+    store.cas.retain(|c| c.id != id);
+    if store.cas.len() == before {
+        return Err(format!("Trusted host CA not found: {}", id));
+    }
+    save_store(&store)
+}
+
+/// Verify a host certificate (e.g. from `ssh-keyscan -c host`) and trust it
+/// for `host:port`, replacing any certificate trusted there before. Returns
+/// the fingerprint of the key it certifies, or why it was rejected.
+pub fn trust_certificate(host: &str, port: u16, certificate: &str) -> Result<String, String> {
+    let cert = crate::ssh::keyfile::parse_certificate(certificate)?;
+    let mut store = load_store();
+    let ca = verify_certificate(&cert, host, port, &store.cas, unix_now())?.fingerprint.clone();
+    let (_, public_key) = certified_key(&cert)?;
+
+    store.certificates.retain(|c| !(c.host.eq_ignore_ascii_case(host) && c.port == port));
+    store.certificates.push(TrustedHostCertificate {
+        host: host.to_string(),
+        port,
+        certificate: cert.to_openssh().map_err(|e| e.to_string())?,
+    });
+    save_store(&store)?;
+    tracing::info!(
+        "Trusted host certificate '{}' for {}:{}, signed by CA {}, until {}",
+        cert.key_id(), host, port, ca, format_unix_utc(cert.valid_before())
+    );
+    Ok(crate::ssh::known_hosts::fingerprint_of(&public_key))
+}
+
+/// Re-verify the certificate trusted for `host:port` against the key the
+/// server presented (base64 blob), the current CAs and the clock. `None`
+/// when no certificate is trusted there; otherwise the CA vouching for the
+/// key now, or why the certificate no longer does.
+pub fn check_trusted_certificate(host: &str, port: u16, public_key: &str) -> Option<Result<TrustedHostCa, String>> {
+    let store = load_store();
+    let trusted = store
+        .certificates
+        .iter()
+        .find(|c| c.host.eq_ignore_ascii_case(host) && c.port == port)?;
+    let cert = match ssh_key::Certificate::from_openssh(&trusted.certificate) {
+        Ok(cert) => cert,
+        Err(e) => return Some(Err(format!("The trusted host certificate can't be read: {}.", e))),
+    };
+    match certified_key(&cert) {
+        Ok((_, certified)) if certified == public_key => {}
+        Ok(_) => {
+            return Some(Err(
+                "The server's key is not the one its trusted host certificate certifies.".into(),
+            ))
+        }
+        Err(e) => return Some(Err(e)),
+    }
+    Some(verify_certificate(&cert, host, port, &store.cas, unix_now()).cloned())
+}
+
+/// The `(key type, base64 blob)` a certificate certifies.
+fn certified_key(cert: &ssh_key::Certificate) -> Result<(String, String), String> {
+    let openssh = ssh_key::PublicKey::from(cert.public_key().clone())
+        .to_openssh()
+        .map_err(|e| e.to_string())?;
+    crate::ssh::known_hosts::parse_public_key(&openssh)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// CAs whose pattern list covers `host:port`.
+pub fn cas_for_host<'a>(cas: &'a [TrustedHostCa], host: &str, port: u16) -> Vec<&'a TrustedHostCa> {
+    cas.iter().filter(|c| host_matches(&c.patterns, host, port)).collect()
+}
+
+/// OpenSSH `match_hostname` semantics: comma-separated patterns with `*`/`?`
+/// wildcards, case-insensitive; any matching `!pattern` vetoes the host. Hosts
+/// on a non-standard port are matched as `[host]:port`, like known_hosts.
+pub fn host_matches(patterns: &str, host: &str, port: u16) -> bool {
+    let candidate = if port == 22 { host.to_string() } else { format!("[{}]:{}", host, port) };
+    let patterns = patterns.split(',').map(str::trim).filter(|p| !p.is_empty());
+    crate::ssh::pattern::match_pattern_list(&candidate, patterns, true)
+}
+
+/// Check a host certificate presented by `host:port` against the trusted CAs.
+/// Returns the CA that vouches for it, or a human-readable rejection reason.
+pub fn verify_certificate<'a>(
+    cert: &ssh_key::Certificate,
+    host: &str,
+    port: u16,
+    cas: &'a [TrustedHostCa],
+    now: u64,
+) -> Result<&'a TrustedHostCa, String> {
+    if !cert.cert_type().is_host() {
+        return Err("The server presented a user certificate, not a host certificate.".into());
+    }
+
+    let signer = cert.signature_key().fingerprint(ssh_key::HashAlg::Sha256);
+    let covering = cas_for_host(cas, host, port);
+    let Some(ca) = covering.iter().find(|c| c.fingerprint == signer.to_string()) else {
+        return Err(if covering.is_empty() {
+            format!("The host certificate is signed by {}, but no trusted CA covers {}.", signer, host)
+        } else {
+            format!("The host certificate is signed by {}, which is not a CA trusted for {}.", signer, host)
+        });
+    };
+
+    let principals = cert.valid_principals();
+    if !principals.is_empty() && !principals.iter().any(|p| p.eq_ignore_ascii_case(host)) {
+        return Err(format!(
+            "The host certificate is for {}, not {}.",
+            principals.join(", "),
+            host
+        ));
+    }
+
+    if now < cert.valid_after() {
+        return Err(format!(
+            "The host certificate is not valid until {}.",
+            format_unix_utc(cert.valid_after())
+        ));
+    }
+    if now >= cert.valid_before() {
+        return Err(format!(
+            "The host certificate expired on {}.",
+            format_unix_utc(cert.valid_before())
+        ));
+    }
+
+    // Signature check last: the cheaper checks above give better messages.
+    cert.validate_at(now, [&signer])
+        .map_err(|e| format!("The host certificate failed verification: {}.", e))?;
+
+    Ok(ca)
+}
+
+/// `YYYY-MM-DD HH:MM UTC` without pulling in a date crate.
+pub(crate) fn format_unix_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        (rem % 3_600) / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_follow_openssh_rules() {
+        assert!(host_matches("*.corp.example", "web1.corp.example", 22));
+        assert!(host_matches("*.CORP.example", "Web1.corp.example", 22));
+        assert!(!host_matches("*.corp.example", "corp.example", 22));
+        assert!(host_matches("10.0.?.*", "10.0.3.17", 22));
+        assert!(!host_matches("*.corp.example,!legacy.corp.example", "legacy.corp.example", 22));
+        assert!(host_matches("*.corp.example,!legacy.corp.example", "new.corp.example", 22));
+    }
+
+    #[test]
+    fn non_standard_ports_use_bracket_form() {
+        assert!(!host_matches("gw.corp.example", "gw.corp.example", 2222));
+        assert!(host_matches("[gw.corp.example]:2222", "gw.corp.example", 2222));
+        assert!(host_matches("[*.corp.example]:*", "gw.corp.example", 2222));
+    }
+
+    #[test]
+    fn formats_utc_dates() {
+        assert_eq!(format_unix_utc(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_unix_utc(1_700_000_000), "2023-11-14 22:13 UTC");
+        assert_eq!(format_unix_utc(951_782_400), "2000-02-29 00:00 UTC");
+    }
+}
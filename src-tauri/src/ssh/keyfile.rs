@@ -19,6 +19,9 @@ pub enum KeyFileKind {
     PrivateKey,
     /// An OpenSSH public key (`ssh-ed25519 AAAA... comment`) — not a secret.
     PublicKey,
+    /// An OpenSSH certificate (`ssh-ed25519-cert-v01@openssh.com AAAA...`,
+    /// usually `id_ed25519-cert.pub`) — public, pairs with a private key.
+    Certificate,
     /// The path exists but isn't a recognizable key.
     NotAKey,
     /// No file at the given path.
@@ -54,6 +57,30 @@ pub struct KeyFileInfo {
     /// Other private keys found in the same directory (deduped against the
     /// suggestion and the inspected file).
     pub sibling_private_keys: Vec<KeyCandidate>,
+    /// The certificate itself (`kind == Certificate`) or, for a private key,
+    /// the sibling `<key>-cert.pub` that will be offered alongside it.
+    pub certificate: Option<CertificateInfo>,
+}
+
+/// What the UI shows about an OpenSSH certificate.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateInfo {
+    /// Where the certificate was read from (None for vault-held certificates).
+    pub path: Option<String>,
+    /// `user` or `host`.
+    pub cert_type: String,
+    pub algo: String,
+    pub key_id: String,
+    /// Empty = valid for any principal.
+    pub principals: Vec<String>,
+    /// Unix seconds; `valid_before == u64::MAX` means "forever".
+    pub valid_after: u64,
+    pub valid_before: u64,
+    pub expired: bool,
+    pub not_yet_valid: bool,
+    /// SHA256 fingerprint of the signing CA key.
+    pub ca_fingerprint: String,
 }
 
 /// OpenSSH public-key line prefixes mapped to a friendly algorithm name.
@@ -88,6 +115,7 @@ pub fn classify_path(raw: &str) -> KeyFileInfo {
             encrypted: false,
             suggested_private_key: None,
             sibling_private_keys: siblings,
+            certificate: None,
         };
     }
 
@@ -96,10 +124,24 @@ pub fn classify_path(raw: &str) -> KeyFileInfo {
     let content = read_head(&expanded, 64 * 1024).unwrap_or_default();
     let (kind, algo, comment, encrypted) = sniff(&content);
 
-    let suggested_private_key = if kind == KeyFileKind::PublicKey {
-        suggest_private_for_public(&expanded)
-    } else {
-        None
+    let suggested_private_key = match kind {
+        KeyFileKind::PublicKey => suggest_private_for_public(&expanded),
+        KeyFileKind::Certificate => suggest_private_for_certificate(&expanded),
+        _ => None,
+    };
+
+    let certificate = match kind {
+        KeyFileKind::Certificate => parse_certificate(&content)
+            .ok()
+            .map(|c| certificate_info(&c, Some(path_str.clone()))),
+        KeyFileKind::PrivateKey => {
+            let cert_path = sibling_certificate_path(&expanded);
+            read_head(&cert_path, 64 * 1024)
+                .ok()
+                .and_then(|c| parse_certificate(&c).ok())
+                .map(|c| certificate_info(&c, Some(cert_path.to_string_lossy().into_owned())))
+        }
+        _ => None,
     };
 
     let suggested_path = suggested_private_key.as_ref().map(|c| PathBuf::from(&c.path));
@@ -122,9 +164,54 @@ pub fn classify_path(raw: &str) -> KeyFileInfo {
         encrypted,
         suggested_private_key,
         sibling_private_keys,
+        certificate,
     }
 }
 
+/// Where OpenSSH looks for the certificate of a key: `id_ed25519` →
+/// `id_ed25519-cert.pub`.
+pub fn sibling_certificate_path(key_path: &Path) -> PathBuf {
+    let mut s = key_path.as_os_str().to_owned();
+    s.push("-cert.pub");
+    PathBuf::from(s)
+}
+
+/// Parse an OpenSSH certificate line (`<type>-cert-v01@openssh.com <base64> [comment]`).
+pub fn parse_certificate(content: &str) -> Result<ssh_key::Certificate, String> {
+    let line = content.trim();
+    ssh_key::Certificate::from_openssh(line).map_err(|e| format!("Invalid OpenSSH certificate: {}", e))
+}
+
+/// Summarize a certificate for display, judging validity against the clock now.
+pub fn certificate_info(cert: &ssh_key::Certificate, path: Option<String>) -> CertificateInfo {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    CertificateInfo {
+        path,
+        cert_type: if cert.cert_type().is_host() { "host" } else { "user" }.to_string(),
+        algo: cert.algorithm().as_str().to_string(),
+        key_id: cert.key_id().to_string(),
+        principals: cert.valid_principals().to_vec(),
+        valid_after: cert.valid_after(),
+        valid_before: cert.valid_before(),
+        expired: now >= cert.valid_before(),
+        not_yet_valid: now < cert.valid_after(),
+        ca_fingerprint: cert
+            .signature_key()
+            .fingerprint(ssh_key::HashAlg::Sha256)
+            .to_string(),
+    }
+}
+
+/// Classify in-memory key material (e.g. a key embedded in a shared session)
+/// the same way [`classify_path`] classifies a file. Returns `(kind, algo)`.
+pub fn classify_content(content: &str) -> (KeyFileKind, Option<String>) {
+    let (kind, algo, _, _) = sniff(content);
+    (kind, algo)
+}
+
 fn read_head(path: &Path, max: u64) -> std::io::Result<String> {
     use std::io::Read;
     let f = std::fs::File::open(path)?;
@@ -153,9 +240,38 @@ fn sniff(content: &str) -> (KeyFileKind, Option<String>, Option<String>, bool) {
         return (KeyFileKind::PrivateKey, algo, None, detect_encrypted(trimmed));
     }
 
+    // --- PuTTY private key ("PuTTY-User-Key-File-N: <type>") ---
+    if crate::ssh::ppk::is_ppk(first_line) {
+        if let Ok(file) = crate::ssh::ppk::PpkFile::parse(trimmed) {
+            let algo = PUBLIC_KEY_TYPES
+                .iter()
+                .find(|(prefix, _)| *prefix == file.algorithm)
+                .map(|(_, algo)| (*algo).to_string());
+            let comment = Some(file.comment.clone()).filter(|c| !c.is_empty());
+            return (KeyFileKind::PrivateKey, algo, comment, file.encrypted);
+        }
+        return (KeyFileKind::NotAKey, None, None, false);
+    }
+
     // --- OpenSSH public key (single line: "<type> <base64> [comment]") ---
     let mut parts = first_line.splitn(3, char::is_whitespace);
     let typ = parts.next().unwrap_or("");
+
+    // Certificates share the layout; the type carries a `-cert-v01` suffix.
+    if let Some(base) = typ.strip_suffix("-cert-v01@openssh.com") {
+        let b64 = parts.next().unwrap_or("");
+        if !b64.is_empty() {
+            let algo = PUBLIC_KEY_TYPES
+                .iter()
+                .find(|(prefix, _)| prefix.trim_end_matches("@openssh.com") == base)
+                .map(|(_, algo)| (*algo).to_string());
+            let comment = parts
+                .next()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+            return (KeyFileKind::Certificate, algo, comment, false);
+        }
+    }
     for (prefix, algo) in PUBLIC_KEY_TYPES {
         if typ == *prefix {
             let b64 = parts.next().unwrap_or("");
@@ -233,6 +349,14 @@ fn suggest_private_for_public(pub_path: &Path) -> Option<KeyCandidate> {
     }
 }
 
+/// For a `*-cert.pub` path, return the private key it certifies (path without
+/// `-cert.pub`) when it exists and really is a private key.
+fn suggest_private_for_certificate(cert_path: &Path) -> Option<KeyCandidate> {
+    let s = cert_path.to_string_lossy();
+    let stripped = s.strip_suffix("-cert.pub")?;
+    suggest_private_for_public(Path::new(&format!("{}.pub", stripped)))
+}
+
 /// Scan a directory for files whose content sniffs as a private key.
 fn scan_dir_for_private_keys(dir: &Path, exclude: Option<&Path>) -> Vec<KeyCandidate> {
     let mut out: Vec<KeyCandidate> = Vec::new();
@@ -271,7 +395,6 @@ fn scan_dir_for_private_keys(dir: &Path, exclude: Option<&Path>) -> Vec<KeyCandi
 fn should_skip(name: &str) -> bool {
     let lower = name.to_ascii_lowercase();
     lower.ends_with(".pub")
-        || lower.ends_with(".ppk") // PuTTY format, not russh-loadable
         || lower.ends_with(".crt")
         || lower.ends_with(".csr")
         || lower.ends_with(".pem.pub")
@@ -346,12 +469,42 @@ mod tests {
         assert_eq!(algo.as_deref(), Some("ed25519"));
     }
 
+    #[test]
+    fn detects_putty_private_key() {
+        let (kind, algo, comment, enc) = sniff(
+            "PuTTY-User-Key-File-3: ssh-ed25519\r\nEncryption: aes256-cbc\r\nComment: work\r\nPublic-Lines: 1\r\nAAAA\r\n\
+             Key-Derivation: Argon2id\r\nArgon2-Memory: 8192\r\nArgon2-Passes: 13\r\nArgon2-Parallelism: 1\r\n\
+             Argon2-Salt: 00\r\nPrivate-Lines: 1\r\nAAAA\r\nPrivate-MAC: 00\r\n",
+        );
+        assert_eq!(kind, KeyFileKind::PrivateKey);
+        assert_eq!(algo.as_deref(), Some("ed25519"));
+        assert_eq!(comment.as_deref(), Some("work"));
+        assert!(enc);
+    }
+
     #[test]
     fn random_text_is_not_a_key() {
         let (kind, ..) = sniff("hello world\nthis is not a key at all");
         assert_eq!(kind, KeyFileKind::NotAKey);
     }
 
+    #[test]
+    fn detects_openssh_certificate() {
+        let (kind, algo, comment, _) =
+            sniff("ssh-ed25519-cert-v01@openssh.com AAAAIHNzaC1lZDI1NTE5LWNlcnQ alice@laptop\n");
+        assert_eq!(kind, KeyFileKind::Certificate);
+        assert_eq!(algo.as_deref(), Some("ed25519"));
+        assert_eq!(comment.as_deref(), Some("alice@laptop"));
+    }
+
+    #[test]
+    fn certificate_path_is_key_path_plus_suffix() {
+        assert_eq!(
+            sibling_certificate_path(Path::new("/home/a/.ssh/id_ed25519")),
+            PathBuf::from("/home/a/.ssh/id_ed25519-cert.pub")
+        );
+    }
+
     #[test]
     fn bare_type_without_base64_is_not_public_key() {
         // A lone algorithm token with no key body must not be misread.
@@ -0,0 +1,337 @@
+//! SSH key generation, public key export and `ssh-copy-id`-style deployment.
+//!
+//! New keys are OpenSSH-format Ed25519, ECDSA or RSA keypairs. They either go
+//! straight into a vault as an `ssh_key` secret — the vault encrypts them, so
+//! no passphrase — or are written to disk as `<path>` and `<path>.pub`,
+//! optionally passphrase-protected, with the permissions OpenSSH insists on.
+//! PuTTY keys are imported the same way, converted to OpenSSH format.
+//!
+//! Deployment appends a public key to `~/.ssh/authorized_keys` on an already
+//! connected host unless it is there already (matched on type and key, not
+//! comment), creating `~/.ssh` and tightening its permissions on the way.
+
+use std::path::Path;
+
+use rand::rngs::OsRng;
+use russh_keys::PublicKeyBase64;
+use serde::{Deserialize, Serialize};
+use ssh_key::private::{KeypairData, RsaKeypair};
+use ssh_key::{Algorithm, EcdsaCurve, HashAlg, LineEnding, PrivateKey, PublicKey};
+use zeroize::Zeroizing;
+
+use crate::ssh::client::{RemoteCommand, SharedHandle};
+use crate::ssh::ppk;
+
+/// RSA sizes offered; 3072 is OpenSSH's default.
+const RSA_BITS: &[usize] = &[2048, 3072, 4096];
+const DEFAULT_RSA_BITS: usize = 3072;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAlgorithm {
+    Ed25519,
+    EcdsaP256,
+    EcdsaP384,
+    EcdsaP521,
+    Rsa,
+}
+
+/// A public key as the UI shows and exports it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyInfo {
+    /// e.g. `ssh-ed25519`.
+    pub algorithm: String,
+    /// The `authorized_keys` line: `<type> <base64> [comment]`.
+    pub openssh: String,
+    /// `SHA256:...`
+    pub fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeployOutcome {
+    Added,
+    AlreadyPresent,
+}
+
+fn public_info(key: &PublicKey) -> Result<PublicKeyInfo, String> {
+    Ok(PublicKeyInfo {
+        algorithm: key.algorithm().as_str().to_string(),
+        openssh: key.to_openssh().map_err(|e| e.to_string())?,
+        fingerprint: key.fingerprint(HashAlg::Sha256).to_string(),
+        comment: Some(key.comment().to_string()).filter(|c| !c.is_empty()),
+    })
+}
+
+/// Generate a keypair. Returns the private key in OpenSSH format (encrypted
+/// with `passphrase` when one is given) and its public half.
+pub fn generate(
+    algorithm: KeyAlgorithm,
+    rsa_bits: Option<usize>,
+    comment: &str,
+    passphrase: Option<&str>,
+) -> Result<(Zeroizing<String>, PublicKeyInfo), String> {
+    let mut rng = OsRng;
+    let mut key = match algorithm {
+        KeyAlgorithm::Ed25519 => PrivateKey::random(&mut rng, Algorithm::Ed25519),
+        KeyAlgorithm::EcdsaP256 => PrivateKey::random(&mut rng, Algorithm::Ecdsa { curve: EcdsaCurve::NistP256 }),
+        KeyAlgorithm::EcdsaP384 => PrivateKey::random(&mut rng, Algorithm::Ecdsa { curve: EcdsaCurve::NistP384 }),
+        KeyAlgorithm::EcdsaP521 => PrivateKey::random(&mut rng, Algorithm::Ecdsa { curve: EcdsaCurve::NistP521 }),
+        KeyAlgorithm::Rsa => {
+            let bits = rsa_bits.unwrap_or(DEFAULT_RSA_BITS);
+            if !RSA_BITS.contains(&bits) {
+                return Err(format!("Unsupported RSA key size {} (use 2048, 3072 or 4096)", bits));
+            }
+            RsaKeypair::random(&mut rng, bits).and_then(|pair| PrivateKey::new(KeypairData::Rsa(pair), ""))
+        }
+    }
+    .map_err(|e| format!("Key generation failed: {}", e))?;
+    key.set_comment(comment);
+
+    let public = public_info(key.public_key())?;
+    Ok((protect(key, passphrase)?, public))
+}
+
+/// Convert a PuTTY `.ppk` key to OpenSSH format, re-encrypted with
+/// `new_passphrase` when one is given.
+pub fn import_ppk(
+    content: &str,
+    passphrase: Option<&str>,
+    new_passphrase: Option<&str>,
+) -> Result<(Zeroizing<String>, PublicKeyInfo), String> {
+    let converted = ppk::to_openssh(content, passphrase).map_err(|e| e.to_string())?;
+    let key = PrivateKey::from_openssh(converted.as_str()).map_err(|e| e.to_string())?;
+    let public = public_info(key.public_key())?;
+    Ok((protect(key, new_passphrase)?, public))
+}
+
+fn protect(key: PrivateKey, passphrase: Option<&str>) -> Result<Zeroizing<String>, String> {
+    let key = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => key
+            .encrypt(&mut OsRng, passphrase)
+            .map_err(|e| format!("Could not encrypt the key: {}", e))?,
+        None => key,
+    };
+    key.to_openssh(LineEnding::LF).map_err(|e| e.to_string())
+}
+
+/// Write a generated pair to `path` and `path.pub`. Never overwrites: an
+/// existing key at either path is an error.
+pub fn write_key_pair(path: &Path, private: &str, public: &PublicKeyInfo) -> Result<(), String> {
+    let public_path = path.with_file_name(format!(
+        "{}.pub",
+        path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+    ));
+    for existing in [path, public_path.as_path()] {
+        if existing.exists() {
+            return Err(format!("{} already exists", existing.display()));
+        }
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700));
+            }
+        }
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    {
+        use std::io::Write;
+        let mut file = options
+            .open(path)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        file.write_all(private.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&public_path, format!("{}\n", public.openssh))
+        .map_err(|e| format!("Cannot write {}: {}", public_path.display(), e))
+}
+
+/// The public half of a private key — OpenSSH, PuTTY, PEM or PKCS#8, decrypted with
+/// `passphrase` if needed. An OpenSSH public key line is accepted as-is.
+pub fn public_key_of(material: &str, passphrase: Option<&str>) -> Result<PublicKeyInfo, String> {
+    let text = material.trim();
+    if let Ok(public) = PublicKey::from_openssh(text) {
+        return public_info(&public);
+    }
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+
+    // PuTTY files carry the public half in clear.
+    if ppk::is_ppk(text) {
+        let file = ppk::PpkFile::parse(text).map_err(|e| e.to_string())?;
+        let mut public = PublicKey::from_bytes(file.public_blob()).map_err(|e| e.to_string())?;
+        public.set_comment(file.comment.as_str());
+        return public_info(&public);
+    }
+
+    // OpenSSH containers keep the comment, so prefer ssh-key for them.
+    if let Ok(key) = PrivateKey::from_openssh(text) {
+        let key = match (key.is_encrypted(), passphrase) {
+            (false, _) => key,
+            (true, Some(passphrase)) => key
+                .decrypt(passphrase)
+                .map_err(|_| "Wrong passphrase for this key".to_string())?,
+            (true, None) => return Err("This key is passphrase-protected".into()),
+        };
+        return public_info(key.public_key());
+    }
+
+    let mut normalized = Zeroizing::new(text.replace("\r\n", "\n"));
+    normalized.push('\n');
+    let pair = russh_keys::decode_secret_key(&normalized, passphrase).map_err(|e| match e {
+        russh_keys::Error::KeyIsEncrypted => "This key is passphrase-protected".to_string(),
+        e => format!("Not a readable private key: {}", e),
+    })?;
+    let blob = pair.clone_public_key().map_err(|e| e.to_string())?.public_key_bytes();
+    public_info(&PublicKey::from_bytes(&blob).map_err(|e| e.to_string())?)
+}
+
+fn shell_escape(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// The POSIX `sh` script that installs `line`, keyed on `needle` (type and
+/// base64, without the comment). Prints `present` or `added`. A commented-out
+/// copy doesn't count: that's how admins revoke a key.
+fn deploy_script(line: &str, needle: &str) -> String {
+    let line = shell_escape(line);
+    let needle = shell_escape(needle);
+    format!(
+        r#"umask 077
+mkdir -p "$HOME/.ssh" || exit 1
+chmod 700 "$HOME/.ssh"
+f="$HOME/.ssh/authorized_keys"
+touch "$f" || exit 1
+chmod 600 "$f"
+if grep -v '^[[:space:]]*#' "$f" | grep -qF {needle}; then echo present; exit 0; fi
+if [ -s "$f" ] && [ -n "$(tail -c1 "$f")" ]; then echo >> "$f"; fi
+printf '%s\n' {line} >> "$f" || exit 1
+command -v restorecon >/dev/null 2>&1 && restorecon -F "$HOME/.ssh" "$f" >/dev/null 2>&1
+echo added"#
+    )
+}
+
+/// Add `public_key` to `~/.ssh/authorized_keys` on the connected host. The
+/// script goes to `sh` on stdin, whatever the login shell; POSIX hosts only.
+pub async fn deploy(handle: &SharedHandle, public_key: &str) -> Result<DeployOutcome, String> {
+    let key = PublicKey::from_openssh(public_key.trim()).map_err(|e| format!("Invalid public key: {}", e))?;
+    let line = key.to_openssh().map_err(|e| e.to_string())?;
+    let needle = line.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+
+    let output = RemoteCommand::new("sh")
+        .stdin(deploy_script(&line, &needle).into_bytes())
+        .idle_timeout(Some(std::time::Duration::from_secs(60)))
+        .run(handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let stdout = output.stdout_lossy();
+    match stdout.lines().last().map(str::trim) {
+        Some("added") if output.success() => Ok(DeployOutcome::Added),
+        Some("present") if output.success() => Ok(DeployOutcome::AlreadyPresent),
+        _ => Err(format!(
+            "Could not update authorized_keys (exit {}): {}",
+            output.exit_code_or_default(),
+            output.stderr_lossy().trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_round_trip() {
+        let (private, public) = generate(KeyAlgorithm::Ed25519, None, "me@laptop", None).unwrap();
+        assert_eq!(public.algorithm, "ssh-ed25519");
+        assert!(public.openssh.ends_with(" me@laptop"));
+        assert!(public.fingerprint.starts_with("SHA256:"));
+        assert_eq!(public_key_of(&private, None).unwrap().openssh, public.openssh);
+    }
+
+    #[test]
+    fn passphrase_protects_the_private_key() {
+        let (private, public) = generate(KeyAlgorithm::EcdsaP256, None, "", Some("hunter2")).unwrap();
+        assert!(public_key_of(&private, None).is_err());
+        assert!(public_key_of(&private, Some("wrong")).is_err());
+        assert_eq!(
+            public_key_of(&private, Some("hunter2")).unwrap().fingerprint,
+            public.fingerprint
+        );
+    }
+
+    #[test]
+    fn rejects_odd_rsa_sizes() {
+        assert!(generate(KeyAlgorithm::Rsa, Some(1024), "", None).is_err());
+    }
+
+    #[test]
+    fn public_key_lines_pass_through() {
+        let (_, public) = generate(KeyAlgorithm::Ed25519, None, "x", None).unwrap();
+        assert_eq!(public_key_of(&public.openssh, None).unwrap().openssh, public.openssh);
+    }
+
+    #[test]
+    fn writes_private_and_public_files() {
+        let dir = std::env::temp_dir().join(format!("reach-keygen-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("id_test");
+        let (private, public) = generate(KeyAlgorithm::Ed25519, None, "t", None).unwrap();
+        write_key_pair(&path, &private, &public).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("id_test.pub")).unwrap().trim(),
+            public.openssh
+        );
+        assert!(write_key_pair(&path, &private, &public).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn deploy_script_quotes_the_comment() {
+        let script = deploy_script("ssh-ed25519 AAAA it's me", "ssh-ed25519 AAAA");
+        assert!(script.contains(r#"printf '%s\n' 'ssh-ed25519 AAAA it'\''s me'"#));
+        assert!(script.contains("grep -qF 'ssh-ed25519 AAAA'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deploy_script_ignores_commented_out_keys() {
+        let home = std::env::temp_dir().join(format!("reach-deploy-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(home.join(".ssh")).unwrap();
+        let keys = home.join(".ssh/authorized_keys");
+        std::fs::write(&keys, "  # ssh-ed25519 AAAA revoked\n").unwrap();
+        let run = || {
+            let out = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(deploy_script("ssh-ed25519 AAAA me", "ssh-ed25519 AAAA"))
+                .env("HOME", &home)
+                .output()
+                .unwrap();
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+        assert_eq!(run(), "added");
+        assert_eq!(run(), "present");
+        assert_eq!(
+            std::fs::read_to_string(&keys).unwrap(),
+            "  # ssh-ed25519 AAAA revoked\nssh-ed25519 AAAA me\n"
+        );
+        let _ = std::fs::remove_dir_all(&home);
+    }
+}
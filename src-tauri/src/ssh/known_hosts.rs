@@ -0,0 +1,572 @@
+//! Pinned host keys — Reach's `known_hosts`.
+//!
+//! Entries use OpenSSH's host-field syntax so they round-trip with
+//! `~/.ssh/known_hosts`: `host` for port 22, `[host]:port` otherwise, pattern
+//! lists (`web*.corp.example,10.0.0.5`) and hashed `|1|salt|hash` names as
+//! imported. Each entry keeps the key type and full public key blob, not just
+//! the fingerprint, so a server that suddenly offers a weaker algorithm than
+//! the one we pinned can be called out as a downgrade.
+//!
+//! Stored as JSON in the app data dir. The original fingerprint-only format
+//! (`{"entries": {"host:port": "<fingerprint>"}}`) is migrated on load; those
+//! entries gain their key type and blob the next time the host is seen.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHostEntry {
+    pub id: String,
+    /// OpenSSH host field: `host`, `[host]:port`, a pattern list or `|1|salt|hash`.
+    pub hosts: String,
+    /// Key algorithm from the blob (`ssh-ed25519`, `ssh-rsa`, ...). `None` for
+    /// entries migrated from the fingerprint-only store and not seen since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<String>,
+    /// Base64 public key blob, as in a known_hosts line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Unprefixed base64 SHA-256 of the blob (russh's `fingerprint()` format).
+    pub fingerprint: String,
+    /// An `@revoked` key: never accepted, for any host it matches.
+    #[serde(default)]
+    pub revoked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl KnownHostEntry {
+    pub fn is_hashed(&self) -> bool {
+        self.hosts.starts_with("|1|")
+    }
+
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        if self.is_hashed() {
+            hashed_name_matches(&self.hosts, &host_field(host, port))
+        } else {
+            crate::ssh::host_ca::host_matches(&self.hosts, host, port)
+        }
+    }
+
+    /// The entry as a known_hosts line, or `None` when we only have a fingerprint.
+    fn to_line(&self) -> Option<String> {
+        let (key_type, public_key) = (self.key_type.as_deref()?, self.public_key.as_deref()?);
+        let mut line = String::new();
+        if self.revoked {
+            line.push_str("@revoked ");
+        }
+        line.push_str(&format!("{} {} {}", self.hosts, key_type, public_key));
+        if let Some(comment) = &self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        Some(line)
+    }
+}
+
+/// Outcome of checking a server's key against the pinned entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    Trusted,
+    /// The key is marked `@revoked` — refuse without prompting.
+    Revoked,
+    /// Nothing pinned for this key type. `note` is set when other key types
+    /// are pinned for the host (the server offered a different algorithm).
+    Unknown { note: Option<String> },
+    /// A different key of the same type is pinned, or the server offered a
+    /// weaker algorithm than one we have pinned (`note` says which).
+    Changed { old_fingerprint: String, note: Option<String> },
+}
+
+/// Counts reported by [`KnownHosts::import`].
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub revoked: usize,
+    pub cert_authorities: usize,
+    pub duplicates: usize,
+    /// Lines we couldn't parse (unknown marker, bad base64, type mismatch).
+    pub skipped: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KnownHosts {
+    #[serde(default)]
+    hosts: Vec<KnownHostEntry>,
+    /// Legacy `host:port -> fingerprint` map; read for migration only.
+    #[serde(default, skip_serializing)]
+    entries: HashMap<String, String>,
+}
+
+fn store_path() -> PathBuf {
+    // Use the Tauri-resolved writable app data dir (not `dirs::data_dir()`)
+    // so this works inside the Android/iOS sandbox too.
+    crate::app_data_dir().join("ssh").join("known_hosts.json")
+}
+
+impl KnownHosts {
+    /// Load the store, migrating the fingerprint-only format if needed. A
+    /// missing or unreadable store means "nothing pinned".
+    pub fn load() -> Self {
+        let mut known: KnownHosts = std::fs::read_to_string(store_path())
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        if !known.entries.is_empty() {
+            known.migrate_legacy();
+            if let Err(e) = known.save() {
+                tracing::warn!("Failed to save migrated known hosts: {}", e);
+            }
+        }
+        known
+    }
+
+    fn migrate_legacy(&mut self) {
+        for (host_id, fingerprint) in std::mem::take(&mut self.entries) {
+            let Some((host, port)) = host_id
+                .rsplit_once(':')
+                .and_then(|(h, p)| Some((h, p.parse::<u16>().ok()?)))
+            else {
+                continue;
+            };
+            self.hosts.push(KnownHostEntry {
+                id: uuid::Uuid::new_v4().to_string(),
+                hosts: host_field(host, port),
+                key_type: None,
+                public_key: None,
+                fingerprint,
+                revoked: false,
+                comment: None,
+            });
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = store_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let raw = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, raw).map_err(|e| e.to_string())
+    }
+
+    pub fn entries(&self) -> &[KnownHostEntry] {
+        &self.hosts
+    }
+
+    /// Check the key a server presented. A fingerprint-only entry that matches
+    /// is upgraded in place with the type and blob; returns whether anything
+    /// changed that should be saved.
+    pub fn check(&mut self, host: &str, port: u16, key_type: &str, public_key: &str) -> (HostKeyStatus, bool) {
+        let fingerprint = fingerprint_of(public_key);
+
+        if self
+            .hosts
+            .iter()
+            .any(|e| e.revoked && e.fingerprint == fingerprint && e.matches(host, port))
+        {
+            return (HostKeyStatus::Revoked, false);
+        }
+
+        let mut pinned: Vec<&mut KnownHostEntry> =
+            self.hosts.iter_mut().filter(|e| !e.revoked && e.matches(host, port)).collect();
+        if let Some(entry) = pinned.iter_mut().find(|e| e.fingerprint == fingerprint) {
+            let upgraded = entry.public_key.is_none();
+            if upgraded {
+                entry.key_type = Some(key_type.to_string());
+                entry.public_key = Some(public_key.to_string());
+            }
+            return (HostKeyStatus::Trusted, upgraded);
+        }
+        if pinned.is_empty() {
+            return (HostKeyStatus::Unknown { note: None }, false);
+        }
+
+        // Same algorithm (or unknown, for migrated entries): the key changed.
+        if let Some(old) = pinned
+            .iter()
+            .find(|e| e.key_type.is_none() || e.key_type.as_deref() == Some(key_type))
+        {
+            return (
+                HostKeyStatus::Changed { old_fingerprint: old.fingerprint.clone(), note: None },
+                false,
+            );
+        }
+
+        let strongest = pinned
+            .iter()
+            .filter_map(|e| e.key_type.as_deref().map(|t| (t, e.fingerprint.as_str())))
+            .max_by_key(|(t, _)| algorithm_rank(t));
+        match strongest {
+            Some((pinned_type, old)) if algorithm_rank(pinned_type) > algorithm_rank(key_type) => (
+                HostKeyStatus::Changed {
+                    old_fingerprint: old.to_string(),
+                    note: Some(format!(
+                        "Algorithm downgrade: a {} key is pinned for this host, but the server offered {}.",
+                        pinned_type, key_type
+                    )),
+                },
+                false,
+            ),
+            _ => {
+                let types: Vec<&str> = pinned.iter().filter_map(|e| e.key_type.as_deref()).collect();
+                (
+                    HostKeyStatus::Unknown {
+                        note: Some(format!(
+                            "A {} key is already pinned for this host; the server offered a {} key instead.",
+                            types.join(" / "),
+                            key_type
+                        )),
+                    },
+                    false,
+                )
+            }
+        }
+    }
+
+    /// Pin `public_key` for `host:port`, replacing any pinned key of the same
+    /// type (or of unknown type) stored for exactly this host. Entries that
+    /// only cover it (multi-host, wildcard or hashed lines) stay for the other
+    /// hosts they name; the new entry takes precedence for this one. Keys of
+    /// other types are kept, so accepting a different algorithm doesn't lose
+    /// the stronger one.
+    pub fn pin(&mut self, host: &str, port: u16, key_type: &str, public_key: &str) -> String {
+        let field = host_field(host, port);
+        self.hosts.retain(|e| {
+            e.revoked
+                || e.hosts != field
+                || e.key_type.as_deref().is_some_and(|t| t != key_type)
+        });
+        let fingerprint = fingerprint_of(public_key);
+        self.hosts.push(KnownHostEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            hosts: field,
+            key_type: Some(key_type.to_string()),
+            public_key: Some(public_key.to_string()),
+            fingerprint: fingerprint.clone(),
+            revoked: false,
+            comment: None,
+        });
+        fingerprint
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<(), String> {
+        let before = self.hosts.len();
+        self.hosts.retain(|e| e.id != id);
+        if self.hosts.len() == before {
+            return Err(format!("Known host entry not found: {}", id));
+        }
+        Ok(())
+    }
+
+    /// Merge OpenSSH known_hosts `content`. `@cert-authority` lines are added
+    /// to the trusted host CAs; everything else becomes a pinned entry.
+    pub fn import(&mut self, content: &str) -> ImportSummary {
+        let mut summary = ImportSummary::default();
+        let mut cas = crate::ssh::host_ca::load();
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some(parsed) = parse_line(line) else {
+                summary.skipped += 1;
+                continue;
+            };
+
+            match parsed.marker {
+                Some(Marker::CertAuthority) => {
+                    let openssh = format!("{} {}", parsed.key_type, parsed.public_key);
+                    let exists = cas.iter().any(|c| {
+                        c.patterns == parsed.hosts
+                            && c.public_key.split_whitespace().nth(1) == Some(parsed.public_key.as_str())
+                    });
+                    if exists {
+                        summary.duplicates += 1;
+                        continue;
+                    }
+                    match crate::ssh::host_ca::add(&parsed.hosts, &openssh, parsed.comment.clone()) {
+                        Ok(ca) => {
+                            cas.push(ca);
+                            summary.cert_authorities += 1;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Skipping @cert-authority line: {}", e);
+                            summary.skipped += 1;
+                        }
+                    }
+                }
+                marker => {
+                    let revoked = marker == Some(Marker::Revoked);
+                    let fingerprint = fingerprint_of(&parsed.public_key);
+                    if self
+                        .hosts
+                        .iter()
+                        .any(|e| e.hosts == parsed.hosts && e.fingerprint == fingerprint && e.revoked == revoked)
+                    {
+                        summary.duplicates += 1;
+                        continue;
+                    }
+                    self.hosts.push(KnownHostEntry {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        hosts: parsed.hosts,
+                        key_type: Some(parsed.key_type),
+                        public_key: Some(parsed.public_key),
+                        fingerprint,
+                        revoked,
+                        comment: parsed.comment,
+                    });
+                    if revoked {
+                        summary.revoked += 1;
+                    } else {
+                        summary.imported += 1;
+                    }
+                }
+            }
+        }
+        summary
+    }
+
+    /// Render everything as an OpenSSH known_hosts file, trusted host CAs
+    /// included. Fingerprint-only entries can't be expressed and are left out.
+    pub fn export(&self) -> String {
+        let mut out = String::new();
+        for ca in crate::ssh::host_ca::load() {
+            out.push_str(&format!("@cert-authority {} {}\n", ca.patterns, ca.public_key));
+        }
+        for line in self.hosts.iter().filter_map(KnownHostEntry::to_line) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Default location of the user's OpenSSH known_hosts.
+pub fn openssh_known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".ssh").join("known_hosts"))
+}
+
+pub fn read_known_hosts_file(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// The known_hosts host field for one host: bare for port 22, else `[host]:port`.
+pub fn host_field(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Unprefixed base64 SHA-256 of a base64 key blob — the same string russh's
+/// `PublicKey::fingerprint()` produces, so both can be compared directly.
+pub fn fingerprint_of(public_key_b64: &str) -> String {
+    let blob = STANDARD.decode(public_key_b64).unwrap_or_default();
+    STANDARD_NO_PAD.encode(Sha256::digest(&blob))
+}
+
+/// The algorithm name embedded at the start of a key blob.
+pub fn blob_key_type(public_key_b64: &str) -> Option<String> {
+    let blob = STANDARD.decode(public_key_b64).ok()?;
+    let len = u32::from_be_bytes(blob.get(..4)?.try_into().ok()?) as usize;
+    let name = blob.get(4..4 + len)?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+/// Find `<type> <base64>` in a public key string — a bare `.pub` line or an
+/// `ssh-keyscan` line with the host in front. Returns the type and blob.
+pub fn parse_public_key(input: &str) -> Result<(String, String), String> {
+    let tokens: Vec<&str> = input.split_whitespace().collect();
+    tokens
+        .windows(2)
+        .find_map(|pair| {
+            let key_type = blob_key_type(pair[1])?;
+            (key_type == pair[0]).then(|| (key_type, pair[1].to_string()))
+        })
+        .ok_or_else(|| "Not an OpenSSH public key (expected e.g. ssh-ed25519 AAAA...)".to_string())
+}
+
+/// Rough strength ordering used for downgrade detection.
+fn algorithm_rank(key_type: &str) -> u8 {
+    match key_type {
+        t if t.contains("ed25519") || t.contains("ed448") => 4,
+        t if t.starts_with("ecdsa-") || t.starts_with("sk-ecdsa-") => 3,
+        "ssh-rsa" => 2,
+        "ssh-dss" => 1,
+        _ => 0,
+    }
+}
+
+/// `|1|salt|hash` matches `name` when HMAC-SHA1(salt, name) equals hash.
+fn hashed_name_matches(field: &str, name: &str) -> bool {
+    let mut parts = field.trim_start_matches("|1|").splitn(2, '|');
+    let (Some(salt), Some(hash)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+    mac.update(name.to_ascii_lowercase().as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    Revoked,
+    CertAuthority,
+}
+
+struct ParsedLine {
+    marker: Option<Marker>,
+    hosts: String,
+    key_type: String,
+    public_key: String,
+    comment: Option<String>,
+}
+
+fn parse_line(line: &str) -> Option<ParsedLine> {
+    let mut fields = line.split_whitespace();
+    let mut first = fields.next()?;
+    let marker = match first {
+        "@revoked" => Some(Marker::Revoked),
+        "@cert-authority" => Some(Marker::CertAuthority),
+        m if m.starts_with('@') => return None,
+        _ => None,
+    };
+    if marker.is_some() {
+        first = fields.next()?;
+    }
+    let key_type = fields.next()?;
+    let public_key = fields.next()?;
+    if blob_key_type(public_key).as_deref() != Some(key_type) {
+        return None;
+    }
+    let comment = fields.collect::<Vec<_>>().join(" ");
+    Some(ParsedLine {
+        marker,
+        hosts: first.to_string(),
+        key_type: key_type.to_string(),
+        public_key: public_key.to_string(),
+        comment: Some(comment).filter(|c| !c.is_empty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ssh-ed25519 blob with key bytes 0..32.
+    const ED25519: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f";
+    const ED25519_FP: &str = "ZkAslGjFiUHdGf/WUL8rQvkib4PTvQatUV0OUQSncCA";
+
+    fn store(lines: &str) -> KnownHosts {
+        let hosts = lines
+            .lines()
+            .filter_map(parse_line)
+            .map(|p| KnownHostEntry {
+                id: uuid::Uuid::new_v4().to_string(),
+                fingerprint: fingerprint_of(&p.public_key),
+                hosts: p.hosts,
+                key_type: Some(p.key_type),
+                public_key: Some(p.public_key),
+                revoked: p.marker == Some(Marker::Revoked),
+                comment: p.comment,
+            })
+            .collect();
+        KnownHosts { hosts, ..Default::default() }
+    }
+
+    #[test]
+    fn fingerprint_matches_russh_format() {
+        assert_eq!(fingerprint_of(ED25519), ED25519_FP);
+        assert_eq!(blob_key_type(ED25519).as_deref(), Some("ssh-ed25519"));
+    }
+
+    #[test]
+    fn hashed_names_match_host_and_bracketed_port() {
+        let salt = "AAECAwQFBgcICQoLDA0ODxAREhM=";
+        assert!(hashed_name_matches(&format!("|1|{}|nnUK16ANsXd3hL31YfAkGOluSjU=", salt), "Example.com"));
+        assert!(hashed_name_matches(&format!("|1|{}|Wgcx+Fm+LmaWwC7rQ80eIf2uHe0=", salt), "[example.com]:2222"));
+        assert!(!hashed_name_matches(&format!("|1|{}|nnUK16ANsXd3hL31YfAkGOluSjU=", salt), "example.org"));
+    }
+
+    #[test]
+    fn parses_markers_and_rejects_mismatched_types() {
+        let line = parse_line(&format!("@revoked * ssh-ed25519 {} old key", ED25519)).unwrap();
+        assert_eq!(line.marker, Some(Marker::Revoked));
+        assert_eq!(line.comment.as_deref(), Some("old key"));
+        assert!(parse_line(&format!("host ssh-rsa {}", ED25519)).is_none());
+        assert!(parse_line(&format!("@unknown host ssh-ed25519 {}", ED25519)).is_none());
+    }
+
+    #[test]
+    fn check_distinguishes_trusted_revoked_and_unknown() {
+        let mut known = store(&format!("[gw.example]:2222 ssh-ed25519 {}", ED25519));
+        assert_eq!(known.check("gw.example", 2222, "ssh-ed25519", ED25519).0, HostKeyStatus::Trusted);
+        assert_eq!(
+            known.check("gw.example", 22, "ssh-ed25519", ED25519).0,
+            HostKeyStatus::Unknown { note: None }
+        );
+
+        let mut revoked = store(&format!("@revoked * ssh-ed25519 {}", ED25519));
+        assert_eq!(revoked.check("any.example", 22, "ssh-ed25519", ED25519).0, HostKeyStatus::Revoked);
+    }
+
+    #[test]
+    fn weaker_algorithm_is_reported_as_downgrade() {
+        // Any well-formed ssh-rsa blob will do for the type check.
+        let rsa = STANDARD.encode(b"\x00\x00\x00\x07ssh-rsa\x00\x00\x00\x01\x03\x00\x00\x00\x01\x01");
+        let mut known = store(&format!("host.example ssh-ed25519 {}", ED25519));
+        match known.check("host.example", 22, "ssh-rsa", &rsa).0 {
+            HostKeyStatus::Changed { old_fingerprint, note } => {
+                assert_eq!(old_fingerprint, ED25519_FP);
+                assert!(note.unwrap().contains("downgrade"));
+            }
+            other => panic!("expected downgrade, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pinning_keeps_lines_shared_with_other_hosts() {
+        let mut key = b"\x00\x00\x00\x0bssh-ed25519\x00\x00\x00\x20".to_vec();
+        key.extend([7u8; 32]);
+        let new_key = STANDARD.encode(key);
+        let mut known = store(&format!(
+            "a.example,b.example ssh-ed25519 {0}\na.example ssh-ed25519 {0}",
+            ED25519
+        ));
+
+        known.pin("a.example", 22, "ssh-ed25519", &new_key);
+        assert_eq!(known.hosts.len(), 2);
+        assert_eq!(known.hosts[0].hosts, "a.example,b.example");
+        assert_eq!(known.check("a.example", 22, "ssh-ed25519", &new_key).0, HostKeyStatus::Trusted);
+        assert_eq!(known.check("b.example", 22, "ssh-ed25519", ED25519).0, HostKeyStatus::Trusted);
+    }
+
+    #[test]
+    fn migrated_entries_upgrade_on_first_match() {
+        let mut known = KnownHosts::default();
+        known.entries.insert("10.0.0.5:2200".into(), ED25519_FP.into());
+        known.migrate_legacy();
+        assert_eq!(known.hosts[0].hosts, "[10.0.0.5]:2200");
+
+        let (status, upgraded) = known.check("10.0.0.5", 2200, "ssh-ed25519", ED25519);
+        assert_eq!(status, HostKeyStatus::Trusted);
+        assert!(upgraded);
+        assert_eq!(known.hosts[0].public_key.as_deref(), Some(ED25519));
+    }
+}
@@ -0,0 +1,163 @@
+//! Whether a server is still talking to us, for the `stalled` connection
+//! state. The transport's read side is wrapped in an [`ActivityStream`] that
+//! notes when bytes last arrived; russh sends `keepalive@openssh.com` once
+//! the link has been quiet for a keepalive interval, so a healthy server is
+//! never silent for much longer than that. A link quiet for two intervals
+//! has left a keepalive unanswered.
+//!
+//! Giving up on the link is russh's job (`keepalive_max` in the client
+//! config): it ends the session, which closes every channel on it. This only
+//! reports the stall before that, and never touches the session handle.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::watch;
+
+/// Inbound activity on one SSH transport, and whether it currently looks
+/// stalled. Every shell on the transport subscribes to the latter.
+#[derive(Debug, Clone)]
+pub(crate) struct Activity(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    start: Instant,
+    /// Milliseconds after `start` that bytes last arrived.
+    last_read: AtomicU64,
+    stalled: watch::Sender<bool>,
+}
+
+impl Activity {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Inner {
+            start: Instant::now(),
+            last_read: AtomicU64::new(0),
+            stalled: watch::Sender::new(false),
+        }))
+    }
+
+    fn touch(&self) {
+        let now = self.0.start.elapsed().as_millis() as u64;
+        self.0.last_read.fetch_max(now, Ordering::Relaxed);
+    }
+
+    /// How long the server has said nothing.
+    pub(crate) fn quiet_for(&self) -> Duration {
+        let last = Duration::from_millis(self.0.last_read.load(Ordering::Relaxed));
+        self.0.start.elapsed().saturating_sub(last)
+    }
+
+    /// Changes to whether the transport is stalled.
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.stalled.subscribe()
+    }
+}
+
+/// Whether a link quiet for `quiet` has left a keepalive unanswered.
+pub(crate) fn is_stalled(quiet: Duration, interval: Duration) -> bool {
+    quiet > interval * 2
+}
+
+/// Re-assess the transport every keepalive interval while any shell is
+/// listening. One per transport, however many shells share it.
+pub(crate) async fn watchdog(activity: Activity, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = activity.0.stalled.closed() => break,
+        }
+        let stalled = is_stalled(activity.quiet_for(), interval);
+        activity.0.stalled.send_if_modified(|current| std::mem::replace(current, stalled) != stalled);
+    }
+}
+
+/// A transport that records when it last read anything.
+pub(crate) struct ActivityStream<S> {
+    inner: S,
+    activity: Activity,
+}
+
+impl<S> ActivityStream<S> {
+    pub(crate) fn new(inner: S, activity: Activity) -> Self {
+        activity.touch();
+        Self { inner, activity }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ActivityStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if matches!(poll, Poll::Ready(Ok(()))) && buf.filled().len() > before {
+            this.activity.touch();
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ActivityStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn stalled_after_an_unanswered_keepalive() {
+        let interval = Duration::from_secs(15);
+        assert!(!is_stalled(Duration::from_secs(20), interval));
+        assert!(!is_stalled(Duration::from_secs(30), interval));
+        assert!(is_stalled(Duration::from_secs(31), interval));
+    }
+
+    #[tokio::test]
+    async fn reads_count_as_activity_and_writes_do_not() {
+        let (near, mut far) = tokio::io::duplex(64);
+        let activity = Activity::new();
+        let mut stream = ActivityStream::new(near, activity.clone());
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(b"ping").await.unwrap();
+        assert!(activity.quiet_for() >= Duration::from_millis(50));
+
+        far.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert!(activity.quiet_for() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn watchdog_reports_stall_and_recovery() {
+        let interval = Duration::from_millis(20);
+        let activity = Activity::new();
+        let mut stalled = activity.subscribe();
+        tokio::spawn(watchdog(activity.clone(), interval));
+
+        stalled.changed().await.unwrap();
+        assert!(*stalled.borrow_and_update());
+        assert!(activity.quiet_for() > interval * 2);
+
+        activity.touch();
+        stalled.changed().await.unwrap();
+        assert!(!*stalled.borrow_and_update());
+    }
+}
@@ -1,3 +1,17 @@
 pub mod client;
 pub mod config;
+pub mod config_export;
 pub mod keyfile;
+pub mod totp;
+pub mod host_ca;
+pub mod known_hosts;
+pub mod liveness;
+pub mod pattern;
+pub mod reconnect;
+pub mod algorithms;
+pub mod agent_forward;
+pub mod vault_agent;
+pub mod keygen;
+pub mod ppk;
+pub mod proxy_command;
+pub mod scrollback;
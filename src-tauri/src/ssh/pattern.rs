@@ -0,0 +1,71 @@
+//! OpenSSH host pattern matching, shared by ssh_config `Host`/`Match`
+//! sections and `@cert-authority` host lists so the two can't drift apart.
+
+/// OpenSSH's `match_pattern_list`: a matching negated pattern rules the value
+/// out, otherwise any matching pattern lets it in.
+pub(crate) fn match_pattern_list<'a>(value: &str, patterns: impl IntoIterator<Item = &'a str>, fold_case: bool) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, pattern),
+        };
+        if wildcard_match(pattern, value, fold_case) {
+            if negated {
+                return false;
+            }
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// Glob match with `*` (any run) and `?` (one character).
+pub(crate) fn wildcard_match(pattern: &str, text: &str, fold_case: bool) -> bool {
+    let fold = |s: &str| -> Vec<char> {
+        if fold_case { s.to_lowercase().chars().collect() } else { s.chars().collect() }
+    };
+    let (p, t) = (fold(pattern), fold(text));
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star_pi, star_ti)) = star {
+            pi = star_pi + 1;
+            ti = star_ti + 1;
+            star = Some((star_pi, star_ti + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_matching() {
+        assert!(wildcard_match("*.example.com", "WWW.example.com", true));
+        assert!(!wildcard_match("*.example.com", "WWW.example.COM", false));
+        assert!(wildcard_match("web-??", "web-01", false));
+        assert!(!wildcard_match("web-??", "web-1", false));
+        assert!(wildcard_match("a*b*c", "aXXbYYc", false));
+        assert!(!wildcard_match("a*b*c", "aXXbYY", false));
+    }
+
+    #[test]
+    fn negated_patterns_veto() {
+        let patterns = ["*.corp.example", "!legacy.corp.example"];
+        assert!(match_pattern_list("web.corp.example", patterns, false));
+        assert!(!match_pattern_list("legacy.corp.example", patterns, false));
+        assert!(!match_pattern_list("legacy.corp.example", ["!legacy.corp.example"], false));
+        assert!(!match_pattern_list("other.example", patterns, false));
+    }
+}
@@ -0,0 +1,201 @@
+//! `ProxyCommand` transport: the SSH connection runs over the stdin/stdout of
+//! a local process (`cloudflared access ssh --hostname %h`, `nc -X connect -x
+//! proxy:3128 %h %p`, ...), exactly like OpenSSH's option of the same name.
+//!
+//! The command line goes through the platform shell, as OpenSSH does, after
+//! `%h`, `%p`, `%r` and `%%` are expanded. The process is killed when the
+//! stream is dropped; its stderr is logged.
+
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader, ReadBuf};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+/// Expand `%h` (host), `%p` (port), `%r` (remote user) and `%%` in a
+/// `ProxyCommand` line. Other `%` sequences are kept as written.
+///
+/// The host and user are substituted unquoted, so — like OpenSSH — values
+/// with shell metacharacters are refused rather than handed to the shell.
+pub fn expand_tokens(command: &str, host: &str, port: u16, user: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(command.len() + host.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => out.push_str(shell_safe(host, "host name")?),
+            Some('p') => out.push_str(&port.to_string()),
+            Some('r') => out.push_str(shell_safe(user, "user name")?),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    Ok(out)
+}
+
+fn shell_safe<'a>(value: &'a str, what: &str) -> Result<&'a str, String> {
+    if is_shell_safe(value) {
+        Ok(value)
+    } else {
+        Err(format!("ProxyCommand: refusing to substitute {} '{}'", what, value))
+    }
+}
+
+/// Whether `value` can go into a shell command line unquoted: a host or user
+/// name made of nothing the shell would interpret.
+pub(crate) fn is_shell_safe(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._:@[]+".contains(c) || !c.is_ascii())
+}
+
+/// A running proxy command, used as the SSH transport.
+pub struct ProxyCommandStream {
+    // Held so `kill_on_drop` ends the process with the stream.
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl ProxyCommandStream {
+    /// Start `command` (already expanded) through the shell.
+    pub fn spawn(command: &str) -> std::io::Result<Self> {
+        #[cfg(unix)]
+        let mut cmd = {
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+            let mut cmd = tokio::process::Command::new(shell);
+            cmd.arg("-c").arg(format!("exec {}", command));
+            cmd
+        };
+        #[cfg(windows)]
+        let mut cmd = {
+            use std::os::windows::process::CommandExt;
+            let mut cmd = tokio::process::Command::new("cmd");
+            cmd.arg("/C").raw_arg(command);
+            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            cmd
+        };
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd.spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| std::io::Error::other("no stdin pipe"))?;
+        let stdout = child.stdout.take().ok_or_else(|| std::io::Error::other("no stdout pipe"))?;
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::warn!("ProxyCommand: {}", line);
+                }
+            });
+        }
+        Ok(Self { _child: child, stdin, stdout })
+    }
+}
+
+impl AsyncRead for ProxyCommandStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stdout).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ProxyCommandStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stdin).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stdin).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stdin).poll_shutdown(cx)
+    }
+}
+
+/// The transport a proxied connection runs over: a TCP stream tunnelled by a
+/// SOCKS/HTTP proxy, or a proxy command's pipes.
+pub enum ProxyStream {
+    Tcp(tokio::net::TcpStream),
+    Command(ProxyCommandStream),
+}
+
+impl AsyncRead for ProxyStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            Self::Command(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ProxyStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            Self::Command(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_flush(cx),
+            Self::Command(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            Self::Command(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_tokens() {
+        assert_eq!(
+            expand_tokens("nc -X connect -x proxy:3128 %h %p", "db.internal", 2222, "me").unwrap(),
+            "nc -X connect -x proxy:3128 db.internal 2222"
+        );
+        assert_eq!(
+            expand_tokens("cloudflared access ssh --hostname %h --user %r 100%% %x", "a.example", 22, "bob").unwrap(),
+            "cloudflared access ssh --hostname a.example --user bob 100% %x"
+        );
+    }
+
+    #[test]
+    fn refuses_shell_metacharacters() {
+        assert!(expand_tokens("nc %h %p", "host;rm -rf ~", 22, "me").is_err());
+        assert!(expand_tokens("nc %h %p --user %r", "host", 22, "$(id)").is_err());
+        // Not substituted, so not checked.
+        assert!(expand_tokens("nc host 22", "a b", 22, "$(id)").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pipes_through_the_process() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut stream = ProxyCommandStream::spawn("cat").unwrap();
+        stream.write_all(b"SSH-2.0-test\r\n").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buf = [0u8; 14];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"SSH-2.0-test\r\n");
+    }
+}
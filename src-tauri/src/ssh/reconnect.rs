@@ -0,0 +1,127 @@
+//! Automatic reconnect for sessions that opted into it.
+//!
+//! When a session's transport dies (keepalives unanswered, network change,
+//! laptop sleep), the original `ConnectSpec` is re-run — jump chain, proxy and
+//! all — under the *same* connection id, so the terminal keeps its tab (its
+//! output resumes once it sends `ssh_ready`) and anything bound to the id
+//! carries on: active tunnels are restarted on the new session and
+//! monitoring is re-attached.
+//! Progress is reported on `ssh-state-{id}`.
+
+use std::time::Duration;
+
+use tauri::{Emitter, Manager};
+
+use crate::ssh::client::{emit_state, ConnectionState, SshManager};
+use crate::state::AppState;
+
+const MAX_ATTEMPTS: u32 = 8;
+const MAX_BACKOFF_SECS: u64 = 30;
+
+/// Delay before the given (1-based) attempt: 1s, 2s, 4s, ... capped at 30s.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs((1u64 << attempt.saturating_sub(1).min(5)).min(MAX_BACKOFF_SECS))
+}
+
+/// Reconnect connection `id`, giving up after `MAX_ATTEMPTS`. Stops early if
+/// the user disconnects in the meantime. On giving up, emits `ssh-exit-{id}`
+/// so the terminal falls back to its manual reconnect prompt.
+pub(crate) async fn run(app: tauri::AppHandle, id: String) {
+    let state = app.state::<AppState>();
+    let Some(spec) = state.ssh_manager.lock().await.reconnect_spec(&id) else {
+        return;
+    };
+
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        emit_state(&app, &id, ConnectionState::Reconnecting, Some(attempt), None);
+        tokio::time::sleep(backoff(attempt)).await;
+
+        if !state.ssh_manager.lock().await.is_connected(&id) {
+            tracing::info!("SSH '{}' was closed while reconnecting", id);
+            return;
+        }
+
+        tracing::info!("SSH '{}' reconnect attempt {}/{}", id, attempt, MAX_ATTEMPTS);
+        // The terminal resizes the new PTY when it sees `connected`.
+        match SshManager::establish(&id, spec.clone(), 80, 24, app.clone()).await {
+            Ok(conn) => {
+                let mut manager = state.ssh_manager.lock().await;
+                if !manager.is_connected(&id) {
+                    conn.close();
+                    return;
+                }
+                manager.register(conn);
+                drop(manager);
+
+                restore_bound_services(&app, &state, &id).await;
+                tracing::info!("SSH '{}' reconnected", id);
+                emit_state(&app, &id, ConnectionState::Connected, None, None);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("SSH '{}' reconnect attempt {} failed: {}", id, attempt, e);
+                last_error = e.to_string();
+            }
+        }
+    }
+
+    let _ = app.emit(&format!("ssh-exit-{}", id), ());
+    emit_state(&app, &id, ConnectionState::Closed, None, Some(last_error));
+    state.recordings.stop(&id);
+    state.scrollback.remove(&id);
+}
+
+/// Restart the tunnels that were running on `id` and re-attach monitoring,
+/// now pointing at the new session.
+async fn restore_bound_services(app: &tauri::AppHandle, state: &AppState, id: &str) {
+    let (handle, remote_forwards) = {
+        let manager = state.ssh_manager.lock().await;
+        match (manager.get_handle(id), manager.get_remote_forwards(id)) {
+            (Ok(h), Ok(f)) => (h, f),
+            _ => return,
+        }
+    };
+
+    let bound: Vec<_> = state
+        .tunnels
+        .read()
+        .await
+        .values()
+        .filter(|t| t.connection_id == id && t.active)
+        .cloned()
+        .collect();
+    if !bound.is_empty() {
+        let mut tunnel_manager = state.tunnel_manager.lock().await;
+        for mut tunnel in bound {
+            // The old task is relaying over a dead session; replace it.
+            let _ = tunnel_manager.stop_tunnel(&mut tunnel).await;
+            let restarted = tunnel_manager
+                .start_tunnel(&mut tunnel, &handle, &remote_forwards)
+                .await;
+            if let Err(e) = &restarted {
+                tracing::warn!("Tunnel {} could not be restored after reconnect: {}", tunnel.id, e);
+            }
+            if let Some(t) = state.tunnels.write().await.get_mut(&tunnel.id) {
+                t.active = restarted.is_ok();
+                t.remote_port = tunnel.remote_port;
+            }
+        }
+    }
+
+    let mut collector = state.monitoring_collector.lock().await;
+    if collector.is_monitoring(id) {
+        collector.start(id, handle, app.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_then_caps() {
+        let secs: Vec<u64> = (1..=8).map(|a| backoff(a).as_secs()).collect();
+        assert_eq!(secs, vec![1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+}
@@ -0,0 +1,328 @@
+//! Per-connection scrollback kept in the backend, so a terminal that is
+//! closed, reloaded or reopened can get its screen back. Output is kept as
+//! raw bytes in a bounded ring; the terminal modes set by whatever fell off
+//! the front (alternate screen, mouse reporting, ...) are tracked so a replay
+//! starts in the right state.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::recording::cast::strip_ansi;
+
+/// Bytes of output kept per connection.
+pub const SCROLLBACK_CAP: usize = 2 * 1024 * 1024;
+
+/// How far past the cap a trim looks for a line break to cut at, so a
+/// replay doesn't open in the middle of an escape sequence.
+const TRIM_SLACK: usize = 4096;
+
+/// DEC private modes worth restoring, with their power-on defaults.
+const TRACKED_MODES: &[(u16, bool)] = &[
+    (1, false),    // application cursor keys
+    (25, true),    // cursor visible
+    (47, false),   // alternate screen
+    (1000, false), // mouse: clicks
+    (1002, false), // mouse: drags
+    (1003, false), // mouse: all motion
+    (1006, false), // mouse: SGR encoding
+    (1047, false), // alternate screen
+    (1049, false), // alternate screen, saving the cursor
+    (2004, false), // bracketed paste
+];
+
+/// A hit from [`Scrollback::search`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScrollbackMatch {
+    /// Line number, counted from the oldest line still kept.
+    pub line: usize,
+    /// Character offset of the match within the line.
+    pub column: usize,
+    /// The whole line, escape sequences removed.
+    pub text: String,
+}
+
+/// DEC private mode state, fed one byte at a time.
+#[derive(Debug, Clone)]
+struct ModeTracker {
+    modes: BTreeMap<u16, bool>,
+    parse: Parse,
+    params: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Parse {
+    Ground,
+    Escape,
+    Csi,
+    PrivateCsi,
+}
+
+impl ModeTracker {
+    fn new() -> Self {
+        Self {
+            modes: TRACKED_MODES.iter().copied().collect(),
+            parse: Parse::Ground,
+            params: String::new(),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.parse = match (self.parse, b) {
+                (_, 0x1b) => Parse::Escape,
+                (Parse::Escape, b'[') => {
+                    self.params.clear();
+                    Parse::Csi
+                }
+                (Parse::Escape, b'c') => {
+                    // RIS: full reset
+                    self.modes = TRACKED_MODES.iter().copied().collect();
+                    Parse::Ground
+                }
+                (Parse::Csi, b'?') if self.params.is_empty() => Parse::PrivateCsi,
+                (Parse::Csi | Parse::PrivateCsi, b'0'..=b'9' | b';') => {
+                    if self.params.len() < 32 {
+                        self.params.push(b as char);
+                    }
+                    self.parse
+                }
+                (Parse::PrivateCsi, b'h' | b'l') => {
+                    let on = b == b'h';
+                    for n in self.params.split(';').filter_map(|p| p.parse::<u16>().ok()) {
+                        if let Some(mode) = self.modes.get_mut(&n) {
+                            *mode = on;
+                        }
+                    }
+                    Parse::Ground
+                }
+                // Any other final byte ends a sequence we don't care about.
+                (Parse::Csi | Parse::PrivateCsi, 0x40..=0x7e) => Parse::Ground,
+                (Parse::Csi | Parse::PrivateCsi, _) => self.parse,
+                _ => Parse::Ground,
+            };
+        }
+    }
+
+    /// Escape sequences that take a fresh terminal to this state.
+    fn restore_sequence(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &(n, default) in TRACKED_MODES {
+            let on = self.modes.get(&n).copied().unwrap_or(default);
+            if on != default {
+                out.extend_from_slice(format!("\x1b[?{}{}", n, if on { 'h' } else { 'l' }).as_bytes());
+            }
+        }
+        out
+    }
+}
+
+/// The kept output of one connection.
+#[derive(Debug, Clone)]
+pub struct Scrollback {
+    bytes: VecDeque<u8>,
+    cap: usize,
+    /// Offset of `bytes[0]` in everything ever written.
+    start: u64,
+    /// Modes in effect at `start`.
+    base: ModeTracker,
+}
+
+impl Scrollback {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            bytes: VecDeque::new(),
+            cap,
+            start: 0,
+            base: ModeTracker::new(),
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.bytes.extend(data);
+        if self.bytes.len() <= self.cap {
+            return;
+        }
+        let excess = self.bytes.len() - self.cap;
+        let cut = self
+            .bytes
+            .range(excess..)
+            .take(TRIM_SLACK)
+            .position(|&b| b == b'\n')
+            .map(|i| excess + i + 1)
+            .unwrap_or(excess);
+        let dropped: Vec<u8> = self.bytes.drain(..cut).collect();
+        self.base.feed(&dropped);
+        self.start += dropped.len() as u64;
+    }
+
+    /// Offset just past the newest byte; what a later [`since`] starts from.
+    ///
+    /// [`since`]: Scrollback::since
+    pub fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+
+    /// Output written at or after `offset` that is still kept.
+    pub fn since(&self, offset: u64) -> Vec<u8> {
+        let skip = offset.saturating_sub(self.start).min(self.bytes.len() as u64) as usize;
+        self.bytes.range(skip..).copied().collect()
+    }
+
+    /// Everything kept, preceded by whatever restores the terminal modes the
+    /// trimmed output had set.
+    pub fn replay(&self) -> Vec<u8> {
+        let mut out = self.base.restore_sequence();
+        out.extend(self.bytes.iter());
+        out
+    }
+
+    /// Lines containing `query`, oldest first, at most `limit` of them.
+    pub fn search(&self, query: &str, case_sensitive: bool, limit: usize) -> Vec<ScrollbackMatch> {
+        if query.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+        let needle = if case_sensitive { query.to_string() } else { query.to_lowercase() };
+        let (a, b) = self.bytes.as_slices();
+        let raw = [a, b].concat();
+        let text = strip_ansi(&String::from_utf8_lossy(&raw));
+
+        let mut matches = Vec::new();
+        for (line_no, line) in text.split('\n').enumerate() {
+            let line = visible_line(line);
+            let haystack = if case_sensitive { line.to_string() } else { line.to_lowercase() };
+            if let Some(pos) = haystack.find(&needle) {
+                matches.push(ScrollbackMatch {
+                    line: line_no,
+                    column: haystack[..pos].chars().count(),
+                    text: line.to_string(),
+                });
+                if matches.len() >= limit {
+                    break;
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// What a carriage return leaves on screen: the text after the last `\r`
+/// (progress bars and prompt redraws), ignoring a trailing one from CRLF.
+fn visible_line(line: &str) -> &str {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    match line.rfind('\r') {
+        Some(pos) => &line[pos + 1..],
+        None => line,
+    }
+}
+
+/// Scrollback for every live SSH connection, keyed by connection id. It
+/// outlives the session task, so an automatic reconnect keeps the history.
+pub struct ScrollbackStore {
+    buffers: Mutex<HashMap<String, Scrollback>>,
+}
+
+impl ScrollbackStore {
+    pub fn new() -> Self {
+        Self { buffers: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn push(&self, id: &str, data: &[u8]) {
+        if let Ok(mut buffers) = self.buffers.lock() {
+            buffers
+                .entry(id.to_string())
+                .or_insert_with(|| Scrollback::new(SCROLLBACK_CAP))
+                .push(data);
+        }
+    }
+
+    pub fn end(&self, id: &str) -> u64 {
+        self.with(id, Scrollback::end).unwrap_or(0)
+    }
+
+    pub fn since(&self, id: &str, offset: u64) -> Vec<u8> {
+        self.with(id, |s| s.since(offset)).unwrap_or_default()
+    }
+
+    pub fn replay(&self, id: &str) -> Vec<u8> {
+        self.with(id, Scrollback::replay).unwrap_or_default()
+    }
+
+    pub fn search(&self, id: &str, query: &str, case_sensitive: bool, limit: usize) -> Vec<ScrollbackMatch> {
+        self.with(id, |s| s.search(query, case_sensitive, limit)).unwrap_or_default()
+    }
+
+    pub fn remove(&self, id: &str) {
+        if let Ok(mut buffers) = self.buffers.lock() {
+            buffers.remove(id);
+        }
+    }
+
+    fn with<T>(&self, id: &str, f: impl FnOnce(&Scrollback) -> T) -> Option<T> {
+        self.buffers.lock().ok()?.get(id).map(f)
+    }
+}
+
+impl Default for ScrollbackStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_at_a_line_break_and_tracks_offsets() {
+        let mut sb = Scrollback::new(16);
+        sb.push(b"first line\r\n");
+        sb.push(b"second line\r\n");
+        assert_eq!(sb.end(), 25);
+        // Over the cap: the whole first line goes, not just the excess.
+        assert_eq!(sb.since(0), b"second line\r\n");
+        assert_eq!(sb.since(sb.end() - 6), b"line\r\n");
+        assert!(sb.since(sb.end()).is_empty());
+    }
+
+    #[test]
+    fn replay_restores_modes_set_by_trimmed_output() {
+        let mut sb = Scrollback::new(12);
+        sb.push(b"\x1b[?1049h\x1b[?25l\x1b[?2004h\r\n");
+        sb.push(b"\x1b[?2004l\r\n");
+        sb.push(b"vim\r\n");
+        assert_eq!(sb.since(0), b"vim\r\n");
+        let replay = sb.replay();
+        let prefix = &replay[..replay.len() - sb.since(0).len()];
+        assert_eq!(prefix, b"\x1b[?25l\x1b[?1049h");
+    }
+
+    #[test]
+    fn reset_clears_tracked_modes() {
+        let mut tracker = ModeTracker::new();
+        tracker.feed(b"\x1b[?1000;1006h");
+        assert_eq!(tracker.restore_sequence(), b"\x1b[?1000h\x1b[?1006h");
+        tracker.feed(b"\x1bc");
+        assert!(tracker.restore_sequence().is_empty());
+    }
+
+    #[test]
+    fn search_skips_escape_codes_and_overwritten_text() {
+        let mut sb = Scrollback::new(1024);
+        sb.push(b"\x1b[01;32malice@db1\x1b[00m:~$ tail -f /var/log/Syslog\r\n");
+        sb.push(b"progress 10%\rprogress 100%\r\n");
+        sb.push(b"error: disk full\r\n");
+
+        let hits = sb.search("syslog", false, 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].line, 0);
+        assert_eq!(hits[0].column, 30);
+        assert_eq!(hits[0].text, "alice@db1:~$ tail -f /var/log/Syslog");
+
+        assert!(sb.search("syslog", true, 10).is_empty());
+        assert!(sb.search("10%", false, 10).is_empty());
+        assert_eq!(sb.search("100%", false, 10)[0].line, 1);
+        assert_eq!(sb.search("e", false, 2).len(), 2);
+    }
+}
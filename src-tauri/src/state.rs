@@ -9,9 +9,13 @@ use serde::{Deserialize, Serialize};
 use crate::monitoring::collector::MonitoringCollector;
 #[cfg(desktop)]
 use crate::pty::manager::PtyManager;
+use crate::recording::manager::RecordingManager;
 #[cfg(desktop)]
 use crate::serial::port::SerialManager;
 use crate::ssh::client::SshManager;
+use crate::ssh::scrollback::ScrollbackStore;
+use crate::ssh::vault_agent::VaultAgent;
+use crate::stream::flow::FlowRegistry;
 use crate::ansible::project::AnsibleProjectManager;
 use crate::tofu::project::TofuProjectManager;
 use crate::tofu::types::SchemaCache;
@@ -26,6 +30,17 @@ pub struct JumpHostConfig {
     pub port: u16,
     pub username: String,
     pub auth_method: AuthMethod,
+    /// Saved session this hop stands for. When set, its host, credentials,
+    /// proxy and own jump chain are used at connect time (the fields above
+    /// are only a snapshot for display).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// How to reach this hop; only used when it is the first one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    /// Connect + authentication timeout for this hop (None = default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
 }
 
 /// Configuration for a saved session.
@@ -54,16 +69,123 @@ pub struct SessionConfig {
     /// (e.g. "fish" or "fish -l"). Also drives shell-aware color init.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub shell: Option<String>,
+    /// Keepalive and auto-reconnect settings (None = defaults, no reconnect)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<KeepaliveConfig>,
+    /// Which key exchange, host key, cipher, MAC and compression algorithms
+    /// to offer (None = russh defaults)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crypto: Option<CryptoPolicy>,
+    /// Forward the local SSH agent to this host (None = off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_forwarding: Option<AgentForwarding>,
+    /// Only authenticate with the configured key, never agent identities
+    /// (OpenSSH `IdentitiesOnly`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identities_only: Option<bool>,
+    /// Environment variables sent before the shell starts (OpenSSH `SetEnv`);
+    /// the server's `AcceptEnv` decides which are honoured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    /// Whether the shell gets a PTY (OpenSSH `RequestTTY`, None = auto)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_tty: Option<RequestTty>,
+    /// Record this session's terminal (None = follow the global setting)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<bool>,
+    /// Character encoding of the remote terminal, as a WHATWG label such as
+    /// "shift_jis" or "gbk" (None = UTF-8)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Port forwards started after each connect. Stored inactive with no
+    /// connection id; a copy is bound to the new connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tunnels: Option<Vec<TunnelConfig>>,
+}
+
+/// Whether an interactive session asks for a PTY, as OpenSSH's `RequestTTY`.
+/// Reach always opens a shell, so everything but `No` requests one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestTty {
+    #[default]
+    Auto,
+    Yes,
+    No,
+    Force,
+}
+
+/// How a session forwards the local SSH agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentForwarding {
+    /// Relay every agent request, like `ssh -A`
+    Allow,
+    /// Ask before each signature the remote side requests
+    Confirm,
 }
 
-/// Proxy configuration for SSH connections (Tor, SOCKS5, HTTP CONNECT).
+/// Connection liveness settings for an SSH session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeepaliveConfig {
+    /// Seconds between keepalives; 0 disables them (and stall detection)
+    pub interval_secs: u64,
+    /// Unanswered keepalives before the connection is considered dead
+    pub max_missed: u32,
+    /// Re-run the original connect (jump chain included) when the connection
+    /// dies, then restore its tunnels and monitoring
+    #[serde(default)]
+    pub auto_reconnect: bool,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self { interval_secs: 15, max_missed: 3, auto_reconnect: false }
+    }
+}
+
+/// Algorithm policy for an SSH session: a preset, optionally overridden per
+/// category with an explicit list (most preferred first, OpenSSH names).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CryptoPolicy {
+    #[serde(default)]
+    pub preset: CryptoPreset,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kex: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CryptoPreset {
+    /// russh's defaults
+    #[default]
+    Default,
+    /// Hardened: curve25519/DH-16 kex, AEAD or CTR+ETM, no SHA-1 anywhere
+    Modern,
+    /// Also offers SHA-1 kex and `ssh-rsa`, CBC ciphers, plain HMACs and zlib
+    /// for old network gear
+    Legacy,
+}
+
+/// Proxy configuration for SSH connections (Tor, SOCKS5, HTTP CONNECT,
+/// ProxyCommand).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
-    /// Proxy type: "socks5", "socks4", "http"
+    /// Proxy type: "socks5", "socks4", "http", "command"
     pub proxy_type: String,
-    /// Proxy host (e.g. "127.0.0.1" for Tor)
+    /// Proxy host (e.g. "127.0.0.1" for Tor); unused for "command"
+    #[serde(default)]
     pub host: String,
-    /// Proxy port (e.g. 9050 for Tor)
+    /// Proxy port (e.g. 9050 for Tor); unused for "command"
+    #[serde(default)]
     pub port: u16,
     /// Optional proxy username
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -71,6 +193,10 @@ pub struct ProxyConfig {
     /// Optional proxy password
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// For "command": the ProxyCommand line, with `%h`, `%p` and `%r` standing
+    /// for the target host, port and user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
 }
 
 /// Authentication method for an SSH session.
@@ -89,6 +215,8 @@ pub enum AuthMethod {
         passphrase: Option<String>,
         #[serde(default, skip_serializing_if = "Option::is_none")]
         key_content: Option<String>, // Embedded key for sharing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        certificate: Option<String>, // OpenSSH user certificate (*-cert.pub line)
     },
     Agent,
 }
@@ -126,9 +254,21 @@ pub struct TunnelConfig {
     pub remote_port: u16,
     pub connection_id: String,
     pub active: bool,
+    /// Local address: the bind address for Local/Dynamic tunnels, or the host
+    /// forwarded connections are relayed to for Remote tunnels. Defaults to
+    /// 127.0.0.1 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_host: Option<String>,
 }
 
 /// The type of SSH tunnel.
+///
+/// - `Local` (`-L`): listen on `local_port`, forward to `remote_host:remote_port`
+///   as seen from the server.
+/// - `Remote` (`-R`): the server listens on `remote_host:remote_port`
+///   (`remote_port` 0 = server-allocated) and connections are relayed back to
+///   `local_host:local_port`.
+/// - `Dynamic` (`-D`): SOCKS proxy on `local_port`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TunnelType {
     Local,
@@ -172,6 +312,14 @@ pub struct AppState {
     #[cfg(desktop)]
     pub serial_manager: Arc<tokio::sync::Mutex<SerialManager>>,
     pub vault_manager: Arc<tokio::sync::Mutex<VaultManager>>,
+    /// asciinema recordings of SSH, local and serial terminals
+    pub recordings: Arc<RecordingManager>,
+    /// Output history of each SSH connection, replayed when a terminal attaches
+    pub scrollback: Arc<ScrollbackStore>,
+    /// Output flow control of every terminal with a view attached
+    pub streams: Arc<FlowRegistry>,
+    /// Built-in SSH agent serving the vault's SSH keys to local terminals
+    pub ssh_agent: Arc<VaultAgent>,
     pub plugin_manager: Arc<tokio::sync::Mutex<PluginManager>>,
     /// URL of the marketplace registry JSON file.
     pub marketplace_index_url: Arc<RwLock<String>>,
@@ -202,6 +350,10 @@ impl AppState {
             #[cfg(desktop)]
             serial_manager: Arc::new(tokio::sync::Mutex::new(SerialManager::new())),
             vault_manager: Arc::new(tokio::sync::Mutex::new(VaultManager::new(app_dir.clone()))),
+            recordings: Arc::new(RecordingManager::new()),
+            scrollback: Arc::new(ScrollbackStore::new()),
+            streams: Arc::new(FlowRegistry::new()),
+            ssh_agent: Arc::new(VaultAgent::new()),
             plugin_manager: Arc::new(tokio::sync::Mutex::new(PluginManager::new(app_dir.join("plugins")))),
             marketplace_index_url: Arc::new(RwLock::new(
                 crate::plugin::marketplace::DEFAULT_INDEX_URL.to_string(),
@@ -0,0 +1,243 @@
+//! Coalescing terminal output into batches. A burst of small reads becomes
+//! one message every few milliseconds instead of one per read; a single
+//! keystroke's echo still goes out after at most [`FLUSH_DELAY`].
+
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::flow::Flow;
+
+/// Longest output waits for more to go out with it.
+pub const FLUSH_DELAY: Duration = Duration::from_millis(4);
+/// Output that goes out without waiting for the delay.
+pub const MAX_BATCH: usize = 64 * 1024;
+/// Output a pump keeps for a view that hasn't attached yet; the oldest goes
+/// first.
+pub const BACKLOG_CAP: usize = 1024 * 1024;
+/// Reads queued for a pump before the reader blocks.
+const QUEUE_DEPTH: usize = 32;
+
+/// Output waiting to be sent.
+#[derive(Debug, Default)]
+pub struct Batcher {
+    pending: Vec<u8>,
+    since: Option<Instant>,
+}
+
+impl Batcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        self.since.get_or_insert_with(Instant::now);
+        self.pending.extend_from_slice(data);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= MAX_BATCH
+    }
+
+    /// When the pending output has to go, if there is any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + FLUSH_DELAY)
+    }
+
+    pub fn take(&mut self) -> Option<Vec<u8>> {
+        self.since = None;
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+}
+
+/// Where a pump's batches go.
+pub trait Sink: Send {
+    /// Deliver a batch. False once nobody is listening any more.
+    fn send(&mut self, batch: Vec<u8>) -> bool;
+    fn flow(&self) -> &Flow;
+}
+
+enum Feed {
+    Data(Vec<u8>),
+    Attach(Box<dyn Sink>),
+    End,
+}
+
+/// Batches the output of a blocking reader (a PTY, a serial port) on a
+/// thread of its own. While the view lags behind, [`Pump::send`] blocks, and
+/// with it the reader.
+pub struct Pump {
+    tx: SyncSender<Feed>,
+    thread: JoinHandle<()>,
+}
+
+/// Attaches views to a [`Pump`] owned by someone else.
+#[derive(Clone)]
+pub struct PumpHandle {
+    tx: SyncSender<Feed>,
+}
+
+impl Pump {
+    pub fn spawn(name: String) -> io::Result<Self> {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_DEPTH);
+        let thread = std::thread::Builder::new().name(name).spawn(move || {
+            let mut batch = Batcher::new();
+            let mut outlet = Outlet::default();
+            loop {
+                let feed = match batch.deadline() {
+                    None => rx.recv().unwrap_or(Feed::End),
+                    Some(deadline) => {
+                        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                            Ok(feed) => feed,
+                            Err(RecvTimeoutError::Timeout) => {
+                                outlet.deliver(batch.take());
+                                continue;
+                            }
+                            Err(RecvTimeoutError::Disconnected) => Feed::End,
+                        }
+                    }
+                };
+                match feed {
+                    Feed::Data(data) => {
+                        batch.push(&data);
+                        if batch.is_full() {
+                            outlet.deliver(batch.take());
+                        }
+                    }
+                    Feed::Attach(sink) => {
+                        let held = std::mem::take(&mut outlet.backlog);
+                        outlet.sink = Some(sink);
+                        outlet.deliver(Some(held));
+                        outlet.deliver(batch.take());
+                    }
+                    Feed::End => {
+                        outlet.deliver(batch.take());
+                        break;
+                    }
+                }
+            }
+        })?;
+        Ok(Self { tx, thread })
+    }
+
+    /// Queue output. False once the pump has stopped.
+    pub fn send(&self, data: Vec<u8>) -> bool {
+        self.tx.send(Feed::Data(data)).is_ok()
+    }
+
+    pub fn handle(&self) -> PumpHandle {
+        PumpHandle { tx: self.tx.clone() }
+    }
+
+    /// Send what's still pending and stop.
+    pub fn finish(self) {
+        let _ = self.tx.send(Feed::End);
+        let _ = self.thread.join();
+    }
+}
+
+impl PumpHandle {
+    /// Send output to `sink` from now on, starting with whatever was held
+    /// for it. Replaces an earlier view.
+    pub fn attach(&self, sink: impl Sink + 'static) -> bool {
+        self.tx.send(Feed::Attach(Box::new(sink))).is_ok()
+    }
+}
+
+/// The attached view, or the output held until there is one.
+#[derive(Default)]
+struct Outlet {
+    sink: Option<Box<dyn Sink>>,
+    backlog: Vec<u8>,
+}
+
+impl Outlet {
+    fn deliver(&mut self, data: Option<Vec<u8>>) {
+        let Some(data) = data.filter(|d| !d.is_empty()) else { return };
+        if let Some(sink) = &mut self.sink {
+            if sink.flow().wait() && sink.send(data.clone()) {
+                return;
+            }
+            self.sink = None;
+        }
+        self.backlog.extend_from_slice(&data);
+        if self.backlog.len() > BACKLOG_CAP {
+            let excess = self.backlog.len() - BACKLOG_CAP;
+            self.backlog.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Collect {
+        batches: Arc<Mutex<Vec<Vec<u8>>>>,
+        flow: Arc<Flow>,
+    }
+
+    impl Sink for Collect {
+        fn send(&mut self, batch: Vec<u8>) -> bool {
+            self.flow.sent(batch.len());
+            self.batches.lock().unwrap().push(batch);
+            true
+        }
+
+        fn flow(&self) -> &Flow {
+            &self.flow
+        }
+    }
+
+    #[test]
+    fn batcher_holds_output_until_full_or_due() {
+        let mut batch = Batcher::new();
+        assert!(batch.deadline().is_none());
+        batch.push(b"ls\r\n");
+        let deadline = batch.deadline().unwrap();
+        assert!(deadline > Instant::now() && !batch.is_full());
+        batch.push(&vec![b'x'; MAX_BATCH]);
+        assert!(batch.is_full());
+        assert_eq!(batch.take().unwrap().len(), MAX_BATCH + 4);
+        assert!(batch.take().is_none() && batch.deadline().is_none());
+    }
+
+    #[test]
+    fn pump_coalesces_reads_and_holds_output_until_attached() {
+        let pump = Pump::spawn("pump-test".into()).unwrap();
+        assert!(pump.send(b"motd\r\n".to_vec()));
+        std::thread::sleep(FLUSH_DELAY * 4);
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let flow = Arc::new(Flow::new());
+        pump.handle().attach(Collect { batches: batches.clone(), flow: flow.clone() });
+        for _ in 0..100 {
+            pump.send(vec![b'a'; 100]);
+        }
+        pump.finish();
+
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches[0], b"motd\r\n");
+        let total: usize = batches.iter().map(Vec::len).sum();
+        assert_eq!(total, 6 + 100 * 100);
+        assert!(batches.len() < 10, "{} batches", batches.len());
+    }
+
+    #[test]
+    fn backlog_keeps_the_newest_output() {
+        let mut outlet = Outlet::default();
+        outlet.deliver(Some(vec![b'a'; BACKLOG_CAP]));
+        outlet.deliver(Some(b"tail".to_vec()));
+        assert_eq!(outlet.backlog.len(), BACKLOG_CAP);
+        assert!(outlet.backlog.ends_with(b"atail"));
+    }
+}
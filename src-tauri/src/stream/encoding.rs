@@ -0,0 +1,170 @@
+//! Terminals whose far end doesn't speak UTF-8. Output is transcoded to
+//! UTF-8 before anything else sees it (terminal, scrollback, recordings) and
+//! keystrokes are encoded back on the way out. Both directions are streaming:
+//! a multi-byte character split across two reads comes out whole.
+//!
+//! Encodings are named by their WHATWG labels (`shift_jis`, `gbk`,
+//! `iso-8859-1`, ...). UTF-8 passes through untouched.
+
+use std::borrow::Cow;
+
+use encoding_rs::{Decoder, Encoder, EncoderResult, Encoding, UTF_8};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EncodingError {
+    #[error("Unknown character encoding: {0}")]
+    Unknown(String),
+    #[error("Character encoding not supported for terminals: {0}")]
+    Unsupported(String),
+}
+
+/// The encoding named by `label`; none (or an empty one) means UTF-8.
+/// Encodings there is no encoder for (UTF-16, `replacement`) are turned
+/// down.
+pub fn lookup(label: Option<&str>) -> Result<&'static Encoding, EncodingError> {
+    let label = match label.map(str::trim) {
+        None | Some("") => return Ok(UTF_8),
+        Some(label) => label,
+    };
+    let encoding = Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| EncodingError::Unknown(label.to_string()))?;
+    if encoding.output_encoding() != encoding {
+        return Err(EncodingError::Unsupported(label.to_string()));
+    }
+    Ok(encoding)
+}
+
+/// Remote output to UTF-8. Keeps the tail of an incomplete character until
+/// the next chunk completes it.
+pub struct OutputDecoder {
+    decoder: Option<Decoder>,
+}
+
+impl OutputDecoder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            decoder: (encoding != UTF_8).then(|| encoding.new_decoder_without_bom_handling()),
+        }
+    }
+
+    /// Bytes that don't decode become U+FFFD.
+    pub fn decode<'a>(&mut self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let Some(decoder) = &mut self.decoder else {
+            return Cow::Borrowed(data);
+        };
+        if data.is_empty() {
+            return Cow::Borrowed(data);
+        }
+        let mut text = String::with_capacity(utf8_capacity(decoder, data.len()));
+        let (_, read, _) = decoder.decode_to_string(data, &mut text, false);
+        debug_assert_eq!(read, data.len());
+        Cow::Owned(text.into_bytes())
+    }
+}
+
+/// Keystrokes (UTF-8 from the terminal) to the remote encoding. Characters
+/// the encoding can't represent are sent as `?`.
+pub struct InputEncoder {
+    codec: Option<(Decoder, Encoder)>,
+}
+
+impl InputEncoder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            codec: (encoding != UTF_8)
+                .then(|| (UTF_8.new_decoder_without_bom_handling(), encoding.new_encoder())),
+        }
+    }
+
+    pub fn encode<'a>(&mut self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let Some((utf8, encoder)) = &mut self.codec else {
+            return Cow::Borrowed(data);
+        };
+        if data.is_empty() {
+            return Cow::Borrowed(data);
+        }
+        let mut text = String::with_capacity(utf8_capacity(utf8, data.len()));
+        let (_, read, _) = utf8.decode_to_string(data, &mut text, false);
+        debug_assert_eq!(read, data.len());
+
+        let mut out = Vec::with_capacity(
+            encoder
+                .max_buffer_length_from_utf8_without_replacement(text.len())
+                .unwrap_or(text.len() * 4),
+        );
+        let mut rest = text.as_str();
+        loop {
+            let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut out, false);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => out.reserve(rest.len() * 4 + 8),
+                // A stateful encoder is back in ASCII by now.
+                EncoderResult::Unmappable(_) => out.push(b'?'),
+            }
+        }
+        Cow::Owned(out)
+    }
+}
+
+fn utf8_capacity(decoder: &Decoder, len: usize) -> usize {
+    decoder.max_utf8_buffer_length(len).unwrap_or(len * 3 + 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(label: &str, chunks: &[&[u8]]) -> String {
+        let mut decoder = OutputDecoder::new(lookup(Some(label)).unwrap());
+        let bytes: Vec<u8> = chunks.iter().flat_map(|c| decoder.decode(c).into_owned()).collect();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn lookup_labels() {
+        assert_eq!(lookup(None).unwrap(), UTF_8);
+        assert_eq!(lookup(Some(" ")).unwrap(), UTF_8);
+        assert_eq!(lookup(Some("Shift_JIS")).unwrap().name(), "Shift_JIS");
+        assert_eq!(lookup(Some("gbk")).unwrap().name(), "GBK");
+        assert!(matches!(lookup(Some("klingon")), Err(EncodingError::Unknown(_))));
+        assert!(matches!(lookup(Some("utf-16le")), Err(EncodingError::Unsupported(_))));
+        assert!(matches!(lookup(Some("iso-2022-kr")), Err(EncodingError::Unsupported(_))));
+    }
+
+    #[test]
+    fn utf8_passes_through() {
+        let mut decoder = OutputDecoder::new(UTF_8);
+        let mut encoder = InputEncoder::new(UTF_8);
+        assert!(matches!(decoder.decode(b"\xe3\x81"), Cow::Borrowed(_)));
+        assert!(matches!(encoder.encode("日本".as_bytes()), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn characters_split_across_chunks() {
+        // "日本語" in Shift_JIS, cut inside the second character.
+        let sjis = b"\x93\xfa\x96\x7b\x8c\xea";
+        assert_eq!(decode_all("shift_jis", &[&sjis[..3], &sjis[3..]]), "日本語");
+        // "中文" in GBK, one byte at a time.
+        let gbk: Vec<&[u8]> = b"\xd6\xd0\xce\xc4".chunks(1).collect();
+        assert_eq!(decode_all("gbk", &gbk), "中文");
+        // ISO-2022-JP keeps its shift state between chunks.
+        let jis = b"\x1b$BF|K\\\x1b(B ok";
+        assert_eq!(decode_all("iso-2022-jp", &[&jis[..4], &jis[4..7], &jis[7..]]), "日本 ok");
+        assert_eq!(decode_all("iso-8859-1", &[b"caf\xe9"]), "café");
+    }
+
+    #[test]
+    fn input_is_encoded() {
+        let mut encoder = InputEncoder::new(lookup(Some("shift_jis")).unwrap());
+        assert_eq!(&*encoder.encode("ls 日本\r".as_bytes()), b"ls \x93\xfa\x96\x7b\r");
+        // A keystroke split mid-character still goes out whole.
+        let bytes = "語".as_bytes();
+        assert_eq!(&*encoder.encode(&bytes[..1]), b"");
+        assert_eq!(&*encoder.encode(&bytes[1..]), b"\x8c\xea");
+
+        let mut latin1 = InputEncoder::new(lookup(Some("latin1")).unwrap());
+        assert_eq!(&*latin1.encode("é→".as_bytes()), b"\xe9?");
+    }
+}
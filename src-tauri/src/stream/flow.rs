@@ -0,0 +1,221 @@
+//! Flow control between a terminal's output and the view rendering it. The
+//! frontend acknowledges output once xterm.js has parsed it; a source that
+//! gets too far ahead pauses until the view catches up, so `cat`-ing a huge
+//! file backs up into the PTY, serial port or SSH window instead of the
+//! webview.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// Unacknowledged output at which a source pauses.
+pub const HIGH_WATER: usize = 1024 * 1024;
+/// Unacknowledged output below which a paused source resumes.
+pub const LOW_WATER: usize = 256 * 1024;
+/// How long a paused source waits for a view that stopped acknowledging
+/// (reloaded, hung) before writing its outstanding output off.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+struct Credit {
+    unacked: usize,
+    paused: bool,
+    closed: bool,
+}
+
+/// Output in flight to one view.
+#[derive(Debug, Default)]
+pub struct Flow {
+    credit: Mutex<Credit>,
+    changed: Condvar,
+    resumed: Notify,
+}
+
+impl Flow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `n` more bytes went to the view.
+    pub fn sent(&self, n: usize) {
+        let mut credit = self.lock();
+        credit.unacked += n;
+        if credit.unacked >= HIGH_WATER && !credit.closed {
+            credit.paused = true;
+        }
+    }
+
+    /// The view has rendered `n` more bytes.
+    pub fn ack(&self, n: usize) {
+        let mut credit = self.lock();
+        credit.unacked = credit.unacked.saturating_sub(n);
+        if credit.paused && credit.unacked < LOW_WATER {
+            credit.paused = false;
+            self.wake();
+        }
+    }
+
+    /// The view is gone: nothing waits on it any more.
+    pub fn close(&self) {
+        let mut credit = self.lock();
+        credit.closed = true;
+        credit.paused = false;
+        self.wake();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    /// Block until the view has caught up. Returns false if it went away.
+    pub fn wait(&self) -> bool {
+        let mut credit = self.lock();
+        while credit.paused {
+            let (guard, timeout) = self
+                .changed
+                .wait_timeout(credit, STALL_TIMEOUT)
+                .unwrap_or_else(PoisonError::into_inner);
+            credit = guard;
+            if timeout.timed_out() && credit.paused {
+                tracing::warn!("Terminal view stopped acknowledging output; resuming");
+                credit.unacked = 0;
+                credit.paused = false;
+            }
+        }
+        !credit.closed
+    }
+
+    /// [`wait`](Flow::wait) for async sources.
+    pub async fn resumed(&self) {
+        while self.is_paused() {
+            // An ack between the check and here leaves a permit behind, so
+            // this can't miss it.
+            if tokio::time::timeout(STALL_TIMEOUT, self.resumed.notified()).await.is_err() {
+                let mut credit = self.lock();
+                if credit.paused {
+                    tracing::warn!("Terminal view stopped acknowledging output; resuming");
+                    credit.unacked = 0;
+                    credit.paused = false;
+                }
+            }
+        }
+    }
+
+    fn wake(&self) {
+        self.changed.notify_all();
+        self.resumed.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Credit> {
+        self.credit.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The flow of every terminal that has a view, keyed by terminal id (SSH
+/// connection id, PTY id or serial port name), for acks coming in over IPC.
+pub struct FlowRegistry {
+    flows: Mutex<HashMap<String, Arc<Flow>>>,
+}
+
+impl FlowRegistry {
+    pub fn new() -> Self {
+        Self { flows: Mutex::new(HashMap::new()) }
+    }
+
+    /// A fresh flow for a view of `id`. One it replaces is closed.
+    pub fn register(&self, id: &str) -> Arc<Flow> {
+        let flow = Arc::new(Flow::new());
+        if let Ok(mut flows) = self.flows.lock() {
+            if let Some(old) = flows.insert(id.to_string(), flow.clone()) {
+                old.close();
+            }
+        }
+        flow
+    }
+
+    pub fn ack(&self, id: &str, n: usize) {
+        let flow = self.flows.lock().ok().and_then(|flows| flows.get(id).cloned());
+        if let Some(flow) = flow {
+            flow.ack(n);
+        }
+    }
+
+    /// Close and forget the flow of `id`.
+    pub fn remove(&self, id: &str) {
+        let flow = self.flows.lock().ok().and_then(|mut flows| flows.remove(id));
+        if let Some(flow) = flow {
+            flow.close();
+        }
+    }
+}
+
+impl Default for FlowRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_at_high_water_and_resumes_below_low_water() {
+        let flow = Flow::new();
+        flow.sent(HIGH_WATER - 1);
+        assert!(!flow.is_paused());
+        flow.sent(1);
+        assert!(flow.is_paused());
+        // Hysteresis: a little headroom isn't enough.
+        flow.ack(HIGH_WATER - LOW_WATER);
+        assert!(flow.is_paused());
+        flow.ack(1);
+        assert!(!flow.is_paused());
+        assert!(flow.wait());
+    }
+
+    #[test]
+    fn wait_returns_once_acked_or_closed() {
+        let flow = Arc::new(Flow::new());
+        flow.sent(HIGH_WATER);
+        let acker = {
+            let flow = flow.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                flow.ack(HIGH_WATER);
+            })
+        };
+        assert!(flow.wait());
+        acker.join().unwrap();
+
+        flow.sent(HIGH_WATER);
+        let closer = {
+            let flow = flow.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                flow.close();
+            })
+        };
+        assert!(!flow.wait());
+        closer.join().unwrap();
+    }
+
+    #[test]
+    fn registering_again_closes_the_old_view() {
+        let registry = FlowRegistry::new();
+        let first = registry.register("pty-1");
+        let second = registry.register("pty-1");
+        assert!(first.is_closed());
+        second.sent(HIGH_WATER);
+        registry.ack("pty-1", HIGH_WATER);
+        assert!(!second.is_paused());
+        registry.remove("pty-1");
+        assert!(second.is_closed());
+    }
+}
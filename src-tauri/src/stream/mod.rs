@@ -0,0 +1,45 @@
+//! Terminal output on its way to the frontend. Each view passes an IPC
+//! channel when it attaches; output goes over it as raw bytes, coalesced
+//! into batches, and xterm.js decodes them itself, so a UTF-8 sequence split
+//! across reads comes out whole; a terminal in another encoding is
+//! transcoded to UTF-8 first ([`encoding`]). The view acknowledges what it
+//! has rendered (`stream_ack`) and a source that gets too far ahead waits
+//! for it.
+
+pub mod batch;
+pub mod encoding;
+pub mod flow;
+
+use std::sync::Arc;
+
+use tauri::ipc::{Channel, InvokeResponseBody};
+
+use batch::Sink;
+use flow::Flow;
+
+/// A view's channel, metered by its flow.
+#[derive(Clone)]
+pub struct OutputSink {
+    channel: Channel,
+    flow: Arc<Flow>,
+}
+
+impl OutputSink {
+    pub fn new(channel: Channel, flow: Arc<Flow>) -> Self {
+        Self { channel, flow }
+    }
+}
+
+impl Sink for OutputSink {
+    fn send(&mut self, batch: Vec<u8>) -> bool {
+        if batch.is_empty() {
+            return true;
+        }
+        self.flow.sent(batch.len());
+        self.channel.send(InvokeResponseBody::Raw(batch)).is_ok()
+    }
+
+    fn flow(&self) -> &Flow {
+        &self.flow
+    }
+}
@@ -13,7 +13,7 @@ fn silent_async_command(program: impl AsRef<std::ffi::OsStr>) -> tokio::process:
     cmd
 }
 
-use crate::ssh::client::{exec_on_connection_streaming, SshManager};
+use crate::ssh::client::{OutputStream, RemoteCommand, SshManager};
 use crate::tofu::types::{TofuCommand, TofuCommandEvent, TofuCommandRequest};
 
 /// Build CLI argument list for a tofu command.
@@ -244,10 +244,27 @@ pub async fn run_remote(
         .get_handle(connection_id)
         .map_err(|e| e.to_string())?;
 
-    let exit_code =
-        exec_on_connection_streaming(&handle, &cmd, run_id, "tofu-output", app_handle)
-            .await
-            .map_err(|e| e.to_string())?;
+    let emit_line = |stream: OutputStream, line: String| {
+        let _ = app_handle.emit(
+            &event_name,
+            TofuCommandEvent {
+                run_id: run_id.to_string(),
+                stream: stream.as_str().to_string(),
+                line,
+                done: false,
+                exit_code: None,
+            },
+        );
+    };
+
+    // A long apply can go quiet for minutes while resources provision;
+    // only the connection dropping ends the run.
+    let output = RemoteCommand::new(cmd)
+        .idle_timeout(None)
+        .run_lines(&handle, emit_line)
+        .await
+        .map_err(|e| e.to_string())?;
+    let exit_code = output.exit_code_or_default();
 
     // Emit done event
     let _ = app_handle.emit(
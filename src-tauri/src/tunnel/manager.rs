@@ -7,8 +7,13 @@ use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
-use crate::ssh::client::SharedHandle;
+use crate::ssh::client::{ForwardedChannel, RemoteForwards, SharedHandle};
 use crate::state::{TunnelConfig, TunnelType};
+use crate::tunnel::remote;
+use crate::tunnel::socks::{self, SocksReply};
+
+/// Default local address for binds and remote-forward targets.
+const DEFAULT_LOCAL_HOST: &str = "127.0.0.1";
 
 #[derive(Debug, Error)]
 pub enum TunnelError {
@@ -18,18 +23,29 @@ pub enum TunnelError {
     NotFound(String),
     #[error("Port already in use: {0}")]
     PortInUse(u16),
+    #[error("Remote port {0} is already forwarded on this connection")]
+    RemoteAlreadyForwarded(u32),
     #[error("Connection not found: {0}")]
     ConnectionNotFound(String),
     #[error("IO error: {0}")]
     IoError(String),
-    #[error("Not implemented: {0}")]
-    NotImplemented(String),
 }
 
 /// An active tunnel task with a shutdown channel.
 struct ActiveTunnel {
     shutdown_tx: mpsc::Sender<()>,
     task: JoinHandle<()>,
+    /// Set for Remote tunnels: what to cancel on the server when stopping.
+    remote: Option<RemoteBinding>,
+}
+
+/// A `tcpip-forward` the server granted us, kept so `stop_tunnel` can send the
+/// matching `cancel-tcpip-forward` and drop the registry entry.
+struct RemoteBinding {
+    handle: SharedHandle,
+    forwards: RemoteForwards,
+    address: String,
+    port: u32,
 }
 
 /// Manages active SSH tunnel forwarding tasks.
@@ -51,6 +67,7 @@ impl TunnelManager {
         remote_host: &str,
         remote_port: u16,
         connection_id: &str,
+        local_host: Option<String>,
     ) -> TunnelConfig {
         TunnelConfig {
             id: uuid::Uuid::new_v4().to_string(),
@@ -60,15 +77,19 @@ impl TunnelManager {
             remote_port,
             connection_id: connection_id.to_string(),
             active: false,
+            local_host: local_host.filter(|h| !h.trim().is_empty()),
         }
     }
 
     /// Start a tunnel. For Local tunnels, binds a TCP listener and relays
-    /// each connection through an SSH direct-tcpip channel.
+    /// each connection through an SSH direct-tcpip channel. For Remote
+    /// tunnels, asks the server to listen (`tcpip-forward`) and relays the
+    /// `forwarded-tcpip` channels it opens back to a local host:port.
     pub async fn start_tunnel(
         &mut self,
         tunnel: &mut TunnelConfig,
         handle: &SharedHandle,
+        remote_forwards: &RemoteForwards,
     ) -> Result<(), TunnelError> {
         if self.active.contains_key(&tunnel.id) {
             return Err(TunnelError::CreationFailed(format!(
@@ -82,16 +103,10 @@ impl TunnelManager {
                 self.start_local_forward(tunnel, handle).await?;
             }
             TunnelType::Remote => {
-                tracing::warn!("Remote port forwarding not yet implemented");
-                return Err(TunnelError::NotImplemented(
-                    "Remote port forwarding".to_string(),
-                ));
+                self.start_remote_forward(tunnel, handle, remote_forwards).await?;
             }
             TunnelType::Dynamic => {
-                tracing::warn!("Dynamic (SOCKS) forwarding not yet implemented");
-                return Err(TunnelError::NotImplemented(
-                    "Dynamic (SOCKS) forwarding".to_string(),
-                ));
+                self.start_dynamic_forward(tunnel, handle).await?;
             }
         }
 
@@ -105,14 +120,7 @@ impl TunnelManager {
         tunnel: &TunnelConfig,
         handle: &SharedHandle,
     ) -> Result<(), TunnelError> {
-        let bind_addr = format!("127.0.0.1:{}", tunnel.local_port);
-        let listener = TcpListener::bind(&bind_addr).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::AddrInUse {
-                TunnelError::PortInUse(tunnel.local_port)
-            } else {
-                TunnelError::IoError(format!("Failed to bind {}: {}", bind_addr, e))
-            }
-        })?;
+        let (listener, bind_addr) = bind_local(tunnel).await?;
 
         tracing::info!(
             "Tunnel {}: listening on {} -> {}:{}",
@@ -176,7 +184,185 @@ impl TunnelManager {
 
         self.active.insert(
             tunnel.id.clone(),
-            ActiveTunnel { shutdown_tx, task },
+            ActiveTunnel { shutdown_tx, task, remote: None },
+        );
+
+        Ok(())
+    }
+
+    /// Start dynamic forwarding: run a SOCKS5/SOCKS4a server locally and open
+    /// a direct-tcpip channel to whatever destination each client asks for.
+    async fn start_dynamic_forward(
+        &mut self,
+        tunnel: &TunnelConfig,
+        handle: &SharedHandle,
+    ) -> Result<(), TunnelError> {
+        let (listener, bind_addr) = bind_local(tunnel).await?;
+
+        tracing::info!("Tunnel {}: SOCKS proxy listening on {}", tunnel.id, bind_addr);
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let tunnel_id = tunnel.id.clone();
+        let ssh_handle = handle.clone();
+
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    accept_result = listener.accept() => {
+                        match accept_result {
+                            Ok((tcp_stream, peer_addr)) => {
+                                tracing::debug!(
+                                    "Tunnel {}: SOCKS client connected from {}",
+                                    tunnel_id, peer_addr
+                                );
+                                let handle_clone = ssh_handle.clone();
+                                let tid = tunnel_id.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = serve_socks_client(tcp_stream, &handle_clone, &tid).await {
+                                        tracing::debug!("Tunnel {}: SOCKS session ended: {}", tid, e);
+                                    }
+                                });
+                            }
+                            Err(e) => {
+                                tracing::error!(
+                                    "Tunnel {}: accept error: {}",
+                                    tunnel_id, e
+                                );
+                            }
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        tracing::info!("Tunnel {}: shutdown signal received", tunnel_id);
+                        break;
+                    }
+                }
+            }
+            tracing::info!("Tunnel {}: SOCKS listener task exiting", tunnel_id);
+        });
+
+        self.active.insert(
+            tunnel.id.clone(),
+            ActiveTunnel { shutdown_tx, task, remote: None },
+        );
+
+        Ok(())
+    }
+
+    /// Start remote port forwarding: ask the server to listen and relay each
+    /// connection it accepts back to `local_host:local_port`.
+    async fn start_remote_forward(
+        &mut self,
+        tunnel: &mut TunnelConfig,
+        handle: &SharedHandle,
+        remote_forwards: &RemoteForwards,
+    ) -> Result<(), TunnelError> {
+        // OpenSSH semantics: an empty bind address means loopback-only on the
+        // server ("localhost"); "0.0.0.0"/"*" need GatewayPorts on the server.
+        let bind_address = match tunnel.remote_host.trim() {
+            "" => "localhost".to_string(),
+            "*" => String::new(),
+            other => other.to_string(),
+        };
+        let requested_port = tunnel.remote_port as u32;
+
+        // Register before asking so a connection racing the reply isn't lost.
+        // Port 0 (server-allocated) can only be registered once we know it.
+        let (fwd_tx, mut fwd_rx) = mpsc::unbounded_channel::<ForwardedChannel>();
+        if requested_port != 0 {
+            remote::register(remote_forwards, requested_port, fwd_tx.clone())?;
+        }
+
+        let granted = handle
+            .lock()
+            .await
+            .tcpip_forward(bind_address.clone(), requested_port)
+            .await;
+        let bound_port = match granted {
+            Ok(port) if requested_port == 0 => port,
+            Ok(_) => requested_port,
+            Err(e) => {
+                remote::unregister(remote_forwards, requested_port);
+                return Err(TunnelError::CreationFailed(format!(
+                    "Server refused remote forward on {}:{}: {}",
+                    bind_address, requested_port, e
+                )));
+            }
+        };
+        if requested_port == 0 {
+            if let Err(e) = remote::register(remote_forwards, bound_port, fwd_tx) {
+                let _ = handle.lock().await.cancel_tcpip_forward(bind_address, bound_port).await;
+                return Err(e);
+            }
+            tunnel.remote_port = bound_port as u16;
+        }
+
+        let local_host = tunnel
+            .local_host
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LOCAL_HOST.to_string());
+        let local_port = tunnel.local_port;
+
+        tracing::info!(
+            "Tunnel {}: server listening on {}:{} -> {}:{}",
+            tunnel.id,
+            bind_address,
+            bound_port,
+            local_host,
+            local_port
+        );
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        let tunnel_id = tunnel.id.clone();
+
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    forwarded = fwd_rx.recv() => {
+                        let Some(forwarded) = forwarded else { break };
+                        tracing::info!(
+                            "Tunnel {}: new remote connection from {}:{}",
+                            tunnel_id, forwarded.originator_address, forwarded.originator_port
+                        );
+                        let target = (local_host.clone(), local_port);
+                        let tid = tunnel_id.clone();
+                        tokio::spawn(async move {
+                            let tcp_stream = match tokio::net::TcpStream::connect((target.0.as_str(), target.1)).await {
+                                Ok(s) => s,
+                                Err(e) => {
+                                    tracing::error!(
+                                        "Tunnel {}: cannot reach {}:{}: {}",
+                                        tid, target.0, target.1, e
+                                    );
+                                    let _ = forwarded.channel.close().await;
+                                    return;
+                                }
+                            };
+                            if let Err(e) = relay_channel(tcp_stream, forwarded.channel).await {
+                                tracing::error!("Tunnel {}: relay error: {}", tid, e);
+                            }
+                        });
+                    }
+                    _ = shutdown_rx.recv() => {
+                        tracing::info!("Tunnel {}: shutdown signal received", tunnel_id);
+                        break;
+                    }
+                }
+            }
+            tracing::info!("Tunnel {}: remote forward task exiting", tunnel_id);
+        });
+
+        self.active.insert(
+            tunnel.id.clone(),
+            ActiveTunnel {
+                shutdown_tx,
+                task,
+                remote: Some(RemoteBinding {
+                    handle: handle.clone(),
+                    forwards: remote_forwards.clone(),
+                    address: bind_address,
+                    port: bound_port,
+                }),
+            },
         );
 
         Ok(())
@@ -189,6 +375,22 @@ impl TunnelManager {
     ) -> Result<(), TunnelError> {
         if let Some(active) = self.active.remove(&tunnel.id) {
             tracing::info!("Tunnel {}: stopping", tunnel.id);
+            if let Some(remote) = active.remote {
+                remote::unregister(&remote.forwards, remote.port);
+                // Best effort: the connection may already be gone.
+                if let Err(e) = remote
+                    .handle
+                    .lock()
+                    .await
+                    .cancel_tcpip_forward(remote.address.clone(), remote.port)
+                    .await
+                {
+                    tracing::debug!(
+                        "Tunnel {}: cancel-tcpip-forward {}:{} failed: {}",
+                        tunnel.id, remote.address, remote.port, e
+                    );
+                }
+            }
             let _ = active.shutdown_tx.send(()).await;
             // Give the task a moment to shut down gracefully
             let _ = tokio::time::timeout(
@@ -217,11 +419,66 @@ impl Default for TunnelManager {
     }
 }
 
+/// Bind the local listener for a Local or Dynamic tunnel. Returns the listener
+/// and the address string for logging.
+async fn bind_local(tunnel: &TunnelConfig) -> Result<(TcpListener, String), TunnelError> {
+    let bind_addr = format!(
+        "{}:{}",
+        tunnel.local_host.as_deref().unwrap_or(DEFAULT_LOCAL_HOST),
+        tunnel.local_port
+    );
+    let listener = TcpListener::bind(&bind_addr).await.map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AddrInUse {
+            TunnelError::PortInUse(tunnel.local_port)
+        } else {
+            TunnelError::IoError(format!("Failed to bind {}: {}", bind_addr, e))
+        }
+    })?;
+    Ok((listener, bind_addr))
+}
+
+/// Handle one SOCKS client: parse its CONNECT, open a direct-tcpip channel to
+/// the destination, report the outcome with a proper SOCKS reply code, then
+/// relay like a Local tunnel.
+async fn serve_socks_client(
+    mut tcp_stream: tokio::net::TcpStream,
+    handle: &SharedHandle,
+    tunnel_id: &str,
+) -> Result<(), TunnelError> {
+    let request = socks::read_request(&mut tcp_stream).await?;
+    tracing::info!(
+        "Tunnel {}: SOCKS CONNECT {}:{}",
+        tunnel_id, request.host, request.port
+    );
+
+    let opened = {
+        let guard = handle.lock().await;
+        guard
+            .channel_open_direct_tcpip(
+                request.host.as_str(),
+                request.port as u32,
+                "127.0.0.1",
+                0,
+            )
+            .await
+    };
+    let channel = match opened {
+        Ok(channel) => channel,
+        Err(e) => {
+            let reply = SocksReply::from_channel_error(&e);
+            let _ = socks::send_reply(&mut tcp_stream, request.version, reply).await;
+            return Err(TunnelError::IoError(format!(
+                "Failed to open direct-tcpip channel to {}:{}: {}",
+                request.host, request.port, e
+            )));
+        }
+    };
+
+    socks::send_reply(&mut tcp_stream, request.version, SocksReply::Succeeded).await?;
+    relay_channel(tcp_stream, channel).await
+}
+
 /// Relay data bidirectionally between a local TCP stream and an SSH direct-tcpip channel.
-///
-/// Uses a single task with `tokio::select!` since russh::Channel is not Clone.
-/// The TCP stream is split into read/write halves; the SSH channel is driven
-/// from a single owner using select over both directions.
 async fn relay_connection(
     tcp_stream: tokio::net::TcpStream,
     handle: &SharedHandle,
@@ -229,7 +486,7 @@ async fn relay_connection(
     remote_port: u16,
 ) -> Result<(), TunnelError> {
     // Open a direct-tcpip channel through the SSH connection
-    let mut channel = {
+    let channel = {
         let guard = handle.lock().await;
         guard
             .channel_open_direct_tcpip(
@@ -242,6 +499,19 @@ async fn relay_connection(
             .map_err(|e| TunnelError::IoError(format!("Failed to open direct-tcpip channel: {}", e)))?
     };
 
+    relay_channel(tcp_stream, channel).await
+}
+
+/// Relay data bidirectionally between a local TCP stream and an open SSH channel
+/// (direct-tcpip for Local tunnels, forwarded-tcpip for Remote ones).
+///
+/// Uses a single task with `tokio::select!` since russh::Channel is not Clone.
+/// The TCP stream is split into read/write halves; the SSH channel is driven
+/// from a single owner using select over both directions.
+async fn relay_channel(
+    tcp_stream: tokio::net::TcpStream,
+    mut channel: russh::Channel<russh::client::Msg>,
+) -> Result<(), TunnelError> {
     let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
 
     let mut buf = vec![0u8; 8192];
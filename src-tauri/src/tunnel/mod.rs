@@ -1 +1,3 @@
 pub mod manager;
+pub mod remote;
+pub mod socks;
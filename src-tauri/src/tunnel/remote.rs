@@ -0,0 +1,102 @@
+//! Bookkeeping for remote (`-R`) forwards on one connection. The server
+//! listens and opens a `forwarded-tcpip` channel per connection it accepts;
+//! the SSH handler routes each one, by the port it arrived on, to the tunnel
+//! task that registered that port.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc;
+
+use super::manager::TunnelError;
+
+/// Forwards on one connection, keyed by the port the server listens on.
+pub type Registry<T> = Mutex<HashMap<u32, mpsc::UnboundedSender<T>>>;
+
+/// Where an incoming channel went.
+#[derive(Debug)]
+pub enum Dispatch<T> {
+    Delivered,
+    /// The forward is registered but its task is shutting down.
+    Closed(T),
+    /// Nothing is registered on that port.
+    Unknown(T),
+}
+
+/// Claim `port` for a tunnel task. Each port is forwarded at most once per
+/// connection.
+pub fn register<T>(registry: &Registry<T>, port: u32, tx: mpsc::UnboundedSender<T>) -> Result<(), TunnelError> {
+    let mut forwards = registry.lock().unwrap();
+    if forwards.contains_key(&port) {
+        return Err(TunnelError::RemoteAlreadyForwarded(port));
+    }
+    forwards.insert(port, tx);
+    Ok(())
+}
+
+pub fn unregister<T>(registry: &Registry<T>, port: u32) {
+    registry.lock().unwrap().remove(&port);
+}
+
+/// Hand a channel that arrived on `port` to the task forwarding it. An
+/// undelivered channel comes back for the caller to close.
+pub fn dispatch<T>(registry: &Registry<T>, port: u32, item: T) -> Dispatch<T> {
+    let sender = registry.lock().unwrap().get(&port).cloned();
+    match sender {
+        Some(tx) => match tx.send(item) {
+            Ok(()) => Dispatch::Delivered,
+            Err(mpsc::error::SendError(item)) => Dispatch::Closed(item),
+        },
+        None => Dispatch::Unknown(item),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_forwarded_once_until_released() {
+        let registry = Registry::<&str>::default();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        register(&registry, 8080, tx.clone()).unwrap();
+        assert!(matches!(
+            register(&registry, 8080, tx.clone()),
+            Err(TunnelError::RemoteAlreadyForwarded(8080))
+        ));
+        register(&registry, 8081, tx.clone()).unwrap();
+
+        unregister(&registry, 8080);
+        register(&registry, 8080, tx).unwrap();
+        assert_eq!(registry.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn channels_reach_the_task_for_their_port() {
+        let registry = Registry::default();
+        let (web_tx, mut web_rx) = mpsc::unbounded_channel();
+        let (db_tx, mut db_rx) = mpsc::unbounded_channel();
+        register(&registry, 80, web_tx).unwrap();
+        register(&registry, 5432, db_tx).unwrap();
+
+        assert!(matches!(dispatch(&registry, 5432, "db"), Dispatch::Delivered));
+        assert!(matches!(dispatch(&registry, 80, "web"), Dispatch::Delivered));
+        assert_eq!(db_rx.try_recv().unwrap(), "db");
+        assert_eq!(web_rx.try_recv().unwrap(), "web");
+        assert!(web_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn undelivered_channels_come_back() {
+        let registry = Registry::default();
+        assert!(matches!(dispatch(&registry, 22, "stray"), Dispatch::Unknown("stray")));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        register(&registry, 22, tx).unwrap();
+        drop(rx);
+        assert!(matches!(dispatch(&registry, 22, "late"), Dispatch::Closed("late")));
+
+        unregister(&registry, 22);
+        assert!(matches!(dispatch(&registry, 22, "after"), Dispatch::Unknown("after")));
+    }
+}
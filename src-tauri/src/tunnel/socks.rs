@@ -0,0 +1,328 @@
+//! Minimal SOCKS server side for dynamic (`-D`) forwarding.
+//!
+//! Speaks SOCKS5 (RFC 1928, no-auth only) and SOCKS4/4a. Only `CONNECT` is
+//! supported — that's all OpenSSH's `-D` offers too. The tunnel manager reads
+//! the request with [`read_request`], opens a direct-tcpip channel to the
+//! requested destination and answers with [`send_reply`].
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::manager::TunnelError;
+
+const SOCKS4_VERSION: u8 = 0x04;
+const SOCKS5_VERSION: u8 = 0x05;
+const CMD_CONNECT: u8 = 0x01;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xFF;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Which protocol the client spoke, so the reply uses the same framing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocksVersion {
+    V4,
+    V5,
+}
+
+/// A parsed `CONNECT` request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocksRequest {
+    pub version: SocksVersion,
+    pub host: String,
+    pub port: u16,
+}
+
+/// Outcome reported back to the SOCKS client. Mapped to the SOCKS5 `REP`
+/// codes; SOCKS4 only distinguishes granted (0x5A) from rejected (0x5B).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocksReply {
+    Succeeded,
+    GeneralFailure,
+    NotAllowed,
+    NetworkUnreachable,
+    HostUnreachable,
+    ConnectionRefused,
+    CommandNotSupported,
+    AddressTypeNotSupported,
+}
+
+impl SocksReply {
+    fn socks5_code(self) -> u8 {
+        match self {
+            SocksReply::Succeeded => 0x00,
+            SocksReply::GeneralFailure => 0x01,
+            SocksReply::NotAllowed => 0x02,
+            SocksReply::NetworkUnreachable => 0x03,
+            SocksReply::HostUnreachable => 0x04,
+            SocksReply::ConnectionRefused => 0x05,
+            SocksReply::CommandNotSupported => 0x07,
+            SocksReply::AddressTypeNotSupported => 0x08,
+        }
+    }
+
+    fn socks4_code(self) -> u8 {
+        match self {
+            SocksReply::Succeeded => 0x5A,
+            _ => 0x5B,
+        }
+    }
+
+    /// Translate a failed `channel_open_direct_tcpip` into the closest reply.
+    pub fn from_channel_error(err: &russh::Error) -> Self {
+        match err {
+            russh::Error::ChannelOpenFailure(reason) => match reason {
+                russh::ChannelOpenFailure::AdministrativelyProhibited => SocksReply::NotAllowed,
+                russh::ChannelOpenFailure::ConnectFailed => SocksReply::ConnectionRefused,
+                _ => SocksReply::GeneralFailure,
+            },
+            _ => SocksReply::GeneralFailure,
+        }
+    }
+}
+
+/// Read the client's greeting and `CONNECT` request. Protocol violations that
+/// still allow a reply (unsupported command / address type) are answered here
+/// before the error is returned; the caller just drops the stream.
+pub async fn read_request<S>(stream: &mut S) -> Result<SocksRequest, TunnelError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match stream.read_u8().await.map_err(io_err)? {
+        SOCKS5_VERSION => read_socks5(stream).await,
+        SOCKS4_VERSION => read_socks4(stream).await,
+        other => Err(TunnelError::IoError(format!(
+            "Unsupported SOCKS version {:#04x}",
+            other
+        ))),
+    }
+}
+
+async fn read_socks5<S>(stream: &mut S) -> Result<SocksRequest, TunnelError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Greeting: NMETHODS, METHODS...
+    let nmethods = stream.read_u8().await.map_err(io_err)? as usize;
+    let mut methods = vec![0u8; nmethods];
+    stream.read_exact(&mut methods).await.map_err(io_err)?;
+    if !methods.contains(&METHOD_NO_AUTH) {
+        let _ = stream
+            .write_all(&[SOCKS5_VERSION, METHOD_NONE_ACCEPTABLE])
+            .await;
+        return Err(TunnelError::IoError(
+            "SOCKS5 client offered no acceptable auth method".into(),
+        ));
+    }
+    stream
+        .write_all(&[SOCKS5_VERSION, METHOD_NO_AUTH])
+        .await
+        .map_err(io_err)?;
+
+    // Request: VER CMD RSV ATYP DST.ADDR DST.PORT
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await.map_err(io_err)?;
+    if head[0] != SOCKS5_VERSION {
+        return Err(TunnelError::IoError(format!(
+            "Bad SOCKS5 request version {:#04x}",
+            head[0]
+        )));
+    }
+
+    let host = match head[3] {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets).await.map_err(io_err)?;
+            Ipv4Addr::from(octets).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets).await.map_err(io_err)?;
+            Ipv6Addr::from(octets).to_string()
+        }
+        ATYP_DOMAIN => {
+            let len = stream.read_u8().await.map_err(io_err)? as usize;
+            let mut name = vec![0u8; len];
+            stream.read_exact(&mut name).await.map_err(io_err)?;
+            String::from_utf8(name)
+                .map_err(|_| TunnelError::IoError("SOCKS5 domain is not UTF-8".into()))?
+        }
+        other => {
+            send_reply(stream, SocksVersion::V5, SocksReply::AddressTypeNotSupported).await?;
+            return Err(TunnelError::IoError(format!(
+                "Unsupported SOCKS5 address type {:#04x}",
+                other
+            )));
+        }
+    };
+    let port = stream.read_u16().await.map_err(io_err)?;
+
+    if head[1] != CMD_CONNECT {
+        send_reply(stream, SocksVersion::V5, SocksReply::CommandNotSupported).await?;
+        return Err(TunnelError::IoError(format!(
+            "Unsupported SOCKS5 command {:#04x}",
+            head[1]
+        )));
+    }
+
+    Ok(SocksRequest { version: SocksVersion::V5, host, port })
+}
+
+async fn read_socks4<S>(stream: &mut S) -> Result<SocksRequest, TunnelError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // VN(already read) CD DSTPORT DSTIP USERID NUL [DOMAIN NUL]
+    let cmd = stream.read_u8().await.map_err(io_err)?;
+    let port = stream.read_u16().await.map_err(io_err)?;
+    let mut ip = [0u8; 4];
+    stream.read_exact(&mut ip).await.map_err(io_err)?;
+    let _user_id = read_nul_terminated(stream).await?;
+
+    // SOCKS4a: 0.0.0.x with x != 0 means "resolve the domain that follows".
+    let host = if ip[..3] == [0, 0, 0] && ip[3] != 0 {
+        let name = read_nul_terminated(stream).await?;
+        String::from_utf8(name)
+            .map_err(|_| TunnelError::IoError("SOCKS4a domain is not UTF-8".into()))?
+    } else {
+        Ipv4Addr::from(ip).to_string()
+    };
+
+    if cmd != CMD_CONNECT {
+        send_reply(stream, SocksVersion::V4, SocksReply::CommandNotSupported).await?;
+        return Err(TunnelError::IoError(format!(
+            "Unsupported SOCKS4 command {:#04x}",
+            cmd
+        )));
+    }
+
+    Ok(SocksRequest { version: SocksVersion::V4, host, port })
+}
+
+/// Read a NUL-terminated field (SOCKS4 user id / 4a domain), capped at 255 bytes.
+async fn read_nul_terminated<S>(stream: &mut S) -> Result<Vec<u8>, TunnelError>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut out = Vec::new();
+    loop {
+        let b = stream.read_u8().await.map_err(io_err)?;
+        if b == 0 {
+            return Ok(out);
+        }
+        if out.len() >= 255 {
+            return Err(TunnelError::IoError("SOCKS4 field too long".into()));
+        }
+        out.push(b);
+    }
+}
+
+/// Send the reply for a `CONNECT`. We don't know the address the server bound
+/// for us, so the bound address is reported as 0.0.0.0:0 (as OpenSSH does).
+pub async fn send_reply<S>(
+    stream: &mut S,
+    version: SocksVersion,
+    reply: SocksReply,
+) -> Result<(), TunnelError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let packet: Vec<u8> = match version {
+        SocksVersion::V5 => vec![
+            SOCKS5_VERSION,
+            reply.socks5_code(),
+            0x00,
+            ATYP_IPV4,
+            0, 0, 0, 0,
+            0, 0,
+        ],
+        SocksVersion::V4 => vec![0x00, reply.socks4_code(), 0, 0, 0, 0, 0, 0],
+    };
+    stream.write_all(&packet).await.map_err(io_err)
+}
+
+fn io_err(e: std::io::Error) -> TunnelError {
+    TunnelError::IoError(format!("SOCKS handshake: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(client_bytes: &[u8]) -> (Result<SocksRequest, TunnelError>, Vec<u8>) {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_all(client_bytes).await.unwrap();
+        let result = read_request(&mut server).await;
+        drop(server);
+        let mut written = Vec::new();
+        client.read_to_end(&mut written).await.unwrap();
+        (result, written)
+    }
+
+    #[tokio::test]
+    async fn socks5_domain_connect() {
+        let mut req = vec![5, 1, 0, 5, 1, 0, 3, 11];
+        req.extend_from_slice(b"example.com");
+        req.extend_from_slice(&443u16.to_be_bytes());
+        let (result, written) = roundtrip(&req).await;
+        let r = result.unwrap();
+        assert_eq!(r.version, SocksVersion::V5);
+        assert_eq!(r.host, "example.com");
+        assert_eq!(r.port, 443);
+        assert_eq!(written, vec![5, 0]);
+    }
+
+    #[tokio::test]
+    async fn socks5_ipv4_and_ipv6_connect() {
+        let (result, _) = roundtrip(&[5, 1, 0, 5, 1, 0, 1, 10, 0, 0, 7, 0, 22]).await;
+        let r = result.unwrap();
+        assert_eq!((r.host.as_str(), r.port), ("10.0.0.7", 22));
+
+        let mut req = vec![5, 1, 0, 5, 1, 0, 4];
+        req.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        req.extend_from_slice(&80u16.to_be_bytes());
+        let (result, _) = roundtrip(&req).await;
+        assert_eq!(result.unwrap().host, "::1");
+    }
+
+    #[tokio::test]
+    async fn socks5_rejects_auth_only_clients() {
+        let (result, written) = roundtrip(&[5, 1, 2]).await;
+        assert!(result.is_err());
+        assert_eq!(written, vec![5, 0xFF]);
+    }
+
+    #[tokio::test]
+    async fn socks5_bind_is_not_supported() {
+        let (result, written) = roundtrip(&[5, 1, 0, 5, 2, 0, 1, 127, 0, 0, 1, 0, 80]).await;
+        assert!(result.is_err());
+        assert_eq!(written[2..4], [5, 0x07]);
+    }
+
+    #[tokio::test]
+    async fn socks4_and_socks4a_connect() {
+        let (result, _) = roundtrip(&[4, 1, 0, 80, 192, 168, 1, 2, b'u', 0]).await;
+        let r = result.unwrap();
+        assert_eq!(r.version, SocksVersion::V4);
+        assert_eq!((r.host.as_str(), r.port), ("192.168.1.2", 80));
+
+        let mut req = vec![4, 1, 0x1F, 0x90, 0, 0, 0, 1, 0];
+        req.extend_from_slice(b"intranet.local\0");
+        let (result, _) = roundtrip(&req).await;
+        let r = result.unwrap();
+        assert_eq!((r.host.as_str(), r.port), ("intranet.local", 8080));
+    }
+
+    #[tokio::test]
+    async fn replies_use_protocol_framing() {
+        let mut out = Vec::new();
+        send_reply(&mut out, SocksVersion::V5, SocksReply::HostUnreachable).await.unwrap();
+        assert_eq!(out, vec![5, 4, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+        let mut out = Vec::new();
+        send_reply(&mut out, SocksVersion::V4, SocksReply::ConnectionRefused).await.unwrap();
+        assert_eq!(out, vec![0, 0x5B, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -338,6 +338,11 @@ impl VaultManager {
         }
     }
 
+    /// Whether a vault's secrets can currently be read.
+    pub fn is_vault_unlocked(&self, vault_id: &str) -> bool {
+        self.vaults.get(vault_id).is_some_and(|v| v.master_dek.is_some())
+    }
+
     /// Check if locked.
     pub fn is_locked(&self) -> bool {
         self.kek.is_none()
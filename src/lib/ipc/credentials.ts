@@ -35,3 +35,16 @@ export async function hasPassword(sessionId: string): Promise<boolean> {
 export async function deletePassword(sessionId: string): Promise<void> {
   return invoke('credential_delete_password', { sessionId });
 }
+
+/** Save a session's TOTP secret (base32 or otpauth:// URI) for 2FA prompts. */
+export async function saveTotp(sessionId: string, secret: string): Promise<void> {
+  return invoke('credential_save_totp', { sessionId, secret });
+}
+
+export async function hasTotp(sessionId: string): Promise<boolean> {
+  return invoke<boolean>('credential_has_totp', { sessionId });
+}
+
+export async function deleteTotp(sessionId: string): Promise<void> {
+  return invoke('credential_delete_totp', { sessionId });
+}
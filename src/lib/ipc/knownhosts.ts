@@ -0,0 +1,92 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** A pinned host key. `hosts` uses OpenSSH known_hosts host-field syntax. */
+export interface KnownHostEntry {
+  id: string;
+  /** `host`, `[host]:port`, a pattern list, or a hashed `|1|salt|hash` name. */
+  hosts: string;
+  /** Absent for entries pinned before key types were recorded. */
+  keyType?: string;
+  publicKey?: string;
+  fingerprint: string;
+  revoked: boolean;
+  comment?: string;
+}
+
+export interface KnownHostsImportSummary {
+  imported: number;
+  revoked: number;
+  certAuthorities: number;
+  duplicates: number;
+  skipped: number;
+}
+
+/** List all pinned host keys, `@revoked` entries included. */
+export async function knownHostsList(): Promise<KnownHostEntry[]> {
+  return invoke<KnownHostEntry[]>('known_hosts_list');
+}
+
+/** Forget a pinned host key; the next connect to that host prompts again. */
+export async function knownHostsRemove(id: string): Promise<void> {
+  return invoke('known_hosts_remove', { id });
+}
+
+/**
+ * Replace the key pinned for `host:port` with `publicKey` (an OpenSSH public
+ * key or an `ssh-keyscan` line). Resolves to the new fingerprint.
+ */
+export async function knownHostsRepin(host: string, port: number, publicKey: string): Promise<string> {
+  return invoke<string>('known_hosts_repin', { host, port, publicKey });
+}
+
+/** Merge an OpenSSH known_hosts file (default `~/.ssh/known_hosts`). */
+export async function knownHostsImport(path?: string): Promise<KnownHostsImportSummary> {
+  return invoke<KnownHostsImportSummary>('known_hosts_import', { path: path ?? null });
+}
+
+/** The pinned keys and trusted CAs as OpenSSH known_hosts text. */
+export async function knownHostsExport(): Promise<string> {
+  return invoke<string>('known_hosts_export');
+}
+
+/** A CA trusted to sign host certificates (`@cert-authority` equivalent). */
+export interface TrustedHostCa {
+  id: string;
+  /** OpenSSH host pattern list, e.g. `*.corp.example,!legacy.corp.example`. */
+  patterns: string;
+  publicKey: string;
+  fingerprint: string;
+  comment?: string;
+}
+
+/** List the trusted host CAs. */
+export async function hostCaList(): Promise<TrustedHostCa[]> {
+  return invoke<TrustedHostCa[]>('host_ca_list');
+}
+
+/** Trust a CA public key for hosts matching `patterns`. */
+export async function hostCaAdd(
+  patterns: string,
+  publicKey: string,
+  comment?: string
+): Promise<TrustedHostCa> {
+  return invoke<TrustedHostCa>('host_ca_add', { patterns, publicKey, comment: comment ?? null });
+}
+
+/** Stop trusting a host CA. */
+export async function hostCaRemove(id: string): Promise<void> {
+  return invoke('host_ca_remove', { id });
+}
+
+/**
+ * Verify a host certificate (e.g. from `ssh-keyscan -c`) against the trusted
+ * CAs and trust it for the host while it stays valid; it is re-checked on
+ * every connect. Resolves to the certified key's fingerprint.
+ */
+export async function hostCaTrustCertificate(
+  host: string,
+  port: number,
+  certificate: string
+): Promise<string> {
+  return invoke<string>('host_ca_trust_certificate', { host, port, certificate });
+}
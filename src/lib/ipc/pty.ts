@@ -1,19 +1,27 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, type Channel } from '@tauri-apps/api/core';
 
 export async function ptySpawn(
 	id: string,
 	shell?: string,
 	cols?: number,
-	rows?: number
+	rows?: number,
+	encoding?: string
 ): Promise<string> {
 	return invoke<string>('pty_spawn', {
 		id,
 		shell: shell ?? null,
 		cols: cols ?? 80,
-		rows: rows ?? 24
+		rows: rows ?? 24,
+		encoding: encoding ?? null
 	});
 }
 
+/** Start receiving a PTY's output on `onData` (see `openTerminalOutput`),
+ *  beginning with whatever the shell printed before. */
+export async function ptyAttach(id: string, onData: Channel<ArrayBuffer>): Promise<void> {
+	return invoke('pty_attach', { id, onData });
+}
+
 export async function ptyWrite(id: string, data: number[]): Promise<void> {
 	return invoke('pty_write', { id, data });
 }
@@ -0,0 +1,63 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export type RecordingKind = 'ssh' | 'local' | 'serial';
+
+export interface RecordingConfig {
+  record_all: boolean; // Record every terminal that doesn't opt out
+  record_input: boolean; // Also record keystrokes (password prompts are skipped)
+  directory: string | null; // null = recordings/ in the app data dir
+  retention_days: number | null; // null = keep forever
+}
+
+export interface RecordingInfo {
+  name: string; // File name; what read/delete take
+  title: string | null;
+  kind: RecordingKind | null;
+  started_at: number; // Unix seconds
+  width: number;
+  height: number;
+  size_bytes: number;
+  duration_secs: number | null;
+  active: boolean; // Still being written
+}
+
+export async function recordingGetConfig(): Promise<RecordingConfig> {
+  return invoke<RecordingConfig>('recording_get_config');
+}
+
+export async function recordingSetConfig(config: RecordingConfig): Promise<void> {
+  return invoke('recording_set_config', { config });
+}
+
+/** Start recording an open terminal (connection id, PTY id or serial port). Returns the file name. */
+export async function recordingStart(params: {
+  id: string;
+  kind: RecordingKind;
+  title: string;
+  cols: number;
+  rows: number;
+}): Promise<string> {
+  return invoke<string>('recording_start', params);
+}
+
+export async function recordingStop(id: string): Promise<void> {
+  return invoke('recording_stop', { id });
+}
+
+/** Ids of the terminals currently being recorded. */
+export async function recordingActive(): Promise<string[]> {
+  return invoke<string[]>('recording_active');
+}
+
+export async function recordingList(): Promise<RecordingInfo[]> {
+  return invoke<RecordingInfo[]>('recording_list');
+}
+
+/** The asciinema v2 `.cast` text of a recording. */
+export async function recordingRead(name: string): Promise<string> {
+  return invoke<string>('recording_read', { name });
+}
+
+export async function recordingDelete(name: string): Promise<void> {
+  return invoke('recording_delete', { name });
+}
@@ -1,10 +1,13 @@
 import { invoke } from '@tauri-apps/api/core';
+import type { TunnelConfig } from './tunnel';
 
 export interface AuthMethod {
   type: 'Password' | 'Key' | 'Agent';
   password?: string; // for Password type - stored encrypted in vault
   path?: string; // for Key type
   passphrase?: string; // for Key type - stored encrypted in vault
+  key_content?: string; // for Key type - embedded key material from shared sessions
+  certificate?: string; // for Key type - OpenSSH user certificate (-cert.pub line)
 }
 
 export interface JumpHostConfig {
@@ -12,6 +15,9 @@ export interface JumpHostConfig {
   port: number;
   username: string;
   auth_method: AuthMethod;
+  session_id?: string | null; // Saved session this hop stands for (fields above are a snapshot)
+  proxy?: ProxySessionConfig | null; // How to reach this hop; first hop only
+  timeout_secs?: number | null; // Connect + auth timeout for this hop (null = default)
 }
 
 export interface SessionConfig {
@@ -28,6 +34,55 @@ export interface SessionConfig {
   jump_chain?: JumpHostConfig[] | null; // ProxyJump chain
   proxy?: ProxySessionConfig | null; // Proxy config (SOCKS5/Tor, HTTP)
   shell?: string | null; // Optional per-session login shell (e.g. "fish -l")
+  keepalive?: KeepaliveConfig | null; // Keepalive probing and auto-reconnect
+  crypto?: CryptoPolicy | null; // Algorithm preset / overrides (null = defaults)
+  agent_forwarding?: AgentForwarding | null; // Forward the local agent (null = off)
+  identities_only?: boolean | null; // Only the configured key, never agent identities
+  env?: Record<string, string> | null; // Sent before the shell starts (SetEnv)
+  request_tty?: RequestTty | null; // PTY request (null = auto)
+  record?: boolean | null; // Record the terminal (null = follow the global setting)
+  encoding?: string | null; // Remote character encoding, a WHATWG label (null = UTF-8)
+  tunnels?: TunnelConfig[] | null; // Port forwards started after each connect
+}
+
+/** Character encodings offered for legacy hosts, by WHATWG label. */
+export const TERMINAL_ENCODINGS: { label: string; value: string }[] = [
+  { label: 'Shift_JIS', value: 'shift_jis' },
+  { label: 'EUC-JP', value: 'euc-jp' },
+  { label: 'ISO-2022-JP', value: 'iso-2022-jp' },
+  { label: 'GBK', value: 'gbk' },
+  { label: 'GB18030', value: 'gb18030' },
+  { label: 'Big5', value: 'big5' },
+  { label: 'EUC-KR', value: 'euc-kr' },
+  { label: 'ISO-8859-1', value: 'iso-8859-1' },
+  { label: 'ISO-8859-2', value: 'iso-8859-2' },
+  { label: 'ISO-8859-15', value: 'iso-8859-15' },
+  { label: 'Windows-1251', value: 'windows-1251' },
+  { label: 'KOI8-R', value: 'koi8-r' },
+];
+
+/** OpenSSH `RequestTTY`; only `no` skips the PTY for an interactive shell. */
+export type RequestTty = 'auto' | 'yes' | 'no' | 'force';
+
+/** `confirm` asks before each signature the remote side requests. */
+export type AgentForwarding = 'allow' | 'confirm';
+
+export interface KeepaliveConfig {
+  interval_secs: number; // 0 disables keepalives
+  max_missed: number;
+  auto_reconnect: boolean;
+}
+
+export type CryptoPreset = 'default' | 'modern' | 'legacy';
+
+/** Per-category lists (OpenSSH names, most preferred first) override the preset. */
+export interface CryptoPolicy {
+  preset: CryptoPreset;
+  kex?: string[];
+  host_key?: string[];
+  cipher?: string[];
+  mac?: string[];
+  compression?: string[];
 }
 
 export interface ProxySessionConfig {
@@ -36,6 +91,7 @@ export interface ProxySessionConfig {
   port: number;
   username?: string | null;
   password?: string | null;
+  command?: string | null; // proxy_type 'command': ProxyCommand line (%h, %p, %r)
 }
 
 export interface Folder {
@@ -65,6 +121,15 @@ export async function sessionCreate(params: {
   jumpChain?: JumpHostConfig[] | null;
   proxy?: ProxySessionConfig | null;
   shell?: string | null;
+  keepalive?: KeepaliveConfig | null;
+  crypto?: CryptoPolicy | null;
+  agentForwarding?: AgentForwarding | null;
+  identitiesOnly?: boolean | null;
+  env?: Record<string, string> | null;
+  requestTty?: RequestTty | null;
+  record?: boolean | null;
+  encoding?: string | null;
+  tunnels?: TunnelConfig[] | null;
 }): Promise<SessionConfig> {
   return invoke<SessionConfig>('session_create', {
     name: params.name,
@@ -78,6 +143,15 @@ export async function sessionCreate(params: {
     jumpChain: params.jumpChain ?? null,
     proxy: params.proxy ?? null,
     shell: params.shell?.trim() ? params.shell.trim() : null,
+    keepalive: params.keepalive ?? null,
+    crypto: params.crypto ?? null,
+    agentForwarding: params.agentForwarding ?? null,
+    identitiesOnly: params.identitiesOnly ?? null,
+    env: params.env ?? null,
+    requestTty: params.requestTty ?? null,
+    record: params.record ?? null,
+    encoding: params.encoding ?? null,
+    tunnels: params.tunnels ?? null,
   });
 }
 
@@ -1,4 +1,5 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, type Channel } from '@tauri-apps/api/core';
+import type { AgentForwarding, CryptoPolicy, KeepaliveConfig, RequestTty } from './sessions';
 
 export interface JumpHostConnectParams {
   host: string;
@@ -8,6 +9,16 @@ export interface JumpHostConnectParams {
   password?: string;
   keyPath?: string;
   keyPassphrase?: string;
+  /** Embedded private key (shared sessions); takes precedence over keyPath. */
+  keyContent?: string;
+  /** OpenSSH user certificate offered with the key. */
+  certificate?: string;
+  /** Saved session this hop stands for; its stored host and credentials are used. */
+  sessionId?: string;
+  /** How to reach this hop; only used for the first one. */
+  proxy?: ProxyConfig;
+  /** Connect + auth timeout for this hop (default 15s). */
+  timeoutSecs?: number;
 }
 
 export interface ProxyConfig {
@@ -16,6 +27,7 @@ export interface ProxyConfig {
   port: number;
   username?: string;
   password?: string;
+  command?: string; // proxy_type 'command': ProxyCommand line (%h, %p, %r)
 }
 
 export interface SshConnectParams {
@@ -27,6 +39,10 @@ export interface SshConnectParams {
   password?: string;
   keyPath?: string;
   keyPassphrase?: string;
+  /** Embedded private key (shared sessions); takes precedence over keyPath. */
+  keyContent?: string;
+  /** OpenSSH user certificate offered with the key (else a sibling `-cert.pub` is used). */
+  certificate?: string;
   cols: number;
   rows: number;
   jumpChain?: JumpHostConnectParams[];
@@ -35,6 +51,22 @@ export interface SshConnectParams {
   shell?: string;
   /** Inject the auto shell-color/prompt init after login (default true). */
   injectColors?: boolean;
+  /** Keepalive probing; with `auto_reconnect` the backend re-dials on loss. */
+  keepalive?: KeepaliveConfig;
+  /** Algorithm preset / overrides for the target host. */
+  crypto?: CryptoPolicy;
+  /** Forward the local SSH agent to the target. */
+  agentForwarding?: AgentForwarding;
+  /** With key auth, don't also offer agent identities. */
+  identitiesOnly?: boolean;
+  /** Environment sent before the shell starts. */
+  env?: Record<string, string>;
+  /** `no` opens the shell without a PTY. */
+  requestTty?: RequestTty;
+  /** Record this terminal; unset follows the global recording setting. */
+  record?: boolean;
+  /** The host's character encoding (WHATWG label); unset is UTF-8. */
+  encoding?: string;
 }
 
 export interface ConnectionInfo {
@@ -42,6 +74,24 @@ export interface ConnectionInfo {
   host: string;
   port: number;
   username: string;
+  /** Set for a shell multiplexed over another connection's SSH session. */
+  master_id?: string;
+  /** What the transport negotiated with the target, when known. */
+  algorithms?: NegotiatedAlgorithms;
+}
+
+export interface Directional<T = string> {
+  client_to_server: T;
+  server_to_client: T;
+}
+
+export interface NegotiatedAlgorithms {
+  kex: string;
+  host_key: string;
+  cipher: Directional;
+  /** `null` for AEAD ciphers, which need no separate MAC. */
+  mac: Directional<string | null>;
+  compression: Directional;
 }
 
 export async function sshConnect(params: SshConnectParams): Promise<string> {
@@ -54,12 +104,40 @@ export async function sshConnect(params: SshConnectParams): Promise<string> {
     password: params.password,
     keyPath: params.keyPath,
     keyPassphrase: params.keyPassphrase,
+    keyContent: params.keyContent ?? null,
+    certificate: params.certificate ?? null,
     cols: params.cols,
     rows: params.rows,
     jumpChain: params.jumpChain ?? null,
     proxy: params.proxy ?? null,
     shell: params.shell?.trim() ? params.shell.trim() : null,
     injectColors: params.injectColors ?? null,
+    keepalive: params.keepalive ?? null,
+    crypto: params.crypto ?? null,
+    agentForwarding: params.agentForwarding ?? null,
+    identitiesOnly: params.identitiesOnly ?? null,
+    env: params.env ?? null,
+    requestTty: params.requestTty ?? null,
+    record: params.record ?? null,
+    encoding: params.encoding ?? null,
+  });
+}
+
+/** Connect to a saved session by ID. Credentials (including jump hosts) are
+ *  resolved from the vault in the backend and never pass through the webview. */
+export async function sshConnectSession(
+  sessionId: string,
+  cols: number,
+  rows: number,
+  id?: string,
+  injectColors?: boolean
+): Promise<string> {
+  return invoke<string>('ssh_connect_session', {
+    sessionId,
+    cols,
+    rows,
+    id: id ?? null,
+    injectColors: injectColors ?? null,
   });
 }
 
@@ -67,10 +145,88 @@ export async function sshSend(connectionId: string, data: number[]): Promise<voi
   return invoke('ssh_send', { connectionId, data });
 }
 
-/** Signal the backend that this terminal's data listener is attached, so it
- *  flushes buffered output (motd/banner) emitted before mount. */
-export async function sshReady(connectionId: string): Promise<void> {
-  return invoke('ssh_ready', { connectionId });
+/** Signal the backend that this terminal is ready for output on `onData`
+ *  (see `openTerminalOutput`), so it flushes buffered output (motd/banner)
+ *  produced before mount. */
+export async function sshReady(connectionId: string, onData: Channel<ArrayBuffer>): Promise<void> {
+  return invoke('ssh_ready', { connectionId, onData });
+}
+
+/** Attach to a running connection (a reopened tab, or after a window reload):
+ *  its scrollback is replayed on `onData`, then output streams live. */
+export async function sshAttach(connectionId: string, onData: Channel<ArrayBuffer>): Promise<void> {
+  return invoke('ssh_attach', { connectionId, onData });
+}
+
+/** Stop streaming a connection's output but leave it running; output keeps
+ *  collecting in the backend scrollback until something attaches again. */
+export async function sshDetach(connectionId: string): Promise<void> {
+  return invoke('ssh_detach', { connectionId });
+}
+
+/** Events on `inband-transfer-{connectionId}`: an `rz`/`sz` (ZMODEM) or
+ *  `trz`/`tsz` (trzsz) transfer run inside the terminal. */
+export type InbandTransferEvent =
+  | { kind: 'request'; protocol: 'zmodem' | 'trzsz'; direction: 'upload' | 'download' }
+  | {
+      kind: 'file';
+      transferId: string;
+      filename: string;
+      totalBytes: number;
+      direction: 'upload' | 'download';
+    }
+  | { kind: 'finished'; ok: boolean; error: string | null; files: string[] };
+
+/** Answer a transfer request: files to upload, or one folder to download
+ *  into. `null` turns it down. */
+export async function sshTransferRespond(connectionId: string, paths: string[] | null): Promise<void> {
+  return invoke('ssh_transfer_respond', { connectionId, paths });
+}
+
+export interface ScrollbackMatch {
+  /** Line number, counted from the oldest line the backend still keeps. */
+  line: number;
+  /** Character offset of the match within the line. */
+  column: number;
+  /** The whole line, escape sequences removed. */
+  text: string;
+}
+
+/** Search a connection's backend scrollback (case-insensitive by default). */
+export async function sshScrollbackSearch(
+  connectionId: string,
+  query: string,
+  caseSensitive?: boolean,
+  limit?: number,
+): Promise<ScrollbackMatch[]> {
+  return invoke<ScrollbackMatch[]>('ssh_scrollback_search', {
+    connectionId,
+    query,
+    caseSensitive: caseSensitive ?? null,
+    limit: limit ?? null,
+  });
+}
+
+/**
+ * Open another shell over an existing connection's SSH session — no new
+ * handshake or login. Resolves to the new connection id.
+ */
+export async function sshOpenChannel(
+  connectionId: string,
+  cols: number,
+  rows: number,
+  id?: string,
+  shell?: string,
+  injectColors?: boolean
+): Promise<string> {
+  return invoke<string>('ssh_open_channel', {
+    connectionId,
+    cols,
+    rows,
+    id: id ?? null,
+    shell: shell ?? null,
+    injectColors: injectColors ?? null
+  });
 }
 
 /** Host-key verification request emitted by the backend (`ssh-hostkey-prompt`). */
@@ -83,6 +239,8 @@ export interface HostKeyPrompt {
   /** true = the stored key changed (possible MITM); false = unknown host (TOFU). */
   changed: boolean;
   oldFingerprint?: string | null;
+  /** Extra context: an algorithm downgrade, or that a trusted host CA covers the host but can't be checked while connecting. */
+  note?: string | null;
 }
 
 /** Report the user's accept/reject decision for a host-key prompt. */
@@ -90,6 +248,82 @@ export async function sshHostkeyResponse(promptId: string, accept: boolean): Pro
   return invoke('ssh_hostkey_response', { promptId, accept });
 }
 
+/** A forwarded-agent signature request awaiting confirmation (`ssh-agent-confirm`). */
+export interface AgentConfirmPrompt {
+  promptId: string;
+  /** Remote host using a forwarded agent; absent for a local program using the built-in agent. */
+  host?: string;
+  /** Vault name of the key (built-in agent only). */
+  keyName?: string;
+  keyType: string;
+  fingerprint: string;
+}
+
+/** Allow or refuse one agent signature request. */
+export async function sshAgentConfirmResponse(promptId: string, allow: boolean): Promise<void> {
+  return invoke('ssh_agent_confirm_response', { promptId, allow });
+}
+
+/** How the built-in agent offers one vault key. */
+export interface AgentKeyOptions {
+  disabled?: boolean;
+  confirm?: boolean;
+  lifetimeSecs?: number;
+}
+
+export interface AgentKeyInfo {
+  secretId: string;
+  vaultId: string;
+  name: string;
+  keyType?: string;
+  fingerprint?: string;
+  /** Why the key can't be served (e.g. passphrase-protected). */
+  error?: string;
+  expired: boolean;
+  expiresInSecs?: number;
+  options: AgentKeyOptions;
+}
+
+export interface AgentStatus {
+  supported: boolean;
+  enabled: boolean;
+  socketPath?: string;
+  locked: boolean;
+  keys: AgentKeyInfo[];
+}
+
+/** State of the built-in SSH agent and the vault keys it can serve. */
+export async function sshAgentStatus(): Promise<AgentStatus> {
+  return invoke<AgentStatus>('ssh_agent_status');
+}
+
+/** Turn the built-in SSH agent on or off (applies to terminals opened afterwards). */
+export async function sshAgentSetEnabled(enabled: boolean): Promise<void> {
+  return invoke('ssh_agent_set_enabled', { enabled });
+}
+
+export async function sshAgentSetKeyOptions(secretId: string, options: AgentKeyOptions): Promise<void> {
+  return invoke('ssh_agent_set_key_options', { secretId, options });
+}
+
+/** Keyboard-interactive (2FA / PAM) request emitted by the backend (`ssh-auth-prompt`)
+ *  for prompts it could not answer from saved credentials. */
+export interface AuthPrompt {
+  promptId: string;
+  host: string;
+  port: number;
+  username: string;
+  name: string;
+  instructions: string;
+  /** `echo: false` = secret input. */
+  prompts: { prompt: string; echo: boolean }[];
+}
+
+/** Answer a keyboard-interactive prompt (one response per prompt), or `null` to cancel. */
+export async function sshAuthPromptResponse(promptId: string, responses: string[] | null): Promise<void> {
+  return invoke('ssh_auth_prompt_response', { promptId, responses });
+}
+
 export async function sshResize(connectionId: string, cols: number, rows: number): Promise<void> {
   return invoke('ssh_resize', { connectionId, cols, rows });
 }
@@ -106,7 +340,23 @@ export async function sshDetectOs(connectionId: string): Promise<string> {
   return invoke<string>('ssh_detect_os', { connectionId });
 }
 
-export type KeyFileKind = 'private_key' | 'public_key' | 'not_a_key' | 'not_found';
+export type KeyFileKind = 'private_key' | 'public_key' | 'certificate' | 'not_a_key' | 'not_found';
+
+/** An OpenSSH certificate: the inspected file itself, or a private key's `-cert.pub`. */
+export interface CertificateInfo {
+  path?: string | null;
+  certType: 'user' | 'host';
+  algo: string;
+  keyId: string;
+  /** Empty = valid for any principal. */
+  principals: string[];
+  /** Unix seconds; validBefore at u64 max means no expiry. */
+  validAfter: number;
+  validBefore: number;
+  expired: boolean;
+  notYetValid: boolean;
+  caFingerprint: string;
+}
 
 export interface KeyCandidate {
   path: string;
@@ -122,6 +372,7 @@ export interface KeyFileInfo {
   encrypted: boolean;
   suggestedPrivateKey?: KeyCandidate | null;
   siblingPrivateKeys: KeyCandidate[];
+  certificate?: CertificateInfo | null;
 }
 
 /** Inspect a key-file path to detect wrong-file selections (e.g. a public key)
@@ -129,3 +380,62 @@ export interface KeyFileInfo {
 export async function inspectKeyFile(path: string): Promise<KeyFileInfo> {
   return invoke<KeyFileInfo>('inspect_key_file', { path });
 }
+
+export type KeyAlgorithm = 'ed25519' | 'ecdsa_p256' | 'ecdsa_p384' | 'ecdsa_p521' | 'rsa';
+
+/** Where a generated key goes: a vault secret, or `path` + `path.pub` on disk. */
+export type KeyDestination =
+  | { type: 'Vault'; vault_id: string; name: string }
+  | { type: 'File'; path: string; passphrase?: string };
+
+/** Where to read a private key from when exporting its public half. */
+export type PrivateKeySource =
+  | { type: 'File'; path: string; passphrase?: string }
+  | { type: 'Vault'; vault_id: string; secret_id: string }
+  | { type: 'Content'; content: string; passphrase?: string };
+
+export interface PublicKeyInfo {
+  /** e.g. `ssh-ed25519`. */
+  algorithm: string;
+  /** The `authorized_keys` line. */
+  openssh: string;
+  /** `SHA256:...` */
+  fingerprint: string;
+  comment?: string;
+}
+
+export interface GeneratedKey {
+  public: PublicKeyInfo;
+  secretId?: string;
+  path?: string;
+}
+
+export type DeployOutcome = 'added' | 'already_present';
+
+/** Generate an SSH keypair. `rsaBits` (2048/3072/4096) only applies to RSA. */
+export async function sshKeyGenerate(
+  algorithm: KeyAlgorithm,
+  destination: KeyDestination,
+  comment?: string,
+  rsaBits?: number
+): Promise<GeneratedKey> {
+  return invoke<GeneratedKey>('ssh_key_generate', { algorithm, rsaBits, comment, destination });
+}
+
+/** OpenSSH public key and fingerprint of a private key. */
+export async function sshKeyPublic(source: PrivateKeySource): Promise<PublicKeyInfo> {
+  return invoke<PublicKeyInfo>('ssh_key_public', { source });
+}
+
+/** Convert a PuTTY `.ppk` key to OpenSSH format and store it. */
+export async function sshKeyImport(
+  source: PrivateKeySource,
+  destination: KeyDestination
+): Promise<GeneratedKey> {
+  return invoke<GeneratedKey>('ssh_key_import', { source, destination });
+}
+
+/** Add a public key to `~/.ssh/authorized_keys` on a connected host (idempotent). */
+export async function sshKeyDeploy(connectionId: string, publicKey: string): Promise<DeployOutcome> {
+  return invoke<DeployOutcome>('ssh_key_deploy', { connectionId, publicKey });
+}
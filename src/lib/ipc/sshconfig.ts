@@ -1,4 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
+import type { RequestTty } from './sessions';
+import type { TunnelConfig } from './tunnel';
 
 export interface JumpHostEntry {
   host: string;
@@ -14,6 +16,14 @@ export interface SshHostEntry {
   user: string;
   identity_files: string[];
   proxy_jump: JumpHostEntry[];
+  proxy_command: string | null; // ProxyCommand, %h/%p/%r unexpanded
+  identities_only: boolean;
+  forwards: TunnelConfig[]; // Local/Remote/DynamicForward, inactive
+  server_alive_interval: number | null;
+  server_alive_count_max: number | null;
+  forward_agent: boolean;
+  set_env: Record<string, string>;
+  request_tty: RequestTty | null;
 }
 
 /** List all named hosts from ~/.ssh/config. */
@@ -30,3 +40,18 @@ export async function sshconfigResolveHost(hostname: string): Promise<SshHostEnt
 export async function sshconfigExists(): Promise<boolean> {
   return invoke<boolean>('sshconfig_exists');
 }
+
+export interface SshConfigExportFilter {
+  folder_id: string | null;
+  tag: string | null;
+}
+
+/** Render saved sessions as an ssh_config fragment. Never contains secrets. */
+export async function sshconfigExport(filter: SshConfigExportFilter): Promise<string> {
+  return invoke<string>('sshconfig_export', { filter });
+}
+
+/** Write the fragment to ~/.ssh/reach.conf, optionally adding an Include to ~/.ssh/config. Returns the written path. */
+export async function sshconfigExportWrite(filter: SshConfigExportFilter, addInclude: boolean): Promise<string> {
+  return invoke<string>('sshconfig_export_write', { filter, addInclude });
+}
@@ -0,0 +1,48 @@
+import { Channel, invoke } from '@tauri-apps/api/core';
+
+/** Rendered output is acknowledged in steps of this many bytes. */
+const ACK_STEP = 64 * 1024;
+
+export interface TerminalOutput {
+	/** Pass to `ptyAttach`, `sshReady` or `sshAttach`. */
+	channel: Channel<ArrayBuffer>;
+	/** Stop handling output; later batches are dropped. */
+	close: () => void;
+}
+
+/**
+ * A channel for terminal `id`'s output (SSH connection id, PTY id or serial
+ * port name). Batches arrive as raw bytes; `write` hands each to xterm.js and
+ * calls `rendered` once it's parsed, which is acknowledged to the backend so
+ * it keeps sending.
+ */
+export function openTerminalOutput(
+	id: string,
+	write: (data: Uint8Array, rendered: () => void) => void,
+): TerminalOutput {
+	const channel = new Channel<ArrayBuffer>();
+	let open = true;
+	let unacked = 0;
+	channel.onmessage = (message) => {
+		if (!open) return;
+		const data = message instanceof ArrayBuffer ? new Uint8Array(message) : new Uint8Array(message as ArrayLike<number>);
+		write(data, () => {
+			unacked += data.length;
+			if (unacked >= ACK_STEP) {
+				const bytes = unacked;
+				unacked = 0;
+				streamAck(id, bytes).catch(() => {});
+			}
+		});
+	};
+	return {
+		channel,
+		close: () => {
+			open = false;
+		},
+	};
+}
+
+export async function streamAck(id: string, bytes: number): Promise<void> {
+	return invoke('stream_ack', { id, bytes });
+}
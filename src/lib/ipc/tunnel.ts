@@ -8,6 +8,8 @@ export interface TunnelConfig {
   remote_port: number;
   connection_id: string;
   active: boolean;
+  /** Bind address (Local/Dynamic) or relay target (Remote). Defaults to 127.0.0.1. */
+  local_host?: string;
 }
 
 export async function tunnelCreate(
@@ -15,9 +17,17 @@ export async function tunnelCreate(
   localPort: number,
   remoteHost: string,
   remotePort: number,
-  connectionId: string
+  connectionId: string,
+  localHost?: string
 ): Promise<TunnelConfig> {
-  return invoke<TunnelConfig>('tunnel_create', { tunnelType, localPort, remoteHost, remotePort, connectionId });
+  return invoke<TunnelConfig>('tunnel_create', {
+    tunnelType,
+    localPort,
+    remoteHost,
+    remotePort,
+    connectionId,
+    localHost: localHost ?? null,
+  });
 }
 
 export async function tunnelStart(tunnelId: string): Promise<void> {
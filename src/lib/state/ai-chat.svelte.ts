@@ -1,7 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { getAISettings } from './ai.svelte';
-import { readTerminalBuffer } from './terminal-buffer.svelte';
+import { readTerminalBuffer, subscribeTerminalOutput } from './terminal-buffer.svelte';
 import { sshSend } from '$lib/ipc/ssh';
 import { ptyWrite } from '$lib/ipc/pty';
 
@@ -89,19 +88,6 @@ function extractCodeBlocks(content: string): string[] {
 	return blocks;
 }
 
-function decodePayload(payload: unknown): string {
-	if (payload instanceof Uint8Array) {
-		return new TextDecoder().decode(payload);
-	}
-	if (Array.isArray(payload)) {
-		return new TextDecoder().decode(new Uint8Array(payload));
-	}
-	if (typeof payload === 'string') {
-		return payload;
-	}
-	return '';
-}
-
 // Strip ANSI escape codes for cleaner output
 function stripAnsi(str: string): string {
 	return str.replace(/\x1b\[[0-9;]*[a-zA-Z]/g, '').replace(/\x1b\][^\x07]*\x07/g, '').replace(/\r/g, '');
@@ -114,16 +100,14 @@ async function captureCommandOutput(
 	const eventId = terminal.tabType === 'ssh' && terminal.connectionId
 		? terminal.connectionId
 		: terminal.bufferId;
-	const eventName = terminal.tabType === 'ssh'
-		? `ssh-data-${eventId}`
-		: `pty-data-${eventId}`;
 
-	// Start capturing data events BEFORE sending the command
+	// Start capturing output BEFORE sending the command. A streaming decoder
+	// keeps characters split across batches intact.
 	const chunks: string[] = [];
-	let unlisten: UnlistenFn | undefined;
+	const decoder = new TextDecoder();
 
-	unlisten = await listen<unknown>(eventName, (event) => {
-		const text = decodePayload(event.payload);
+	const unsubscribe = subscribeTerminalOutput(eventId, (data) => {
+		const text = decoder.decode(data, { stream: true });
 		if (text) chunks.push(text);
 	});
 
@@ -153,7 +137,7 @@ async function captureCommandOutput(
 	}
 
 	// Stop listening
-	unlisten?.();
+	unsubscribe();
 
 	// Combine all captured data
 	const rawOutput = chunks.join('');
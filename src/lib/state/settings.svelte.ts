@@ -1,6 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
 import * as settingsIpc from '$lib/ipc/settings';
 import type { AppSettings } from '$lib/ipc/settings';
+import { recordingSetConfig } from '$lib/ipc/recording';
 
 export interface Settings {
 	theme: 'dark' | 'light' | 'system';
@@ -12,6 +13,10 @@ export interface Settings {
 	minimizeToTray: boolean;
 	startWithSystem: boolean;
 	injectShellColors: boolean;
+	recordSessions: boolean;
+	recordInput: boolean;
+	recordingDir: string;
+	recordingRetentionDays: number;
 	setupComplete: boolean;
 	pendingTursoOrg: string;
 	pendingTursoApiToken: string;
@@ -36,6 +41,10 @@ const defaults: Settings = {
 	minimizeToTray: false,
 	startWithSystem: false,
 	injectShellColors: true,
+	recordSessions: false,
+	recordInput: false,
+	recordingDir: '',
+	recordingRetentionDays: 0,
 	setupComplete: false,
 	pendingTursoOrg: '',
 	pendingTursoApiToken: ''
@@ -78,6 +87,10 @@ export function loadSettings(): void {
 			settings.minimizeToTray = parsed.minimizeToTray ?? defaults.minimizeToTray;
 			settings.startWithSystem = parsed.startWithSystem ?? defaults.startWithSystem;
 			settings.injectShellColors = parsed.injectShellColors ?? defaults.injectShellColors;
+			settings.recordSessions = parsed.recordSessions ?? defaults.recordSessions;
+			settings.recordInput = parsed.recordInput ?? defaults.recordInput;
+			settings.recordingDir = parsed.recordingDir ?? defaults.recordingDir;
+			settings.recordingRetentionDays = parsed.recordingRetentionDays ?? defaults.recordingRetentionDays;
 			settings.pendingTursoOrg = parsed.pendingTursoOrg ?? defaults.pendingTursoOrg;
 			settings.pendingTursoApiToken = parsed.pendingTursoApiToken ?? defaults.pendingTursoApiToken;
 			// Migration: existing users who already have localStorage data get setupComplete: true
@@ -186,6 +199,20 @@ export async function syncTraySettings(): Promise<void> {
 	}
 }
 
+/** Push the recording settings to the backend, which does the recording. Call after loadSettings(). */
+export async function syncRecordingSettings(): Promise<void> {
+	try {
+		await recordingSetConfig({
+			record_all: settings.recordSessions,
+			record_input: settings.recordInput,
+			directory: settings.recordingDir.trim() || null,
+			retention_days: settings.recordingRetentionDays > 0 ? settings.recordingRetentionDays : null,
+		});
+	} catch {
+		// Backend not ready yet
+	}
+}
+
 /** Restore local settings from vault AppSettings (after backup import + relaunch). */
 export async function restoreLocalSettingsFromVault(): Promise<void> {
 	try {
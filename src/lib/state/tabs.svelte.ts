@@ -14,6 +14,10 @@ export interface Tab {
 	detectedOs?: string | null;
 	/** SSH connect params for reconnection */
 	sshConnectParams?: SshConnectParams;
+	/** Saved session ID; when set, reconnection resolves credentials in the backend */
+	sshSessionId?: string;
+	/** Opened on an already running connection: replay its backend scrollback */
+	attach?: boolean;
 }
 
 let tabs = $state<Tab[]>([]);
@@ -42,3 +42,27 @@ export function readTerminalBufferFrom(id: string, startLine: number, maxLines:
 		return '';
 	}
 }
+
+type OutputListener = (data: Uint8Array) => void;
+
+const outputListeners = new Map<string, Set<OutputListener>>();
+
+/** Watch the raw output a terminal view renders. Returns the unsubscribe. */
+export function subscribeTerminalOutput(id: string, listener: OutputListener): () => void {
+	let listeners = outputListeners.get(id);
+	if (!listeners) {
+		listeners = new Set();
+		outputListeners.set(id, listeners);
+	}
+	listeners.add(listener);
+	return () => {
+		listeners.delete(listener);
+		if (listeners.size === 0) outputListeners.delete(id);
+	};
+}
+
+export function publishTerminalOutput(id: string, data: Uint8Array): void {
+	const listeners = outputListeners.get(id);
+	if (!listeners) return;
+	for (const listener of listeners) listener(data);
+}
@@ -1,5 +1,6 @@
 import { SvelteMap } from 'svelte/reactivity';
 import * as vaultIpc from '$lib/ipc/vault';
+import { sshKeyGenerate, sshKeyImport, sshKeyPublic, type KeyAlgorithm, type PublicKeyInfo } from '$lib/ipc/ssh';
 import { restoreLocalSettingsFromVault } from '$lib/state/settings.svelte';
 import type {
 	VaultInfo,
@@ -290,6 +291,43 @@ export async function createSecret(
 	return secretId;
 }
 
+/** Generate an SSH key straight into the active vault. */
+export async function generateSshKey(
+	name: string,
+	algorithm: KeyAlgorithm,
+	comment?: string
+): Promise<PublicKeyInfo> {
+	if (!vaultState.activeVaultId) throw new Error('No active vault');
+	const generated = await sshKeyGenerate(
+		algorithm,
+		{ type: 'Vault', vault_id: vaultState.activeVaultId, name },
+		comment
+	);
+	await refreshSecrets();
+	return generated.public;
+}
+
+/** Import a PuTTY `.ppk` file into the active vault as an OpenSSH key. */
+export async function importPuttyKey(
+	name: string,
+	path: string,
+	passphrase?: string
+): Promise<PublicKeyInfo> {
+	if (!vaultState.activeVaultId) throw new Error('No active vault');
+	const imported = await sshKeyImport(
+		{ type: 'File', path, passphrase },
+		{ type: 'Vault', vault_id: vaultState.activeVaultId, name }
+	);
+	await refreshSecrets();
+	return imported.public;
+}
+
+/** Public half of an SSH key secret in the active vault. */
+export async function readSshPublicKey(secretId: string): Promise<PublicKeyInfo> {
+	if (!vaultState.activeVaultId) throw new Error('No active vault');
+	return sshKeyPublic({ type: 'Vault', vault_id: vaultState.activeVaultId, secret_id: secretId });
+}
+
 export async function readSecret(secretId: string): Promise<string> {
 	if (!vaultState.activeVaultId) throw new Error('No active vault');
 	return vaultIpc.readSecret(vaultState.activeVaultId, secretId);